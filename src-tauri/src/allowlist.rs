@@ -0,0 +1,207 @@
+// User overrides for files the user says are clean.
+//
+// A false-positive report goes to the threat API and the hash is allowlisted
+// locally so scans and the realtime watcher stop flagging it. Revoking the
+// override removes the hash again; the backend report is not withdrawn.
+
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+
+use crate::{
+    app_data_dir, build_client_payload, check_session, hashing, http_client,
+    notify_api_error_state, onaccess, pinning, session_token, ThreatApiClient, API_BASE_URL,
+};
+
+const API_FALSE_POSITIVE_PATH: &str = "/api/av/v1/false-positive/report";
+
+// Serializes read-modify-write of allowlist.json.
+static ALLOWLIST_LOCK: Mutex<()> = Mutex::new(());
+// Allowlisted hashes, read once and replaced whenever allowlist.json is saved.
+// Every realtime scan checks it.
+static CACHED_HASHES: Mutex<Option<HashSet<String>>> = Mutex::new(None);
+
+#[derive(Serialize, Deserialize, Clone)]
+pub(crate) struct AllowlistEntry {
+    sha256: String,
+    path: String,
+    note: Option<String>,
+    added_at: u64,
+    /// Whether the backend accepted the false-positive report.
+    reported: bool,
+}
+
+#[derive(Serialize)]
+struct FalsePositiveReport {
+    client: ThreatApiClient,
+    sha256: String,
+    file_name: Option<String>,
+    note: Option<String>,
+}
+
+fn allowlist_path() -> PathBuf {
+    app_data_dir().join("allowlist.json")
+}
+
+fn load_entries() -> Vec<AllowlistEntry> {
+    fs::read(allowlist_path())
+        .ok()
+        .and_then(|b| serde_json::from_slice(&b).ok())
+        .unwrap_or_default()
+}
+
+fn save_entries(items: &[AllowlistEntry]) {
+    let p = allowlist_path();
+    if let Some(parent) = p.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    if let Ok(json) = serde_json::to_vec_pretty(items) {
+        let _ = fs::write(p, json);
+    }
+    if let Ok(mut cache) = CACHED_HASHES.lock() {
+        *cache = Some(items.iter().map(|e| e.sha256.clone()).collect());
+    }
+}
+
+fn with_hashes<T>(f: impl FnOnce(&HashSet<String>) -> T) -> T {
+    let load = || load_entries().into_iter().map(|e| e.sha256).collect();
+    match CACHED_HASHES.lock() {
+        Ok(mut cache) => f(cache.get_or_insert_with(load)),
+        Err(_) => f(&load()),
+    }
+}
+
+/// Lowercase hashes the user has overridden as clean.
+pub(crate) fn allowlisted_hashes() -> HashSet<String> {
+    with_hashes(HashSet::clone)
+}
+
+pub(crate) fn is_allowlisted(sha256: &str) -> bool {
+    let sha256 = sha256.to_lowercase();
+    with_hashes(|hashes| hashes.contains(&sha256))
+}
+
+fn send_report(report: &FalsePositiveReport) -> Result<(), String> {
+    let url = format!("{}{}", API_BASE_URL, API_FALSE_POSITIVE_PATH);
+    let client = http_client()?;
+
+    let token = session_token();
+    let mut builder = client.post(&url).json(report);
+    if let Some(t) = &token {
+        builder = builder.bearer_auth(t);
+    }
+
+    let resp = builder.send().map_err(|e| {
+        pinning::pin_failure(&e).unwrap_or_else(|| format!("False-positive report error: {e}"))
+    })?;
+
+    let status = resp.status();
+    check_session(status, token.is_some())?;
+    if !status.is_success() {
+        return Err(format!("False-positive report returned HTTP {status}"));
+    }
+
+    Ok(())
+}
+
+// ---- Commands ----
+
+/// Allowlists `hash` locally and reports it to the backend. The override is
+/// kept even if the report can't be delivered (offline, API down).
+#[tauri::command]
+pub(crate) async fn report_false_positive(
+    app: AppHandle,
+    hash: String,
+    path: String,
+    note: Option<String>,
+) -> Result<AllowlistEntry, String> {
+    // The UI may only know the path (e.g. right after a restore)
+    let sha256 = match hash.trim() {
+        "" => {
+            let file = PathBuf::from(&path);
+            tauri::async_runtime::spawn_blocking(move || hashing::hash_file(&file))
+                .await
+                .map_err(|e| format!("Hashing task failed: {e}"))?
+                .ok_or_else(|| format!("Failed to hash {path}"))?
+                .sha256
+        }
+        h => h.to_lowercase(),
+    };
+    if sha256.len() != 64 || !sha256.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err("Invalid SHA-256 hash".to_string());
+    }
+
+    let note = note.filter(|n| !n.trim().is_empty());
+    let report = FalsePositiveReport {
+        client: build_client_payload(),
+        sha256: sha256.clone(),
+        file_name: Path::new(&path)
+            .file_name()
+            .map(|s| s.to_string_lossy().to_string()),
+        note: note.clone(),
+    };
+
+    let sent = tauri::async_runtime::spawn_blocking(move || send_report(&report))
+        .await
+        .map_err(|e| format!("False-positive report task failed: {e}"))?;
+
+    let reported = match sent {
+        Ok(()) => true,
+        Err(e) => {
+            eprintln!("[FP] report failed for {}: {e}", sha256);
+            notify_api_error_state(&app, &e);
+            false
+        }
+    };
+
+    let entry = AllowlistEntry {
+        sha256: sha256.clone(),
+        path,
+        note,
+        added_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0),
+        reported,
+    };
+
+    let _guard = ALLOWLIST_LOCK.lock();
+    let mut entries = load_entries();
+    entries.retain(|e| e.sha256 != sha256);
+    entries.push(entry.clone());
+    save_entries(&entries);
+    onaccess::forget(&sha256);
+
+    println!("[FP] allowlisted {} (reported={})", sha256, reported);
+    Ok(entry)
+}
+
+#[tauri::command]
+pub(crate) fn revoke_false_positive(hash: String) -> Result<(), String> {
+    let sha256 = hash.trim().to_lowercase();
+
+    let _guard = ALLOWLIST_LOCK.lock();
+    let mut entries = load_entries();
+    let before = entries.len();
+    entries.retain(|e| e.sha256 != sha256);
+
+    if entries.len() == before {
+        return Err(format!("Hash {sha256} is not allowlisted"));
+    }
+
+    save_entries(&entries);
+    println!("[FP] allowlist override revoked for {}", sha256);
+    Ok(())
+}
+
+#[tauri::command]
+pub(crate) fn get_allowlist() -> Vec<AllowlistEntry> {
+    load_entries()
+}
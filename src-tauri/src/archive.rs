@@ -0,0 +1,395 @@
+// Archive descent for scans: ZIP, TAR and gzip (including .tar.gz).
+//
+// Members are handed to the caller in memory with a virtual path of the form
+// `archive.zip!inner/payload.exe` (nested: `a.zip!b.tar!c.exe`). Every read is
+// capped so a zip bomb costs at most the configured budget, never the disk or
+// the heap.
+
+use std::{
+    fs,
+    io::{Cursor, Read, Seek},
+    path::Path,
+};
+
+use flate2::read::GzDecoder;
+
+use crate::filetype::{self, FileType};
+
+pub(crate) struct Limits {
+    /// Containers inside containers; the file on disk is depth 1.
+    pub(crate) max_depth: usize,
+    pub(crate) max_members: usize,
+    /// Decompressed bytes across all members of one archive on disk.
+    pub(crate) max_expanded_bytes: u64,
+    pub(crate) max_member_bytes: u64,
+}
+
+pub(crate) const SCAN_LIMITS: Limits = Limits {
+    max_depth: 3,
+    max_members: 2000,
+    max_expanded_bytes: 256 * 1024 * 1024, // 256 MB
+    max_member_bytes: 32 * 1024 * 1024,    // 32 MB
+};
+
+pub(crate) struct Member<'a> {
+    pub(crate) path: String,
+    pub(crate) data: &'a [u8],
+}
+
+/// Totals for one walk; `truncated` means a limit cut it short.
+#[derive(Default)]
+pub(crate) struct WalkSummary {
+    pub(crate) members: usize,
+    pub(crate) expanded_bytes: u64,
+    pub(crate) truncated: bool,
+}
+
+struct Walker<'l, F> {
+    limits: &'l Limits,
+    summary: WalkSummary,
+    visit: F,
+}
+
+/// Formats we descend into. Jar/APK/OOXML are zips too but are judged whole.
+pub(crate) fn is_container(file_type: FileType) -> bool {
+    matches!(file_type, FileType::Zip | FileType::Tar | FileType::Gzip)
+}
+
+impl<F: FnMut(Member)> Walker<'_, F> {
+    fn budget_left(&mut self) -> Option<u64> {
+        if self.summary.members >= self.limits.max_members
+            || self.summary.expanded_bytes >= self.limits.max_expanded_bytes
+        {
+            self.summary.truncated = true;
+            return None;
+        }
+        Some(
+            self.limits
+                .max_member_bytes
+                .min(self.limits.max_expanded_bytes - self.summary.expanded_bytes),
+        )
+    }
+
+    /// Reads one member within budget. `None` = over a limit or unreadable.
+    fn read_member(&mut self, reader: impl Read) -> Option<Vec<u8>> {
+        let budget = self.budget_left()?;
+
+        let mut data = Vec::new();
+        // One byte past the budget tells "exactly at the limit" from "over it"
+        reader.take(budget + 1).read_to_end(&mut data).ok()?;
+
+        self.summary.members += 1;
+        self.summary.expanded_bytes += data.len() as u64;
+        if data.len() as u64 > budget {
+            self.summary.truncated = true;
+            return None;
+        }
+        Some(data)
+    }
+
+    fn member(&mut self, path: String, data: &[u8], depth: usize) {
+        (self.visit)(Member {
+            path: path.clone(),
+            data,
+        });
+
+        let inner_type = filetype::sniff(data);
+        if is_container(inner_type) {
+            if depth < self.limits.max_depth {
+                self.container(Cursor::new(data), inner_type, &path, depth + 1);
+            } else {
+                self.summary.truncated = true;
+            }
+        }
+    }
+
+    fn container<R: Read + Seek>(
+        &mut self,
+        reader: R,
+        file_type: FileType,
+        path: &str,
+        depth: usize,
+    ) {
+        match file_type {
+            FileType::Zip => self.zip(reader, path, depth),
+            FileType::Tar => self.tar(reader, path, depth),
+            FileType::Gzip => self.gzip(reader, path, depth),
+            _ => {}
+        }
+    }
+
+    fn zip<R: Read + Seek>(&mut self, reader: R, path: &str, depth: usize) {
+        let Ok(mut archive) = zip::ZipArchive::new(reader) else {
+            return;
+        };
+
+        for i in 0..archive.len() {
+            let Ok(entry) = archive.by_index(i) else {
+                continue;
+            };
+            // Encrypted members can't be read without the password
+            if entry.is_dir() || entry.encrypted() {
+                continue;
+            }
+
+            let name = format!("{path}!{}", entry.name());
+            let Some(data) = self.read_member(entry) else {
+                if self.summary.truncated {
+                    return;
+                }
+                continue;
+            };
+            self.member(name, &data, depth);
+        }
+    }
+
+    fn tar<R: Read>(&mut self, reader: R, path: &str, depth: usize) {
+        let mut archive = tar::Archive::new(reader);
+        let Ok(entries) = archive.entries() else {
+            return;
+        };
+
+        for entry in entries {
+            let Ok(entry) = entry else {
+                // A corrupt header ends the stream; there's no resync in tar
+                return;
+            };
+            // Links and devices have no content of their own
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+
+            let name = match entry.path() {
+                Ok(p) => format!("{path}!{}", p.to_string_lossy()),
+                Err(_) => continue,
+            };
+            let Some(data) = self.read_member(entry) else {
+                if self.summary.truncated {
+                    return;
+                }
+                continue;
+            };
+            self.member(name, &data, depth);
+        }
+    }
+
+    fn gzip<R: Read>(&mut self, reader: R, path: &str, depth: usize) {
+        let mut decoder = GzDecoder::new(reader);
+
+        // gzip has one member; name it after the stored name or `x.tar.gz` -> `x.tar`
+        let inner_name = decoder
+            .header()
+            .and_then(|h| h.filename())
+            .map(|n| String::from_utf8_lossy(n).to_string())
+            .unwrap_or_else(|| {
+                let outer = path.rsplit(['/', '\\', '!']).next().unwrap_or(path);
+                let lower = outer.to_lowercase();
+                if lower.ends_with(".tgz") {
+                    format!("{}.tar", &outer[..outer.len() - 4])
+                } else if lower.ends_with(".gz") {
+                    outer[..outer.len() - 3].to_string()
+                } else {
+                    "(gzip)".to_string()
+                }
+            });
+
+        let Some(data) = self.read_member(&mut decoder) else {
+            return;
+        };
+        self.member(format!("{path}!{inner_name}"), &data, depth);
+    }
+}
+
+/// Walks every member of the archive at `path`, calling `visit` for each one
+/// (nested containers are visited and then descended into).
+pub(crate) fn walk(
+    path: &Path,
+    file_type: FileType,
+    limits: &Limits,
+    visit: impl FnMut(Member),
+) -> WalkSummary {
+    let mut walker = Walker {
+        limits,
+        summary: WalkSummary::default(),
+        visit,
+    };
+
+    if let Ok(file) = fs::File::open(path) {
+        let label = path.to_string_lossy().to_string();
+        walker.container(file, file_type, &label, 1);
+    }
+
+    walker.summary
+}
+
+/// `archive.zip!inner/payload.exe` -> `inner/payload.exe`, given the container path.
+pub(crate) fn member_name<'a>(virtual_path: &'a str, container: &str) -> &'a str {
+    virtual_path
+        .strip_prefix(container)
+        .and_then(|rest| rest.strip_prefix('!'))
+        .unwrap_or(virtual_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::{write::GzEncoder, Compression};
+    use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+    use super::*;
+
+    const SMALL: Limits = Limits {
+        max_depth: 3,
+        max_members: 10,
+        max_expanded_bytes: 64 * 1024,
+        max_member_bytes: 16 * 1024,
+    };
+
+    fn zip_of(members: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut w = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+        for (name, data) in members {
+            w.start_file(*name, options).unwrap();
+            w.write_all(data).unwrap();
+        }
+        w.finish().unwrap().into_inner()
+    }
+
+    fn tar_of(members: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut b = tar::Builder::new(Vec::new());
+        for (name, data) in members {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            b.append_data(&mut header, name, *data).unwrap();
+        }
+        b.into_inner().unwrap()
+    }
+
+    fn gzip_of(data: &[u8]) -> Vec<u8> {
+        let mut e = GzEncoder::new(Vec::new(), Compression::default());
+        e.write_all(data).unwrap();
+        e.finish().unwrap()
+    }
+
+    /// Walks `data` and returns (summary, visited paths), checking the budget.
+    fn walk_bytes(data: &[u8], file_type: FileType, limits: &Limits) -> (WalkSummary, Vec<String>) {
+        let mut seen: Vec<String> = Vec::new();
+        let mut walker = Walker {
+            limits,
+            summary: WalkSummary::default(),
+            visit: |m: Member| {
+                assert!(m.data.len() as u64 <= limits.max_member_bytes, "{}", m.path);
+                seen.push(m.path);
+            },
+        };
+        walker.container(Cursor::new(data), file_type, "t", 1);
+        let summary = walker.summary;
+        assert!(summary.members <= limits.max_members);
+        // The read that finds a limit exceeded goes one byte past it
+        assert!(summary.expanded_bytes <= limits.max_expanded_bytes + 1);
+        (summary, seen)
+    }
+
+    #[test]
+    fn limits_cut_walks_short() {
+        let zeros = vec![0u8; 1024 * 1024];
+        let kb = vec![7u8; 1024];
+        let many: Vec<(String, &[u8])> = (0..50).map(|i| (format!("f{i}"), &kb[..])).collect();
+        let many: Vec<(&str, &[u8])> = many.iter().map(|(n, d)| (n.as_str(), *d)).collect();
+        let large: Vec<(String, &[u8])> = (0..8)
+            .map(|i| (format!("l{i}"), &zeros[..15 * 1024]))
+            .collect();
+        let large: Vec<(&str, &[u8])> = large.iter().map(|(n, d)| (n.as_str(), *d)).collect();
+
+        // (case, archive, type, members visited, truncated)
+        let cases: Vec<(&str, Vec<u8>, FileType, usize, bool)> = vec![
+            (
+                "zip within limits",
+                zip_of(&many[..3]),
+                FileType::Zip,
+                3,
+                false,
+            ),
+            (
+                "zip bomb member",
+                zip_of(&[("bomb", &zeros)]),
+                FileType::Zip,
+                0,
+                true,
+            ),
+            ("zip member count", zip_of(&many), FileType::Zip, 10, true),
+            ("zip expanded total", zip_of(&large), FileType::Zip, 4, true),
+            (
+                "tar within limits",
+                tar_of(&many[..3]),
+                FileType::Tar,
+                3,
+                false,
+            ),
+            (
+                "tar oversized member",
+                tar_of(&[("big", &zeros)]),
+                FileType::Tar,
+                0,
+                true,
+            ),
+            ("tar member count", tar_of(&many), FileType::Tar, 10, true),
+            ("tar expanded total", tar_of(&large), FileType::Tar, 4, true),
+            ("gzip bomb", gzip_of(&zeros), FileType::Gzip, 0, true),
+        ];
+
+        for (name, data, file_type, visited, truncated) in &cases {
+            let (summary, seen) = walk_bytes(data, *file_type, &SMALL);
+            assert_eq!(seen.len(), *visited, "{name}");
+            assert_eq!(summary.truncated, *truncated, "{name}");
+        }
+    }
+
+    #[test]
+    fn nesting_stops_at_max_depth() {
+        let mut data = zip_of(&[("payload.exe", b"MZ")]);
+        for level in 0..4 {
+            let name = format!("level{level}.zip");
+            data = zip_of(&[(name.as_str(), &data)]);
+        }
+
+        let (summary, seen) = walk_bytes(&data, FileType::Zip, &SMALL);
+        assert!(summary.truncated);
+        assert_eq!(
+            seen,
+            [
+                "t!level3.zip",
+                "t!level3.zip!level2.zip",
+                "t!level3.zip!level2.zip!level1.zip"
+            ]
+        );
+    }
+
+    #[test]
+    fn truncated_and_lying_headers_are_handled() {
+        let kb = vec![7u8; 1024];
+        let members: &[(&str, &[u8])] = &[("a", &kb), ("b.tar", &tar_of(&[("c", &kb)]))];
+        for (data, file_type) in [
+            (zip_of(members), FileType::Zip),
+            (tar_of(members), FileType::Tar),
+            (gzip_of(&tar_of(members)), FileType::Gzip),
+        ] {
+            for len in (0..data.len()).step_by(7) {
+                walk_bytes(&data[..len], file_type, &SMALL);
+            }
+        }
+
+        // A tar header claiming 8 GB of content that isn't there
+        let mut data = tar_of(&[("huge", &kb)]);
+        data[124..136].copy_from_slice(b"77777777777\0");
+        let mut header = tar::Header::from_byte_slice(&data[..512]).clone();
+        header.set_cksum();
+        data[..512].copy_from_slice(header.as_bytes());
+        let (summary, seen) = walk_bytes(&data, FileType::Tar, &SMALL);
+        assert!(seen.len() <= 1);
+        assert!(summary.expanded_bytes <= data.len() as u64);
+    }
+}
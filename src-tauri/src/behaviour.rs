@@ -0,0 +1,412 @@
+// Ransomware behaviour: patterns across realtime events rather than verdicts
+// on single files.
+//
+// Per directory we keep a sliding window of writes and renames and count three
+// signals: renames from a known extension to an unknown one
+// (`report.docx` -> `report.docx.locked`), rewrites whose entropy jumps to
+// near-random, and the overall burst size. Busy but benign directories (builds,
+// unpacking, syncing) produce bursts without the other two signals, so a burst
+// alone never alerts.
+
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use crate::{entropy::EntropyProfile, filetype};
+
+const WINDOW: Duration = Duration::from_secs(10);
+// One alert per directory per cooldown; the user already knows
+const COOLDOWN: Duration = Duration::from_secs(60);
+
+const ENCRYPTED_REWRITES: usize = 3;
+const UNKNOWN_EXTENSION_RENAMES: usize = 5;
+const BURST_EVENTS: usize = 30;
+// With a burst, fewer signals are enough
+const BURST_SIGNALS: usize = 2;
+
+// Rewritten files below this were plausibly plain content before
+const LOW_ENTROPY: f64 = 7.0;
+const MAX_AFFECTED_PATHS: usize = 100;
+const MAX_TRACKED_FILES: usize = 8192;
+
+// Downloads and editors rename through these; not an "unknown" extension
+const TEMP_EXTENSIONS: &[&str] = &[
+    "tmp",
+    "temp",
+    "part",
+    "partial",
+    "crdownload",
+    "download",
+    "swp",
+    "bak",
+    "old",
+    "lock",
+];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Signal {
+    Write,
+    Rename,
+    UnknownExtension,
+    EncryptedRewrite,
+}
+
+impl Signal {
+    fn is_flag(self) -> bool {
+        matches!(self, Signal::UnknownExtension | Signal::EncryptedRewrite)
+    }
+}
+
+struct Mark {
+    at: Instant,
+    path: PathBuf,
+    signal: Signal,
+}
+
+#[derive(Default)]
+struct DirWindow {
+    marks: VecDeque<Mark>,
+    alerted_at: Option<Instant>,
+}
+
+pub(crate) struct RansomwareAlert {
+    pub(crate) directory: PathBuf,
+    pub(crate) paths: Vec<PathBuf>,
+    pub(crate) reason: String,
+}
+
+pub(crate) struct BehaviourMonitor {
+    dirs: HashMap<PathBuf, DirWindow>,
+    // Last whole-file entropy seen per path, to spot rewrites
+    entropy: HashMap<PathBuf, f64>,
+    last_rename: Option<(PathBuf, PathBuf)>,
+}
+
+fn extension(path: &Path) -> Option<String> {
+    path.extension()
+        .and_then(|s| s.to_str())
+        .map(|s| s.to_lowercase())
+}
+
+/// `report.docx` renamed to something no program would open.
+fn is_unknown_extension_rename(from: &Path, to: &Path) -> bool {
+    if !filetype::is_known_extension(from) || filetype::is_known_extension(to) {
+        return false;
+    }
+    match extension(to) {
+        Some(ext) => {
+            extension(from).as_ref() != Some(&ext) && !TEMP_EXTENSIONS.contains(&ext.as_str())
+        }
+        None => false,
+    }
+}
+
+impl BehaviourMonitor {
+    pub(crate) fn new() -> Self {
+        BehaviourMonitor {
+            dirs: HashMap::new(),
+            entropy: HashMap::new(),
+            last_rename: None,
+        }
+    }
+
+    /// A file was created or rewritten and hashed.
+    pub(crate) fn record_write(
+        &mut self,
+        path: &Path,
+        file_type: filetype::FileType,
+        profile: &EntropyProfile,
+        now: Instant,
+    ) -> Option<RansomwareAlert> {
+        if self.entropy.len() >= MAX_TRACKED_FILES {
+            self.entropy.clear();
+        }
+        let before = self.entropy.insert(path.to_path_buf(), profile.entropy);
+
+        // Ciphertext has no magic; compressed formats that look random do
+        let noise = file_type == filetype::FileType::Unknown && profile.looks_encrypted();
+        let jumped = before.is_some_and(|e| e <= LOW_ENTROPY);
+        let claims_format = filetype::claims_known_format(path);
+
+        let signal = if noise && (jumped || claims_format) {
+            Signal::EncryptedRewrite
+        } else {
+            Signal::Write
+        };
+        self.push(path, signal, now)
+    }
+
+    /// A rename with both names known.
+    pub(crate) fn record_rename(
+        &mut self,
+        from: &Path,
+        to: &Path,
+        now: Instant,
+    ) -> Option<RansomwareAlert> {
+        // Some backends report a rename as both a from/to pair and a combined event
+        let pair = (from.to_path_buf(), to.to_path_buf());
+        if self.last_rename.as_ref() == Some(&pair) {
+            return None;
+        }
+        self.last_rename = Some(pair);
+
+        if let Some(e) = self.entropy.remove(from) {
+            self.entropy.insert(to.to_path_buf(), e);
+        }
+
+        let signal = if is_unknown_extension_rename(from, to) {
+            Signal::UnknownExtension
+        } else {
+            Signal::Rename
+        };
+        self.push(to, signal, now)
+    }
+
+    fn push(&mut self, path: &Path, signal: Signal, now: Instant) -> Option<RansomwareAlert> {
+        let dir = path.parent()?.to_path_buf();
+
+        if self.dirs.len() > 1024 {
+            self.dirs.retain(|_, w| {
+                w.marks
+                    .back()
+                    .is_some_and(|m| now.duration_since(m.at) < WINDOW)
+            });
+        }
+
+        let window = self.dirs.entry(dir.clone()).or_default();
+        window.marks.push_back(Mark {
+            at: now,
+            path: path.to_path_buf(),
+            signal,
+        });
+        while window
+            .marks
+            .front()
+            .is_some_and(|m| now.duration_since(m.at) > WINDOW)
+        {
+            window.marks.pop_front();
+        }
+
+        if window
+            .alerted_at
+            .is_some_and(|t| now.duration_since(t) < COOLDOWN)
+        {
+            return None;
+        }
+
+        let count = |s: Signal| window.marks.iter().filter(|m| m.signal == s).count();
+        let encrypted = count(Signal::EncryptedRewrite);
+        let renamed = count(Signal::UnknownExtension);
+        let total = window.marks.len();
+
+        let reason = if encrypted >= ENCRYPTED_REWRITES {
+            format!("{encrypted} files rewritten with encrypted-looking content")
+        } else if renamed >= UNKNOWN_EXTENSION_RENAMES {
+            format!("{renamed} files renamed to unknown extensions")
+        } else if total >= BURST_EVENTS && encrypted + renamed >= BURST_SIGNALS {
+            format!(
+                "{total} changes in {}s, {encrypted} encrypted rewrite(s), {renamed} rename(s) to unknown extensions",
+                WINDOW.as_secs()
+            )
+        } else {
+            return None;
+        };
+
+        // Files carrying a signal first; they are the ones worth restoring
+        let mut seen: HashSet<&Path> = HashSet::new();
+        let mut paths: Vec<PathBuf> = Vec::new();
+        let flagged = window.marks.iter().filter(|m| m.signal.is_flag());
+        let rest = window.marks.iter().filter(|m| !m.signal.is_flag());
+        for m in flagged.chain(rest) {
+            if paths.len() >= MAX_AFFECTED_PATHS {
+                break;
+            }
+            if seen.insert(&m.path) {
+                paths.push(m.path.clone());
+            }
+        }
+
+        window.alerted_at = Some(now);
+        Some(RansomwareAlert {
+            directory: dir,
+            paths,
+            reason,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filetype::FileType;
+
+    fn profile(entropy: f64) -> EntropyProfile {
+        EntropyProfile {
+            entropy,
+            block_size: crate::entropy::BLOCK_SIZE,
+            blocks: 4,
+            high_blocks: if entropy >= 7.5 { 4 } else { 0 },
+            head: entropy,
+            min_block: entropy,
+            max_block: entropy,
+        }
+    }
+
+    fn dir() -> PathBuf {
+        PathBuf::from("/home/user/Documents")
+    }
+
+    fn at(start: Instant, secs: u64) -> Instant {
+        start + Duration::from_secs(secs)
+    }
+
+    /// Rewrites the named documents with ciphertext; the first alert raised.
+    fn encrypt(
+        m: &mut BehaviourMonitor,
+        names: impl Iterator<Item = String>,
+        now: Instant,
+    ) -> Option<RansomwareAlert> {
+        let mut first = None;
+        for name in names {
+            let alert = m.record_write(&dir().join(name), FileType::Unknown, &profile(7.99), now);
+            first = first.or(alert);
+        }
+        first
+    }
+
+    #[test]
+    fn encrypted_rewrites_alert_at_the_threshold() {
+        let now = Instant::now();
+        let mut m = BehaviourMonitor::new();
+        let names = (0..ENCRYPTED_REWRITES).map(|i| format!("report{i}.docx"));
+        let mut results: Vec<bool> = Vec::new();
+        for name in names {
+            let alert = m.record_write(&dir().join(name), FileType::Unknown, &profile(7.99), now);
+            results.push(alert.is_some());
+        }
+        assert_eq!(results, [false, false, true]);
+    }
+
+    #[test]
+    fn only_noise_that_replaced_something_counts_as_encrypted() {
+        let now = Instant::now();
+        let mut m = BehaviourMonitor::new();
+
+        // No known format in the name and no earlier low-entropy version
+        let blobs = (0..10).map(|i| format!("blob{i}.bin"));
+        assert!(encrypt(&mut m, blobs, now).is_none());
+
+        // Compressed formats are recognised by magic and never count
+        for i in 0..10 {
+            let path = dir().join(format!("archive{i}.zip"));
+            let alert = m.record_write(&path, FileType::Zip, &profile(7.99), now);
+            assert!(alert.is_none());
+        }
+
+        // Plain content rewritten as noise counts, whatever the name
+        let mut m = BehaviourMonitor::new();
+        let mut last = None;
+        for i in 0..ENCRYPTED_REWRITES {
+            let path = dir().join(format!("blob{i}.bin"));
+            m.record_write(&path, FileType::Unknown, &profile(5.0), now);
+            last = m.record_write(&path, FileType::Unknown, &profile(7.99), now);
+        }
+        let alert = last.unwrap();
+        assert_eq!(alert.directory, dir());
+        assert_eq!(
+            alert.reason,
+            "3 files rewritten with encrypted-looking content"
+        );
+    }
+
+    #[test]
+    fn unknown_extension_renames_alert_at_the_threshold() {
+        let now = Instant::now();
+        let mut m = BehaviourMonitor::new();
+        let rename = |m: &mut BehaviourMonitor, from: &str, to: &str| {
+            m.record_rename(&dir().join(from), &dir().join(to), now)
+        };
+
+        // Temp names, known-to-known and unknown sources never count
+        for i in 0..10 {
+            assert!(rename(&mut m, &format!("a{i}.docx"), &format!("a{i}.docx.tmp")).is_none());
+            assert!(rename(&mut m, &format!("b{i}.docx"), &format!("b{i}.pdf")).is_none());
+            assert!(rename(&mut m, &format!("c{i}.dat"), &format!("c{i}.locked")).is_none());
+        }
+
+        let mut m = BehaviourMonitor::new();
+        for i in 0..UNKNOWN_EXTENSION_RENAMES - 1 {
+            let from = format!("photo{i}.jpg");
+            assert!(rename(&mut m, &from, &format!("{from}.locked")).is_none());
+        }
+        // A rename reported twice is one rename
+        assert!(rename(&mut m, "photo3.jpg", "photo3.jpg.locked").is_none());
+
+        let alert = rename(&mut m, "photo9.jpg", "photo9.jpg.locked").unwrap();
+        assert_eq!(alert.reason, "5 files renamed to unknown extensions");
+        assert_eq!(alert.paths.len(), UNKNOWN_EXTENSION_RENAMES);
+    }
+
+    #[test]
+    fn a_burst_needs_signals_to_alert() {
+        let now = Instant::now();
+        let write = |m: &mut BehaviourMonitor, i: usize| {
+            let path = dir().join(format!("build{i}.o"));
+            m.record_write(&path, FileType::Elf, &profile(5.0), now)
+        };
+
+        // A busy folder with nothing suspicious in it
+        let mut m = BehaviourMonitor::new();
+        assert!((0..BURST_EVENTS * 3).all(|i| write(&mut m, i).is_none()));
+
+        // One signal short of the burst rule
+        let mut m = BehaviourMonitor::new();
+        assert!(encrypt(&mut m, ["x.pdf".to_string()].into_iter(), now).is_none());
+        assert!((0..BURST_EVENTS * 3).all(|i| write(&mut m, i).is_none()));
+
+        // Two signals, but the burst is one event short
+        let mut m = BehaviourMonitor::new();
+        let docs = ["x.pdf".to_string(), "y.pdf".to_string()].into_iter();
+        assert!(encrypt(&mut m, docs, now).is_none());
+        assert!((0..BURST_EVENTS - 3).all(|i| write(&mut m, i).is_none()));
+
+        let alert = write(&mut m, BURST_EVENTS).unwrap();
+        assert!(alert
+            .reason
+            .starts_with("30 changes in 10s, 2 encrypted rewrite(s)"));
+        // The files carrying a signal lead the list
+        assert_eq!(alert.paths[..2], [dir().join("x.pdf"), dir().join("y.pdf")]);
+    }
+
+    #[test]
+    fn signals_expire_with_the_window_and_alerts_cool_down() {
+        let start = Instant::now();
+        let mut m = BehaviourMonitor::new();
+        let docs = |from: usize, n: usize| (from..from + n).map(|i| format!("d{i}.pdf"));
+
+        // Spread over more than the window: never three at once
+        for i in 0..6 {
+            assert!(encrypt(&mut m, docs(i, 1), at(start, i as u64 * 6)).is_none());
+        }
+
+        let mut m = BehaviourMonitor::new();
+        assert!(encrypt(&mut m, docs(0, 3), start).is_some());
+        // Still within the cooldown
+        assert!(encrypt(&mut m, docs(10, 3), at(start, 30)).is_none());
+        assert!(encrypt(&mut m, docs(20, 3), at(start, 59)).is_none());
+        assert!(encrypt(&mut m, docs(30, 3), at(start, 61)).is_some());
+
+        // Other folders have their own window
+        let mut m = BehaviourMonitor::new();
+        assert!(encrypt(&mut m, docs(0, 3), start).is_some());
+        let elsewhere = PathBuf::from("/home/user/Desktop");
+        let mut last = None;
+        for i in 0..3 {
+            let path = elsewhere.join(format!("e{i}.pdf"));
+            last = m.record_write(&path, FileType::Unknown, &profile(7.99), start);
+        }
+        assert_eq!(last.unwrap().directory, elsewhere);
+    }
+}
@@ -0,0 +1,290 @@
+// Canary (decoy) files planted in the folders ransomware goes for first.
+//
+// Nobody has a reason to touch them, so any modification, rename or deletion
+// seen by the realtime watcher is an alert on its own. Content is generated
+// deterministically, so the expected bytes never need to be stored: a canary is
+// intact when it reads back exactly as `content()` produces it.
+
+use std::{
+    fs,
+    io::{self, Cursor, Write},
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use zip::{write::SimpleFileOptions, CompressionMethod, DateTime, ZipWriter};
+
+// Dot names hide them on Unix; Windows gets the hidden attribute. Sorting
+// first puts them ahead of real files in a directory walk.
+pub(crate) const NAMES: &[&str] = &[".~0000-stellar-canary.docx", ".~0000-stellar-canary.pdf"];
+
+const STAGING_SUFFIX: &str = ".new";
+const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+
+pub(crate) enum Tamper {
+    Modified,
+    Renamed(PathBuf),
+    Deleted,
+}
+
+impl Tamper {
+    pub(crate) fn describe(&self) -> String {
+        match self {
+            Tamper::Modified => "modified".to_string(),
+            Tamper::Renamed(to) => format!("renamed to {}", to.to_string_lossy()),
+            Tamper::Deleted => "deleted".to_string(),
+        }
+    }
+}
+
+fn file_name(path: &Path) -> Option<&str> {
+    path.file_name().and_then(|n| n.to_str())
+}
+
+pub(crate) fn is_canary(path: &Path) -> bool {
+    file_name(path).is_some_and(|n| NAMES.contains(&n))
+}
+
+/// The temporary file `restore` writes before renaming it into place.
+pub(crate) fn is_staging(path: &Path) -> bool {
+    file_name(path)
+        .and_then(|n| n.strip_suffix(STAGING_SUFFIX))
+        .is_some_and(|n| NAMES.contains(&n))
+}
+
+// ---- Content ----
+
+fn docx() -> Vec<u8> {
+    const CONTENT_TYPES: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types"><Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/><Default Extension="xml" ContentType="application/xml"/><Override PartName="/word/document.xml" ContentType="application/vnd.openxmlformats-officedocument.wordprocessingml.document.main+xml"/></Types>"#;
+    const RELS: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships"><Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument" Target="word/document.xml"/></Relationships>"#;
+    const DOCUMENT: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<w:document xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main"><w:body><w:p><w:r><w:t>Account numbers and passwords</w:t></w:r></w:p></w:body></w:document>"#;
+
+    // Fixed timestamp keeps the archive byte-for-byte reproducible
+    let options = SimpleFileOptions::default()
+        .compression_method(CompressionMethod::Deflated)
+        .last_modified_time(
+            DateTime::from_date_and_time(2023, 3, 14, 9, 26, 0).unwrap_or_default(),
+        );
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    for (name, body) in [
+        ("[Content_Types].xml", CONTENT_TYPES),
+        ("_rels/.rels", RELS),
+        ("word/document.xml", DOCUMENT),
+    ] {
+        if zip.start_file(name, options).is_err() || zip.write_all(body.as_bytes()).is_err() {
+            return Vec::new();
+        }
+    }
+    zip.finish().map(|c| c.into_inner()).unwrap_or_default()
+}
+
+fn pdf() -> Vec<u8> {
+    let objects = [
+        "<< /Type /Catalog /Pages 2 0 R >>",
+        "<< /Type /Pages /Kids [3 0 R] /Count 1 >>",
+        "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 612 792] /Contents 4 0 R /Resources << /Font << /F1 5 0 R >> >> >>",
+        "<< /Length 60 >>\nstream\nBT /F1 12 Tf 72 720 Td (Account numbers and passwords) Tj ET\nendstream",
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>",
+    ];
+
+    let mut out = b"%PDF-1.4\n".to_vec();
+    let mut offsets = Vec::new();
+    for (i, body) in objects.iter().enumerate() {
+        offsets.push(out.len());
+        out.extend_from_slice(format!("{} 0 obj\n{body}\nendobj\n", i + 1).as_bytes());
+    }
+
+    let xref = out.len();
+    out.extend_from_slice(
+        format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).as_bytes(),
+    );
+    for offset in offsets {
+        out.extend_from_slice(format!("{offset:010} 00000 n \n").as_bytes());
+    }
+    out.extend_from_slice(
+        format!(
+            "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{xref}\n%%EOF\n",
+            objects.len() + 1
+        )
+        .as_bytes(),
+    );
+    out
+}
+
+fn content(path: &Path) -> &'static [u8] {
+    static DOCX: OnceLock<Vec<u8>> = OnceLock::new();
+    static PDF: OnceLock<Vec<u8>> = OnceLock::new();
+
+    let is_pdf = path
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("pdf"));
+    if is_pdf {
+        PDF.get_or_init(pdf)
+    } else {
+        DOCX.get_or_init(docx)
+    }
+}
+
+fn intact(path: &Path) -> bool {
+    fs::read(path).is_ok_and(|data| data == content(path))
+}
+
+// ---- Planting ----
+
+fn write_hidden(path: &Path, data: &[u8]) -> io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::OpenOptionsExt;
+        options.attributes(FILE_ATTRIBUTE_HIDDEN);
+    }
+    #[cfg(not(windows))]
+    let _ = FILE_ATTRIBUTE_HIDDEN;
+
+    options.open(path)?.write_all(data)
+}
+
+/// Puts an intact canary back at `path`. Written aside and renamed into place,
+/// so the watcher never sees a half-written decoy.
+pub(crate) fn restore(path: &Path) -> io::Result<()> {
+    if intact(path) {
+        return Ok(());
+    }
+
+    let mut staging = path.as_os_str().to_owned();
+    staging.push(STAGING_SUFFIX);
+    let staging = PathBuf::from(staging);
+
+    write_hidden(&staging, content(path))?;
+    fs::rename(&staging, path).inspect_err(|_| {
+        let _ = fs::remove_file(&staging);
+    })
+}
+
+/// Plants (or repairs) every canary in each directory; returns their paths.
+pub(crate) fn plant(dirs: &[PathBuf]) -> Vec<PathBuf> {
+    let mut planted = Vec::new();
+    for dir in dirs {
+        for name in NAMES {
+            let path = dir.join(name);
+            match restore(&path) {
+                Ok(()) => planted.push(path),
+                Err(e) => eprintln!("[CANARY] failed to plant {:?}: {e}", path),
+            }
+        }
+    }
+    planted
+}
+
+/// What happened to the canary at `path`, if anything. `renamed_to` is the new
+/// name when the event was a rename away from it.
+pub(crate) fn check(path: &Path, renamed_to: Option<&Path>) -> Option<Tamper> {
+    if let Some(to) = renamed_to {
+        return Some(Tamper::Renamed(to.to_path_buf()));
+    }
+    if !path.exists() {
+        return Some(Tamper::Deleted);
+    }
+    (!intact(path)).then_some(Tamper::Modified)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir =
+                std::env::temp_dir().join(format!("stellar-canary-{name}-{}", std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            TempDir(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn names_and_staging_files() {
+        let dir = Path::new("/home/u/Documents");
+        for name in NAMES {
+            assert!(is_canary(&dir.join(name)));
+            assert!(!is_staging(&dir.join(name)));
+            assert!(is_staging(&dir.join(format!("{name}{STAGING_SUFFIX}"))));
+        }
+        assert!(!is_canary(&dir.join("report.docx")));
+        assert!(!is_staging(&dir.join("report.docx.new")));
+    }
+
+    #[test]
+    fn content_is_reproducible_and_looks_real() {
+        assert_eq!(docx(), docx());
+        assert_eq!(pdf(), pdf());
+        assert!(docx().starts_with(b"PK\x03\x04"));
+        assert!(pdf().starts_with(b"%PDF-") && pdf().ends_with(b"%%EOF\n"));
+    }
+
+    #[test]
+    fn plant_is_idempotent_and_leaves_no_staging_files() {
+        let dir = TempDir::new("plant");
+        let planted = plant(std::slice::from_ref(&dir.0));
+        assert_eq!(planted.len(), NAMES.len());
+        assert!(planted.iter().all(|p| intact(p)));
+
+        let before: Vec<_> = planted
+            .iter()
+            .map(|p| fs::metadata(p).unwrap().modified().unwrap())
+            .collect();
+        assert_eq!(plant(std::slice::from_ref(&dir.0)), planted);
+        let after: Vec<_> = planted
+            .iter()
+            .map(|p| fs::metadata(p).unwrap().modified().unwrap())
+            .collect();
+        assert_eq!(before, after, "intact canaries are not rewritten");
+
+        assert_eq!(fs::read_dir(&dir.0).unwrap().count(), NAMES.len());
+    }
+
+    #[test]
+    fn check_reports_each_kind_of_tampering() {
+        let dir = TempDir::new("check");
+        let path = dir.0.join(NAMES[0]);
+        restore(&path).unwrap();
+        assert!(check(&path, None).is_none());
+
+        // Overwritten in place, as an encryptor would
+        fs::write(&path, b"\x8f\x01 encrypted").unwrap();
+        assert!(matches!(check(&path, None), Some(Tamper::Modified)));
+        restore(&path).unwrap();
+        assert!(check(&path, None).is_none());
+
+        // Truncated or appended to
+        let mut longer = content(&path).to_vec();
+        longer.push(0);
+        fs::write(&path, longer).unwrap();
+        assert!(matches!(check(&path, None), Some(Tamper::Modified)));
+        restore(&path).unwrap();
+
+        let to = dir.0.join(format!("{}.locked", NAMES[0]));
+        fs::rename(&path, &to).unwrap();
+        match check(&path, Some(&to)) {
+            Some(Tamper::Renamed(got)) => assert_eq!(got, to),
+            _ => panic!("expected a rename"),
+        }
+
+        fs::remove_file(&to).unwrap();
+        assert!(matches!(check(&path, None), Some(Tamper::Deleted)));
+        assert_eq!(Tamper::Deleted.describe(), "deleted");
+    }
+}
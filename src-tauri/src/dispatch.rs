@@ -0,0 +1,147 @@
+// Realtime scan queue: the watcher thread only decides *what* to scan, worker
+// threads do the hashing and API calls.
+//
+// A slow API round-trip used to stall every event behind it. Now settled paths
+// go into a bounded FIFO; a path already waiting is not queued twice, and when
+// the queue is full `submit` refuses the path (and counts it as deferred)
+// rather than letting memory grow during a burst. The caller keeps a refused
+// path and offers it again later. Depth and counters are exposed to the UI.
+
+use std::{
+    collections::{HashSet, VecDeque},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Condvar, Mutex, OnceLock,
+    },
+    thread,
+};
+
+use serde::Serialize;
+
+const QUEUE_CAPACITY: usize = 1024;
+const MIN_WORKERS: usize = 2;
+const MAX_WORKERS: usize = 4;
+
+#[derive(Default)]
+struct Pending {
+    queue: VecDeque<PathBuf>,
+    queued: HashSet<PathBuf>,
+}
+
+#[derive(Default)]
+struct ScanQueue {
+    pending: Mutex<Pending>,
+    ready: Condvar,
+    workers: AtomicUsize,
+    in_flight: AtomicUsize,
+    enqueued: AtomicU64,
+    coalesced: AtomicU64,
+    deferred: AtomicU64,
+    processed: AtomicU64,
+}
+
+#[derive(Serialize, Clone)]
+pub(crate) struct RealtimeQueueStatus {
+    depth: usize,
+    capacity: usize,
+    workers: usize,
+    in_flight: usize,
+    enqueued: u64,
+    coalesced: u64,
+    deferred: u64,
+    processed: u64,
+}
+
+fn queue() -> &'static ScanQueue {
+    static QUEUE: OnceLock<ScanQueue> = OnceLock::new();
+    QUEUE.get_or_init(ScanQueue::default)
+}
+
+/// Queues `path` for scanning. False when the queue is full; the caller still
+/// owns the path and should submit it again later.
+pub(crate) fn submit(path: PathBuf) -> bool {
+    let q = queue();
+    let Ok(mut pending) = q.pending.lock() else {
+        return false;
+    };
+
+    // Workers read the file when they get to it, so one entry covers every write
+    if pending.queued.contains(&path) {
+        q.coalesced.fetch_add(1, Ordering::Relaxed);
+        return true;
+    }
+    if pending.queue.len() >= QUEUE_CAPACITY {
+        let deferred = q.deferred.fetch_add(1, Ordering::Relaxed) + 1;
+        if deferred.is_power_of_two() {
+            eprintln!("[Realtime] scan queue full; {deferred} file(s) deferred so far");
+        }
+        return false;
+    }
+
+    pending.queued.insert(path.clone());
+    pending.queue.push_back(path);
+    q.enqueued.fetch_add(1, Ordering::Relaxed);
+    q.ready.notify_one();
+    true
+}
+
+fn next() -> Option<PathBuf> {
+    let q = queue();
+    let mut pending = q.pending.lock().ok()?;
+    loop {
+        if let Some(path) = pending.queue.pop_front() {
+            pending.queued.remove(&path);
+            return Some(path);
+        }
+        pending = q.ready.wait(pending).ok()?;
+    }
+}
+
+/// Starts the worker threads, each running `scan` on queued paths.
+pub(crate) fn start_workers<F>(scan: F)
+where
+    F: Fn(&Path) + Send + Sync + Clone + 'static,
+{
+    let workers = thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(MIN_WORKERS)
+        .clamp(MIN_WORKERS, MAX_WORKERS);
+
+    for i in 0..workers {
+        let scan = scan.clone();
+        let spawned = thread::Builder::new()
+            .name(format!("realtime-scan-{i}"))
+            .spawn(move || {
+                let q = queue();
+                while let Some(path) = next() {
+                    q.in_flight.fetch_add(1, Ordering::Relaxed);
+                    scan(&path);
+                    q.in_flight.fetch_sub(1, Ordering::Relaxed);
+                    q.processed.fetch_add(1, Ordering::Relaxed);
+                }
+            });
+        match spawned {
+            Ok(_) => {
+                queue().workers.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(e) => eprintln!("[Realtime] failed to start scan worker: {e}"),
+        }
+    }
+}
+
+#[tauri::command]
+pub(crate) fn get_realtime_queue_status() -> RealtimeQueueStatus {
+    let q = queue();
+    let depth = q.pending.lock().map(|p| p.queue.len()).unwrap_or(0);
+    RealtimeQueueStatus {
+        depth,
+        capacity: QUEUE_CAPACITY,
+        workers: q.workers.load(Ordering::Relaxed),
+        in_flight: q.in_flight.load(Ordering::Relaxed),
+        enqueued: q.enqueued.load(Ordering::Relaxed),
+        coalesced: q.coalesced.load(Ordering::Relaxed),
+        deferred: q.deferred.load(Ordering::Relaxed),
+        processed: q.processed.load(Ordering::Relaxed),
+    }
+}
@@ -0,0 +1,285 @@
+// Active content in documents: VBA / XLM macros and remote templates in Office
+// files (OOXML and legacy OLE), JavaScript and launch actions in PDFs.
+//
+// None of this is malicious by itself, so findings are reported with a
+// "suspicious" verdict and the reason, next to (not instead of) hash verdicts.
+
+use std::io::{Cursor, Read};
+
+use flate2::read::ZlibDecoder;
+use memchr::memmem;
+
+use crate::filetype::FileType;
+
+// Decompressed bytes inspected per document (PDF object streams, OOXML parts)
+const MAX_INFLATED_BYTES: u64 = 8 * 1024 * 1024;
+const MAX_PDF_STREAMS: usize = 512;
+const MAX_OOXML_PARTS: usize = 1024;
+
+pub(crate) struct Finding {
+    pub(crate) rule_id: &'static str,
+    pub(crate) name: &'static str,
+    pub(crate) severity: &'static str,
+    pub(crate) reason: String,
+}
+
+fn finding(
+    rule_id: &'static str,
+    name: &'static str,
+    severity: &'static str,
+    reason: &str,
+) -> Finding {
+    Finding {
+        rule_id,
+        name,
+        severity,
+        reason: reason.to_string(),
+    }
+}
+
+// ---- Office: OOXML ----
+
+fn analyze_ooxml(data: &[u8]) -> Vec<Finding> {
+    let mut out: Vec<Finding> = Vec::new();
+    let Ok(mut archive) = zip::ZipArchive::new(Cursor::new(data)) else {
+        return out;
+    };
+
+    let parts: Vec<String> = archive
+        .file_names()
+        .take(MAX_OOXML_PARTS)
+        .map(|n| n.to_string())
+        .collect();
+    let names: Vec<String> = parts.iter().map(|n| n.to_lowercase()).collect();
+
+    if let Some(part) = parts
+        .iter()
+        .find(|n| n.to_lowercase().ends_with("vbaproject.bin"))
+    {
+        out.push(finding(
+            "STL-DOC-0001",
+            "Stellar.Suspicious.Macro.VBA",
+            "medium",
+            &format!("VBA macro project ({part})"),
+        ));
+    }
+
+    if names.iter().any(|n| n.starts_with("xl/macrosheets/")) {
+        out.push(finding(
+            "STL-DOC-0002",
+            "Stellar.Suspicious.Macro.XLM",
+            "medium",
+            "Excel 4.0 (XLM) macro sheet",
+        ));
+    }
+
+    if names.iter().any(|n| n.contains("/activex/")) {
+        out.push(finding(
+            "STL-DOC-0003",
+            "Stellar.Suspicious.ActiveX",
+            "low",
+            "Embedded ActiveX control",
+        ));
+    }
+
+    // Template injection: settings.xml.rels pointing attachedTemplate at a URL
+    let mut remote_template = false;
+    let mut budget = MAX_INFLATED_BYTES;
+    for name in parts.iter().filter(|n| n.to_lowercase().ends_with(".rels")) {
+        let Ok(part) = archive.by_name(name) else {
+            continue;
+        };
+        let mut xml = Vec::new();
+        if part.take(budget).read_to_end(&mut xml).is_err() {
+            continue;
+        }
+        budget = budget.saturating_sub(xml.len() as u64);
+
+        let lower = xml.to_ascii_lowercase();
+        if memmem::find(&lower, b"attachedtemplate").is_some()
+            && memmem::find(&lower, b"targetmode=\"external\"").is_some()
+        {
+            remote_template = true;
+            break;
+        }
+        if budget == 0 {
+            break;
+        }
+    }
+    if remote_template {
+        out.push(finding(
+            "STL-DOC-0004",
+            "Stellar.Suspicious.RemoteTemplate",
+            "high",
+            "Document loads its template from a remote location",
+        ));
+    }
+
+    out
+}
+
+// ---- Office: OLE (doc/xls/ppt) ----
+
+fn analyze_ole(data: &[u8]) -> Vec<Finding> {
+    let mut out: Vec<Finding> = Vec::new();
+    let Ok(compound) = cfb::CompoundFile::open(Cursor::new(data)) else {
+        return out;
+    };
+
+    let mut vba: Option<String> = None;
+    let mut ole_package = false;
+    for entry in compound.walk() {
+        let name = entry.name();
+        if vba.is_none()
+            && (name.eq_ignore_ascii_case("_VBA_PROJECT") || name.eq_ignore_ascii_case("VBA"))
+        {
+            vba = Some(entry.path().to_string_lossy().to_string());
+        }
+        if name == "\u{1}Ole10Native" {
+            ole_package = true;
+        }
+    }
+
+    if let Some(stream) = vba {
+        out.push(finding(
+            "STL-DOC-0001",
+            "Stellar.Suspicious.Macro.VBA",
+            "medium",
+            &format!("VBA macro project ({stream})"),
+        ));
+    }
+    if ole_package {
+        out.push(finding(
+            "STL-DOC-0005",
+            "Stellar.Suspicious.EmbeddedPackage",
+            "medium",
+            "Embedded OLE package object",
+        ));
+    }
+
+    out
+}
+
+// ---- PDF ----
+
+/// Undoes `#xx` escapes inside name tokens so `/J#61vaScript` reads `/JavaScript`.
+fn unescape_names(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    let mut i = 0;
+    while i < data.len() {
+        if data[i] == b'#' && i + 2 < data.len() {
+            let hex = std::str::from_utf8(&data[i + 1..i + 3])
+                .ok()
+                .and_then(|h| u8::from_str_radix(h, 16).ok());
+            if let Some(b) = hex {
+                out.push(b);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(data[i]);
+        i += 1;
+    }
+    out
+}
+
+/// Inflates `stream ... endstream` bodies; object streams hide most of a
+/// modern PDF's dictionaries.
+fn inflate_streams(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut pos = 0;
+    let mut streams = 0;
+
+    while let Some(start) = memmem::find(&data[pos..], b"stream") {
+        if streams >= MAX_PDF_STREAMS || out.len() as u64 >= MAX_INFLATED_BYTES {
+            break;
+        }
+        let mut body = pos + start + b"stream".len();
+        // "endstream" also contains "stream"
+        if data[..pos + start].ends_with(b"end") {
+            pos = body;
+            continue;
+        }
+        if data.get(body) == Some(&b'\r') {
+            body += 1;
+        }
+        if data.get(body) == Some(&b'\n') {
+            body += 1;
+        }
+        let Some(len) = memmem::find(&data[body..], b"endstream") else {
+            break;
+        };
+
+        streams += 1;
+        let remaining = MAX_INFLATED_BYTES - out.len() as u64;
+        let _ = ZlibDecoder::new(&data[body..body + len])
+            .take(remaining)
+            .read_to_end(&mut out);
+        out.push(b'\n');
+        pos = body + len;
+    }
+
+    out
+}
+
+fn analyze_pdf(data: &[u8]) -> Vec<Finding> {
+    let mut text = unescape_names(data);
+    text.extend(unescape_names(&inflate_streams(data)));
+
+    let has = |token: &[u8]| {
+        memmem::find_iter(&text, token).any(|i| {
+            // Whole name only: /JS but not /JSomething
+            !text
+                .get(i + token.len())
+                .is_some_and(|b| b.is_ascii_alphanumeric())
+        })
+    };
+
+    let javascript = has(b"/JavaScript") || has(b"/JS");
+    let launch = has(b"/Launch");
+    let embedded = has(b"/EmbeddedFile");
+    let auto = has(b"/OpenAction") || has(b"/AA");
+
+    let mut out: Vec<Finding> = Vec::new();
+    if javascript {
+        let reason = if auto {
+            "JavaScript run automatically (OpenAction / AA)"
+        } else {
+            "Contains JavaScript"
+        };
+        out.push(finding(
+            "STL-PDF-0001",
+            "Stellar.Suspicious.PDF.JavaScript",
+            if auto { "high" } else { "medium" },
+            reason,
+        ));
+    }
+    if launch {
+        out.push(finding(
+            "STL-PDF-0002",
+            "Stellar.Suspicious.PDF.Launch",
+            "high",
+            "Launch action starts an external program",
+        ));
+    }
+    if embedded && auto {
+        out.push(finding(
+            "STL-PDF-0003",
+            "Stellar.Suspicious.PDF.EmbeddedFile",
+            "medium",
+            "Embedded file with an automatic action",
+        ));
+    }
+
+    out
+}
+
+/// Active-content findings for Office documents and PDFs; empty for other types.
+pub(crate) fn analyze(file_type: FileType, data: &[u8]) -> Vec<Finding> {
+    match file_type {
+        FileType::OfficeOoxml => analyze_ooxml(data),
+        FileType::OfficeOle => analyze_ole(data),
+        FileType::Pdf => analyze_pdf(data),
+        _ => vec![],
+    }
+}
@@ -0,0 +1,859 @@
+// ELF static analysis: headers, sections, imports, interpreter, packers.
+//
+// Works on bytes only (no loader, no external tools) so the same code runs on
+// every host and on archive members. Malformed input never panics: anything
+// out of bounds simply ends that part of the walk. The indicator weights add
+// up to a 0-100 score; from `DETECTION_SCORE` the scan reports the file as
+// suspicious.
+
+use memchr::memmem;
+use serde::Serialize;
+
+use crate::{entropy, indicator_score, Indicator};
+
+/// Score from which the scanner reports the file as suspicious.
+pub(crate) const DETECTION_SCORE: u32 = 60;
+pub(crate) const DETECTION_NAME: &str = "Stellar.Heur.ELF.Suspicious";
+
+const MAX_SEGMENTS: usize = 1024; // the kernel loads at most 64 KB of program headers
+const MAX_SECTIONS: usize = 512;
+const MAX_SYMBOLS: usize = 20_000;
+const MAX_NEEDED: usize = 256;
+const MAX_NAME_LEN: usize = 4096;
+// UPX's l_info (checksum, "UPX!", ...) follows the program headers
+const UPX_INFO_WINDOW: usize = 16;
+const MAX_REPORTED_IMPORTS: usize = 256;
+
+const PT_LOAD: u32 = 1;
+const PT_INTERP: u32 = 3;
+const PT_GNU_STACK: u32 = 0x6474_e551;
+const PF_X: u32 = 1;
+const PF_W: u32 = 2;
+
+const SHT_DYNAMIC: u32 = 6;
+const SHT_NOBITS: u32 = 8;
+const SHT_DYNSYM: u32 = 11;
+const SHF_WRITE: u64 = 1;
+const SHF_EXECINSTR: u64 = 4;
+
+const DT_NEEDED: u64 = 1;
+
+// Loaders shipped by glibc, musl, Android and the BSDs
+const KNOWN_INTERPRETER_PREFIXES: &[&str] = &[
+    "/lib/ld-",
+    "/lib64/ld-",
+    "/lib32/ld-",
+    "/libx32/ld-",
+    "/usr/lib/ld-",
+    "/usr/lib64/ld-",
+    "/lib/ld.so",
+    "/system/bin/linker",
+    "/usr/libexec/ld-elf",
+    "/libexec/ld-elf",
+    "/nix/store/",
+];
+
+#[derive(Serialize, Clone)]
+pub(crate) struct ElfSection {
+    name: String,
+    size: u64,
+    entropy: f64,
+    executable: bool,
+    writable: bool,
+}
+
+#[derive(Serialize, Clone)]
+pub(crate) struct ElfReport {
+    class: &'static str, // "elf32" | "elf64"
+    endian: &'static str,
+    elf_type: &'static str,
+    machine: String,
+    entry: u64,
+    interpreter: Option<String>,
+    needed: Vec<String>,
+    imports: Vec<String>,
+    sections: Vec<ElfSection>,
+    entropy: f64,
+    packer: Option<String>,
+    pub(crate) score: u32,
+    pub(crate) indicators: Vec<Indicator>,
+}
+
+// ---- Byte access ----
+
+struct Elf<'a> {
+    data: &'a [u8],
+    is64: bool,
+    le: bool,
+}
+
+impl Elf<'_> {
+    fn bytes<const N: usize>(&self, off: u64) -> Option<[u8; N]> {
+        let off = usize::try_from(off).ok()?;
+        self.data.get(off..off.checked_add(N)?)?.try_into().ok()
+    }
+
+    fn u8(&self, off: u64) -> Option<u8> {
+        self.bytes::<1>(off).map(|b| b[0])
+    }
+
+    fn u16(&self, off: u64) -> Option<u16> {
+        let b = self.bytes(off)?;
+        Some(if self.le {
+            u16::from_le_bytes(b)
+        } else {
+            u16::from_be_bytes(b)
+        })
+    }
+
+    fn u32(&self, off: u64) -> Option<u32> {
+        let b = self.bytes(off)?;
+        Some(if self.le {
+            u32::from_le_bytes(b)
+        } else {
+            u32::from_be_bytes(b)
+        })
+    }
+
+    fn u64(&self, off: u64) -> Option<u64> {
+        let b = self.bytes(off)?;
+        Some(if self.le {
+            u64::from_le_bytes(b)
+        } else {
+            u64::from_be_bytes(b)
+        })
+    }
+
+    /// Address / offset / size field: 8 bytes on ELF64, 4 on ELF32.
+    fn word(&self, off: u64) -> Option<u64> {
+        if self.is64 {
+            self.u64(off)
+        } else {
+            self.u32(off).map(u64::from)
+        }
+    }
+
+    /// `table + i * entsize`, only if it lands inside the file (so the small
+    /// field offsets added to it can't overflow).
+    fn offset(&self, table: u64, i: u64, entsize: u64) -> Option<u64> {
+        let off = table.checked_add(i.checked_mul(entsize)?)?;
+        (off < self.data.len() as u64).then_some(off)
+    }
+
+    fn slice(&self, off: u64, len: u64) -> Option<&[u8]> {
+        let start = usize::try_from(off).ok()?;
+        let end = start.checked_add(usize::try_from(len).ok()?)?;
+        self.data.get(start..end)
+    }
+
+    /// NUL-terminated string at `table + idx`, at most `MAX_NAME_LEN` bytes.
+    fn cstr(&self, table: u64, idx: u64) -> Option<String> {
+        let start = usize::try_from(table.checked_add(idx)?).ok()?;
+        let rest = self.data.get(start..)?;
+        let rest = &rest[..rest.len().min(MAX_NAME_LEN)];
+        let end = memchr::memchr(0, rest)?;
+        Some(String::from_utf8_lossy(&rest[..end]).to_string())
+    }
+}
+
+// ---- Parsed tables ----
+
+struct Segment {
+    p_type: u32,
+    flags: u32,
+    offset: u64,
+    filesz: u64,
+}
+
+struct Section {
+    name: String,
+    sh_type: u32,
+    flags: u64,
+    addr: u64,
+    offset: u64,
+    size: u64,
+    link: u32,
+    entsize: u64,
+}
+
+fn segments(elf: &Elf) -> Vec<Segment> {
+    let (phoff, entsize, num) = if elf.is64 {
+        (elf.u64(32), elf.u16(54), elf.u16(56))
+    } else {
+        (elf.u32(28).map(u64::from), elf.u16(42), elf.u16(44))
+    };
+    let (Some(phoff), Some(entsize), Some(num)) = (phoff, entsize, num) else {
+        return vec![];
+    };
+    // Smaller entries would overlap each other
+    if entsize < if elf.is64 { 56 } else { 32 } {
+        return vec![];
+    }
+
+    let count = usize::from(num).min(MAX_SEGMENTS) as u64;
+    (0..count)
+        .map_while(|i| {
+            let base = elf.offset(phoff, i, u64::from(entsize))?;
+            Some(if elf.is64 {
+                Segment {
+                    p_type: elf.u32(base)?,
+                    flags: elf.u32(base + 4)?,
+                    offset: elf.u64(base + 8)?,
+                    filesz: elf.u64(base + 32)?,
+                }
+            } else {
+                Segment {
+                    p_type: elf.u32(base)?,
+                    offset: u64::from(elf.u32(base + 4)?),
+                    filesz: u64::from(elf.u32(base + 16)?),
+                    flags: elf.u32(base + 24)?,
+                }
+            })
+        })
+        .collect()
+}
+
+fn sections(elf: &Elf) -> Vec<Section> {
+    let (shoff, entsize, num, strndx) = if elf.is64 {
+        (elf.u64(40), elf.u16(58), elf.u16(60), elf.u16(62))
+    } else {
+        (
+            elf.u32(32).map(u64::from),
+            elf.u16(46),
+            elf.u16(48),
+            elf.u16(50),
+        )
+    };
+    let (Some(shoff), Some(entsize), Some(num), Some(strndx)) = (shoff, entsize, num, strndx)
+    else {
+        return vec![];
+    };
+    if shoff == 0 || entsize < if elf.is64 { 64 } else { 40 } {
+        return vec![];
+    }
+
+    let header = |i: u64| -> Option<(u32, Section)> {
+        let base = elf.offset(shoff, i, u64::from(entsize))?;
+        let name_idx = elf.u32(base)?;
+        let section = if elf.is64 {
+            Section {
+                name: String::new(),
+                sh_type: elf.u32(base + 4)?,
+                flags: elf.u64(base + 8)?,
+                addr: elf.u64(base + 16)?,
+                offset: elf.u64(base + 24)?,
+                size: elf.u64(base + 32)?,
+                link: elf.u32(base + 40)?,
+                entsize: elf.u64(base + 56)?,
+            }
+        } else {
+            Section {
+                name: String::new(),
+                sh_type: elf.u32(base + 4)?,
+                flags: u64::from(elf.u32(base + 8)?),
+                addr: u64::from(elf.u32(base + 12)?),
+                offset: u64::from(elf.u32(base + 16)?),
+                size: u64::from(elf.u32(base + 20)?),
+                link: elf.u32(base + 24)?,
+                entsize: u64::from(elf.u32(base + 36)?),
+            }
+        };
+        Some((name_idx, section))
+    };
+
+    let count = usize::from(num).min(MAX_SECTIONS) as u64;
+    let mut out: Vec<(u32, Section)> = (0..count).map_while(header).collect();
+
+    let strtab = out.get(usize::from(strndx)).map(|(_, s)| s.offset);
+    if let Some(strtab) = strtab {
+        for (idx, s) in &mut out {
+            s.name = elf.cstr(strtab, u64::from(*idx)).unwrap_or_default();
+        }
+    }
+
+    out.into_iter().map(|(_, s)| s).collect()
+}
+
+/// Undefined dynamic symbols, i.e. functions the binary imports.
+fn imported_symbols(elf: &Elf, sections: &[Section]) -> Vec<String> {
+    let Some(dynsym) = sections.iter().find(|s| s.sh_type == SHT_DYNSYM) else {
+        return vec![];
+    };
+    let Some(strtab) = sections.get(dynsym.link as usize) else {
+        return vec![];
+    };
+
+    let entsize = if dynsym.entsize > 0 {
+        dynsym.entsize
+    } else if elf.is64 {
+        24
+    } else {
+        16
+    };
+    let count = (dynsym.size / entsize).min(MAX_SYMBOLS as u64);
+
+    let mut out: Vec<String> = Vec::new();
+    for i in 1..count {
+        let Some(base) = elf.offset(dynsym.offset, i, entsize) else {
+            break;
+        };
+        let (name, shndx) = if elf.is64 {
+            (elf.u32(base), elf.u16(base + 6))
+        } else {
+            (elf.u32(base), elf.u16(base + 14))
+        };
+        let (Some(name), Some(shndx)) = (name, shndx) else {
+            break;
+        };
+        if shndx != 0 || name == 0 {
+            continue;
+        }
+        if let Some(sym) = elf.cstr(strtab.offset, u64::from(name)) {
+            if !sym.is_empty() && !out.contains(&sym) {
+                out.push(sym);
+            }
+        }
+    }
+    out
+}
+
+fn needed_libraries(elf: &Elf, sections: &[Section]) -> Vec<String> {
+    let Some(dynamic) = sections.iter().find(|s| s.sh_type == SHT_DYNAMIC) else {
+        return vec![];
+    };
+    let Some(strtab) = sections.get(dynamic.link as usize) else {
+        return vec![];
+    };
+
+    let entsize = if elf.is64 { 16 } else { 8 };
+    let half = entsize / 2;
+    let mut out: Vec<String> = Vec::new();
+    for i in 0..dynamic.size / entsize {
+        if out.len() >= MAX_NEEDED {
+            break;
+        }
+        let Some(base) = elf.offset(dynamic.offset, i, entsize) else {
+            break;
+        };
+        let (Some(tag), Some(val)) = (elf.word(base), elf.word(base + half)) else {
+            break;
+        };
+        match tag {
+            0 => break,
+            DT_NEEDED => {
+                if let Some(lib) = elf.cstr(strtab.offset, val) {
+                    out.push(lib);
+                }
+            }
+            _ => {}
+        }
+    }
+    out
+}
+
+fn machine_name(machine: u16) -> String {
+    match machine {
+        3 => "x86".to_string(),
+        8 => "mips".to_string(),
+        20 => "ppc".to_string(),
+        21 => "ppc64".to_string(),
+        40 => "arm".to_string(),
+        62 => "x86_64".to_string(),
+        183 => "aarch64".to_string(),
+        243 => "riscv".to_string(),
+        other => format!("0x{other:x}"),
+    }
+}
+
+fn elf_type_name(t: u16) -> &'static str {
+    match t {
+        1 => "relocatable",
+        2 => "executable",
+        3 => "shared",
+        4 => "core",
+        _ => "unknown",
+    }
+}
+
+/// Where UPX puts its `l_info` block: right behind the program headers.
+fn headers_end(elf: &Elf) -> Option<u64> {
+    let (phoff, entsize, num) = if elf.is64 {
+        (elf.u64(32)?, elf.u16(54)?, elf.u16(56)?)
+    } else {
+        (u64::from(elf.u32(28)?), elf.u16(42)?, elf.u16(44)?)
+    };
+    phoff.checked_add(u64::from(entsize) * u64::from(num))
+}
+
+/// Judged by headers and section names only: the marker strings could sit in
+/// any file's data.
+fn detect_packer(elf: &Elf, sections: &[Section]) -> Option<String> {
+    let upx_info = headers_end(elf)
+        .and_then(|end| usize::try_from(end).ok())
+        .and_then(|end| {
+            let window_end = elf.data.len().min(end.saturating_add(UPX_INFO_WINDOW));
+            elf.data.get(end..window_end)
+        })
+        .is_some_and(|info| memmem::find(info, b"UPX!").is_some());
+    if upx_info
+        || sections
+            .iter()
+            .any(|s| s.name.to_uppercase().starts_with("UPX"))
+    {
+        return Some("UPX".to_string());
+    }
+    None
+}
+
+// ---- Heuristics ----
+
+fn has(imports: &[String], name: &str) -> bool {
+    imports.iter().any(|i| i == name)
+}
+
+fn score_indicators(
+    report: &ElfReport,
+    segments: &[Segment],
+    sections: &[Section],
+) -> Vec<Indicator> {
+    let mut out: Vec<Indicator> = Vec::new();
+    let mut add = |id: &'static str, weight: u32, detail: String| {
+        out.push(Indicator { id, weight, detail });
+    };
+
+    if let Some(packer) = &report.packer {
+        add("packed", 30, format!("Packed with {packer}"));
+    }
+
+    let executable_type = matches!(report.elf_type, "executable" | "shared");
+    if sections.is_empty() && executable_type {
+        add("no_sections", 20, "No section headers".to_string());
+    }
+
+    for s in &report.sections {
+        if s.executable && s.entropy > 7.2 {
+            add(
+                "high_entropy_code",
+                25,
+                format!("Executable section {} has entropy {:.2}", s.name, s.entropy),
+            );
+            break;
+        }
+    }
+
+    if segments
+        .iter()
+        .any(|p| p.p_type == PT_LOAD && p.flags & PF_W != 0 && p.flags & PF_X != 0)
+    {
+        add(
+            "wx_segment",
+            25,
+            "Writable and executable segment".to_string(),
+        );
+    }
+
+    if segments
+        .iter()
+        .any(|p| p.p_type == PT_GNU_STACK && p.flags & PF_X != 0)
+    {
+        add("exec_stack", 10, "Executable stack".to_string());
+    }
+
+    if let Some(interp) = &report.interpreter {
+        if !KNOWN_INTERPRETER_PREFIXES
+            .iter()
+            .any(|p| interp.starts_with(p))
+        {
+            add(
+                "odd_interpreter",
+                20,
+                format!("Unusual interpreter {interp}"),
+            );
+        }
+    }
+
+    if report.entry != 0 && executable_type && !sections.is_empty() {
+        let in_code = sections.iter().any(|s| {
+            s.flags & SHF_EXECINSTR != 0
+                && report.entry >= s.addr
+                && report.entry < s.addr.saturating_add(s.size)
+        });
+        if !in_code {
+            add(
+                "entry_outside_code",
+                20,
+                "Entry point outside executable sections".to_string(),
+            );
+        }
+    }
+
+    let imports = &report.imports;
+    let execs = ["execve", "execl", "execlp", "execvp", "system", "popen"];
+    if has(imports, "socket")
+        && has(imports, "connect")
+        && has(imports, "dup2")
+        && execs.iter().any(|e| has(imports, e))
+    {
+        add(
+            "reverse_shell_imports",
+            35,
+            "Imports socket/connect/dup2 with process execution".to_string(),
+        );
+    }
+    if has(imports, "memfd_create")
+        && ["fexecve", "execveat", "execve"]
+            .iter()
+            .any(|e| has(imports, e))
+    {
+        add(
+            "fileless_exec_imports",
+            30,
+            "Imports memfd_create with exec (fileless execution)".to_string(),
+        );
+    }
+    if has(imports, "ptrace") {
+        add("ptrace_import", 15, "Imports ptrace".to_string());
+    }
+
+    out
+}
+
+/// Parses an ELF image. `None` if `data` isn't ELF or the header is unusable.
+pub(crate) fn analyze(data: &[u8]) -> Option<ElfReport> {
+    if !data.starts_with(b"\x7fELF") {
+        return None;
+    }
+
+    let probe = Elf {
+        data,
+        is64: false,
+        le: true,
+    };
+    let is64 = match probe.u8(4)? {
+        1 => false,
+        2 => true,
+        _ => return None,
+    };
+    let le = match probe.u8(5)? {
+        1 => true,
+        2 => false,
+        _ => return None,
+    };
+    let elf = Elf { data, is64, le };
+
+    let segments = segments(&elf);
+    let sections = sections(&elf);
+
+    let interpreter = segments
+        .iter()
+        .find(|p| p.p_type == PT_INTERP)
+        .and_then(|p| elf.slice(p.offset, p.filesz))
+        .map(|b| {
+            let b = &b[..b.len().min(MAX_NAME_LEN)];
+            let end = memchr::memchr(0, b).unwrap_or(b.len());
+            String::from_utf8_lossy(&b[..end]).to_string()
+        });
+
+    let imports = imported_symbols(&elf, &sections);
+
+    let section_infos = sections
+        .iter()
+        .filter(|s| !s.name.is_empty())
+        .map(|s| {
+            let content = if s.sh_type == SHT_NOBITS {
+                None
+            } else {
+                elf.slice(s.offset, s.size)
+            };
+            ElfSection {
+                name: s.name.clone(),
+                size: s.size,
+                entropy: entropy::rounded(content.map(entropy::shannon).unwrap_or(0.0)),
+                executable: s.flags & SHF_EXECINSTR != 0,
+                writable: s.flags & SHF_WRITE != 0,
+            }
+        })
+        .collect();
+
+    let mut report = ElfReport {
+        class: if is64 { "elf64" } else { "elf32" },
+        endian: if le { "little" } else { "big" },
+        elf_type: elf_type_name(elf.u16(16)?),
+        machine: machine_name(elf.u16(18)?),
+        entry: elf.word(24)?,
+        interpreter,
+        needed: needed_libraries(&elf, &sections),
+        imports,
+        sections: section_infos,
+        entropy: entropy::rounded(entropy::shannon(data)),
+        packer: detect_packer(&elf, &sections),
+        score: 0,
+        indicators: vec![],
+    };
+
+    report.indicators = score_indicators(&report, &segments, &sections);
+    report.score = indicator_score(&report.indicators);
+    // Scored on the full list, reported trimmed
+    report.imports.truncate(MAX_REPORTED_IMPORTS);
+
+    Some(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PHOFF: usize = 64;
+    const SHOFF: usize = 0x400;
+    const SHSTRTAB: &[u8] = b"\0.text\0.shstrtab\0.dynstr\0.dynamic\0.dynsym\0";
+    const DYNSTR: &[u8] = b"\0libc.so.6\0socket\0";
+    const INTERP: &[u8] = b"/lib64/ld-linux-x86-64.so.2\0";
+
+    fn put16(data: &mut [u8], at: usize, v: u16) {
+        data[at..at + 2].copy_from_slice(&v.to_le_bytes());
+    }
+
+    fn put32(data: &mut [u8], at: usize, v: u32) {
+        data[at..at + 4].copy_from_slice(&v.to_le_bytes());
+    }
+
+    fn put64(data: &mut [u8], at: usize, v: u64) {
+        data[at..at + 8].copy_from_slice(&v.to_le_bytes());
+    }
+
+    fn set_phdr(data: &mut [u8], idx: usize, p_type: u32, flags: u32, offset: u64, size: u64) {
+        let at = PHOFF + idx * 56;
+        put32(data, at, p_type);
+        put32(data, at + 4, flags);
+        put64(data, at + 8, offset);
+        put64(data, at + 32, size);
+    }
+
+    /// (name, type, flags, addr, offset, size, link, entsize)
+    type Shdr = (u32, u32, u64, u64, u64, u64, u32, u64);
+
+    fn set_shdr(data: &mut [u8], idx: usize, h: Shdr) {
+        let at = SHOFF + idx * 64;
+        put32(data, at, h.0);
+        put32(data, at + 4, h.1);
+        put64(data, at + 8, h.2);
+        put64(data, at + 16, h.3);
+        put64(data, at + 24, h.4);
+        put64(data, at + 32, h.5);
+        put32(data, at + 40, h.6);
+        put64(data, at + 56, h.7);
+    }
+
+    /// ELF64 executable: interpreter, `.text`, and a dynamic section that
+    /// needs libc.so.6 and imports `socket`.
+    fn image() -> Vec<u8> {
+        let mut data = vec![0u8; 0x600];
+        data[..4].copy_from_slice(b"\x7fELF");
+        data[4] = 2;
+        data[5] = 1;
+        data[6] = 1;
+        put16(&mut data, 16, 2);
+        put16(&mut data, 18, 62);
+        put64(&mut data, 24, 0x40_1000);
+        put64(&mut data, 32, PHOFF as u64);
+        put64(&mut data, 40, SHOFF as u64);
+        put16(&mut data, 54, 56);
+        put16(&mut data, 56, 2);
+        put16(&mut data, 58, 64);
+        put16(&mut data, 60, 6);
+        put16(&mut data, 62, 2);
+
+        set_phdr(&mut data, 0, PT_INTERP, 4, 0x200, INTERP.len() as u64);
+        set_phdr(&mut data, 1, PT_LOAD, 5, 0, 0x600);
+        data[0x200..0x200 + INTERP.len()].copy_from_slice(INTERP);
+        data[0x280..0x280 + SHSTRTAB.len()].copy_from_slice(SHSTRTAB);
+        data[0x2c0..0x2c0 + DYNSTR.len()].copy_from_slice(DYNSTR);
+        put64(&mut data, 0x300, DT_NEEDED);
+        put64(&mut data, 0x308, 1);
+        put32(&mut data, 0x340 + 24, 11);
+
+        set_shdr(&mut data, 1, (1, 1, 6, 0x40_1000, 0x240, 0x40, 0, 0));
+        set_shdr(
+            &mut data,
+            2,
+            (7, 3, 0, 0, 0x280, SHSTRTAB.len() as u64, 0, 0),
+        );
+        set_shdr(
+            &mut data,
+            3,
+            (17, 3, 0, 0, 0x2c0, DYNSTR.len() as u64, 0, 0),
+        );
+        set_shdr(&mut data, 4, (25, SHT_DYNAMIC, 0, 0, 0x300, 32, 3, 16));
+        set_shdr(&mut data, 5, (34, SHT_DYNSYM, 0, 0, 0x340, 48, 3, 24));
+        data
+    }
+
+    fn check_bounds(name: &str, data: &[u8]) -> Option<ElfReport> {
+        let report = analyze(data)?;
+        assert!(report.sections.len() <= MAX_SECTIONS, "{name}");
+        assert!(report.needed.len() <= MAX_NEEDED, "{name}");
+        assert!(report.imports.len() <= MAX_REPORTED_IMPORTS, "{name}");
+        let names = report
+            .needed
+            .iter()
+            .chain(&report.imports)
+            .chain(&report.interpreter)
+            .chain(report.sections.iter().map(|s| &s.name));
+        for n in names {
+            assert!(n.len() <= MAX_NAME_LEN, "{name}");
+        }
+        Some(report)
+    }
+
+    #[test]
+    fn well_formed_image_parses() {
+        let report = check_bounds("image", &image()).unwrap();
+        assert_eq!(
+            report.interpreter.as_deref(),
+            Some("/lib64/ld-linux-x86-64.so.2")
+        );
+        assert_eq!(report.needed, vec!["libc.so.6"]);
+        assert_eq!(report.imports, vec!["socket"]);
+        let names: Vec<&str> = report.sections.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [".text", ".shstrtab", ".dynstr", ".dynamic", ".dynsym"]
+        );
+        assert!(report
+            .indicators
+            .iter()
+            .all(|i| i.id != "entry_outside_code"));
+    }
+
+    #[test]
+    fn upx_marker_only_counts_behind_the_headers() {
+        let mut data = image();
+        data[0x250..0x254].copy_from_slice(b"UPX!");
+        assert_eq!(analyze(&data).unwrap().packer, None);
+
+        // l_info: checksum, then the magic
+        let info = PHOFF + 2 * 56 + 4;
+        data[info..info + 4].copy_from_slice(b"UPX!");
+        assert_eq!(analyze(&data).unwrap().packer.as_deref(), Some("UPX"));
+    }
+
+    #[test]
+    fn every_truncation_is_handled() {
+        let data = image();
+        for len in 0..=data.len() {
+            check_bounds("truncated", &data[..len]);
+        }
+    }
+
+    #[test]
+    fn hostile_headers_stay_bounded() {
+        let edit = |f: &dyn Fn(&mut Vec<u8>)| {
+            let mut data = image();
+            f(&mut data);
+            data
+        };
+
+        let cases: Vec<(&str, Vec<u8>)> = vec![
+            ("bad class", edit(&|d| d[4] = 9)),
+            ("65535 program headers", edit(&|d| put16(d, 56, u16::MAX))),
+            (
+                "overlapping program headers",
+                edit(&|d| {
+                    put16(d, 54, 0);
+                    put16(d, 56, u16::MAX);
+                }),
+            ),
+            (
+                "program headers past EOF",
+                edit(&|d| put64(d, 32, u64::MAX - 8)),
+            ),
+            (
+                "interpreter past EOF",
+                edit(&|d| set_phdr(d, 0, PT_INTERP, 4, 0x200, u64::MAX)),
+            ),
+            ("65535 sections", edit(&|d| put16(d, 60, u16::MAX))),
+            ("overlapping sections", edit(&|d| put16(d, 58, 1))),
+            ("sections past EOF", edit(&|d| put64(d, 40, u64::MAX))),
+            (
+                "string table out of range",
+                edit(&|d| put16(d, 62, u16::MAX)),
+            ),
+            (
+                "section larger than the file",
+                edit(&|d| set_shdr(d, 1, (1, 1, 6, 0x40_1000, u64::MAX, u64::MAX, 0, 0))),
+            ),
+            (
+                "address range wraps",
+                edit(&|d| set_shdr(d, 1, (1, 1, 6, u64::MAX - 4, 0x240, 0x40, 0, 0))),
+            ),
+            (
+                "dynamic link out of range",
+                edit(&|d| set_shdr(d, 4, (25, SHT_DYNAMIC, 0, 0, 0x300, 32, u32::MAX, 16))),
+            ),
+            (
+                "huge dynamic table",
+                edit(&|d| set_shdr(d, 4, (25, SHT_DYNAMIC, 0, 0, 0x300, u64::MAX, 3, 16))),
+            ),
+            (
+                "huge symbol table",
+                edit(&|d| set_shdr(d, 5, (34, SHT_DYNSYM, 0, 0, 0x340, u64::MAX, 3, 1))),
+            ),
+        ];
+
+        for (name, data) in &cases {
+            check_bounds(name, data);
+        }
+
+        let get = |name: &str| {
+            let (_, data) = cases.iter().find(|(n, _)| *n == name).unwrap();
+            analyze(data)
+        };
+        assert!(get("bad class").is_none());
+        assert_eq!(get("interpreter past EOF").unwrap().interpreter, None);
+        assert!(get("sections past EOF").unwrap().sections.is_empty());
+        assert!(get("overlapping sections").unwrap().sections.is_empty());
+        let no_phdrs = get("overlapping program headers").unwrap();
+        assert!(no_phdrs.interpreter.is_none());
+    }
+
+    #[test]
+    fn long_unterminated_strings_are_not_copied() {
+        // 1 MB string table without a NUL, referenced from every dynamic entry
+        let mut data = image();
+        let table = data.len();
+        data.resize(table + (1 << 20), b'A');
+        let dynamic = data.len();
+        data.resize(dynamic + 4096 * 16, 0);
+        for i in 0..4096 {
+            put64(&mut data, dynamic + i * 16, DT_NEEDED);
+            put64(&mut data, dynamic + i * 16 + 8, i as u64 * 16);
+        }
+        set_shdr(&mut data, 3, (17, 3, 0, 0, table as u64, 1 << 20, 0, 0));
+        set_shdr(
+            &mut data,
+            4,
+            (25, SHT_DYNAMIC, 0, 0, dynamic as u64, 4096 * 16, 3, 16),
+        );
+        set_shdr(
+            &mut data,
+            5,
+            (34, SHT_DYNSYM, 0, 0, dynamic as u64, 4096 * 16, 3, 24),
+        );
+
+        let report = check_bounds("unterminated", &data).unwrap();
+        assert!(report.needed.is_empty());
+
+        // A NUL at the limit: the entry at offset 0 is still too long, the
+        // next one (offset 16) fits
+        data[table + MAX_NAME_LEN] = 0;
+        let report = check_bounds("at the limit", &data).unwrap();
+        assert_eq!(report.needed[0].len(), MAX_NAME_LEN - 16);
+
+        // Terminated names are kept, but only MAX_NEEDED of them
+        for i in 0..4096 {
+            data[table + i * 16 + 8] = 0;
+        }
+        let report = check_bounds("many needed", &data).unwrap();
+        assert_eq!(report.needed.len(), MAX_NEEDED);
+    }
+}
@@ -0,0 +1,242 @@
+// Shannon entropy helpers (bits per byte, 0.0 ..= 8.0).
+//
+// Compressed or encrypted data sits close to 8; native code is usually 5-6.5.
+// `EntropyCounter` profiles a whole file in fixed blocks while it is hashed, so
+// a partly encrypted file (ransomware often only encrypts the head) still shows.
+
+use serde::Serialize;
+
+pub(crate) const BLOCK_SIZE: usize = 64 * 1024;
+// A trailing block shorter than this can't reach a meaningful entropy
+const MIN_BLOCK: usize = 4096;
+
+/// Block entropy counted as "high" (compressed, encrypted or packed data).
+pub(crate) const HIGH_BLOCK_ENTROPY: f64 = 7.5;
+// Executables above this are probably packed or encrypted
+const PACKED_ENTROPY: f64 = 7.2;
+// Random-looking output: ciphertext scores ~7.99, good compression ~7.9
+const ENCRYPTED_ENTROPY: f64 = 7.9;
+
+fn from_counts(counts: &[u64; 256], len: u64) -> f64 {
+    if len == 0 {
+        return 0.0;
+    }
+
+    let len = len as f64;
+    counts
+        .iter()
+        .filter(|c| **c > 0)
+        .map(|c| {
+            let p = *c as f64 / len;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+pub(crate) fn shannon(data: &[u8]) -> f64 {
+    let mut counts = [0u64; 256];
+    for b in data {
+        counts[*b as usize] += 1;
+    }
+    from_counts(&counts, data.len() as u64)
+}
+
+/// Two decimals is plenty for reports and keeps JSON readable.
+pub(crate) fn rounded(e: f64) -> f64 {
+    (e * 100.0).round() / 100.0
+}
+
+/// Whole-file and per-block entropy of one file.
+#[derive(Serialize, Clone)]
+pub(crate) struct EntropyProfile {
+    pub(crate) entropy: f64,
+    pub(crate) block_size: usize,
+    pub(crate) blocks: usize,
+    pub(crate) high_blocks: usize, // blocks at or above HIGH_BLOCK_ENTROPY
+    pub(crate) head: f64,          // first block
+    pub(crate) min_block: f64,
+    pub(crate) max_block: f64,
+}
+
+impl EntropyProfile {
+    fn high_ratio(&self) -> f64 {
+        if self.blocks == 0 {
+            return 0.0;
+        }
+        self.high_blocks as f64 / self.blocks as f64
+    }
+
+    /// High entropy across most of an executable: packed or encrypted code.
+    pub(crate) fn looks_packed(&self) -> bool {
+        self.entropy >= PACKED_ENTROPY && self.high_ratio() >= 0.7
+    }
+
+    /// Near-random content as ransomware leaves behind: throughout, or just
+    /// in the first block (intermittent encryption).
+    pub(crate) fn looks_encrypted(&self) -> bool {
+        let whole =
+            self.blocks > 0 && self.entropy >= ENCRYPTED_ENTROPY && self.high_ratio() >= 0.9;
+        let head = self.blocks > 1 && self.head >= ENCRYPTED_ENTROPY;
+        whole || head
+    }
+}
+
+/// Streaming counterpart of `shannon`, fed alongside the digests.
+pub(crate) struct EntropyCounter {
+    total: [u64; 256],
+    total_len: u64,
+    block: [u64; 256],
+    block_len: usize,
+    blocks: Vec<f64>,
+}
+
+impl EntropyCounter {
+    pub(crate) fn new() -> Self {
+        EntropyCounter {
+            total: [0; 256],
+            total_len: 0,
+            block: [0; 256],
+            block_len: 0,
+            blocks: Vec::new(),
+        }
+    }
+
+    pub(crate) fn update(&mut self, mut data: &[u8]) {
+        while !data.is_empty() {
+            let take = data.len().min(BLOCK_SIZE - self.block_len);
+            for b in &data[..take] {
+                self.total[*b as usize] += 1;
+                self.block[*b as usize] += 1;
+            }
+            self.block_len += take;
+            self.total_len += take as u64;
+            data = &data[take..];
+
+            if self.block_len == BLOCK_SIZE {
+                self.close_block();
+            }
+        }
+    }
+
+    fn close_block(&mut self) {
+        self.blocks
+            .push(from_counts(&self.block, self.block_len as u64));
+        self.block = [0; 256];
+        self.block_len = 0;
+    }
+
+    pub(crate) fn finish(mut self) -> EntropyProfile {
+        if self.block_len >= MIN_BLOCK || (self.blocks.is_empty() && self.block_len > 0) {
+            self.close_block();
+        }
+
+        let blocks = &self.blocks;
+        EntropyProfile {
+            entropy: rounded(from_counts(&self.total, self.total_len)),
+            block_size: BLOCK_SIZE,
+            blocks: blocks.len(),
+            high_blocks: blocks.iter().filter(|e| **e >= HIGH_BLOCK_ENTROPY).count(),
+            head: rounded(blocks.first().copied().unwrap_or(0.0)),
+            min_block: rounded(blocks.iter().copied().reduce(f64::min).unwrap_or(0.0)),
+            max_block: rounded(blocks.iter().copied().reduce(f64::max).unwrap_or(0.0)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Deterministic bytes with entropy close to 8
+    fn noise(len: usize, mut seed: u64) -> Vec<u8> {
+        (0..len)
+            .map(|_| {
+                seed ^= seed << 13;
+                seed ^= seed >> 7;
+                seed ^= seed << 17;
+                (seed >> 24) as u8
+            })
+            .collect()
+    }
+
+    // Low-entropy filler, like text or zero padding
+    fn plain(len: usize) -> Vec<u8> {
+        b"the quick brown fox "
+            .iter()
+            .copied()
+            .cycle()
+            .take(len)
+            .collect()
+    }
+
+    fn profile(parts: &[&[u8]]) -> EntropyProfile {
+        let mut counter = EntropyCounter::new();
+        for p in parts {
+            counter.update(p);
+        }
+        counter.finish()
+    }
+
+    #[test]
+    fn shannon_bounds() {
+        assert_eq!(shannon(&[]), 0.0);
+        assert_eq!(shannon(&[7; 1000]), 0.0);
+        assert_eq!(shannon(&[0, 1]), 1.0);
+        let all: Vec<u8> = (0..=255).collect();
+        assert_eq!(shannon(&all), 8.0);
+        assert!(shannon(&noise(BLOCK_SIZE, 1)) > 7.99);
+    }
+
+    #[test]
+    fn streaming_matches_one_shot_whatever_the_chunking() {
+        let data = [noise(100_000, 3), plain(50_000)].concat();
+        let whole = profile(&[&data]);
+        let chunked = profile(&data.chunks(777).collect::<Vec<_>>());
+        assert_eq!(whole.entropy, rounded(shannon(&data)));
+        assert_eq!(chunked.entropy, whole.entropy);
+        assert_eq!(chunked.blocks, whole.blocks);
+        assert_eq!(chunked.min_block, whole.min_block);
+        assert_eq!(chunked.max_block, whole.max_block);
+    }
+
+    #[test]
+    fn short_tail_blocks_are_dropped_but_short_files_are_not() {
+        assert_eq!(profile(&[]).blocks, 0);
+        assert_eq!(profile(&[&plain(100)]).blocks, 1);
+        assert_eq!(profile(&[&plain(BLOCK_SIZE + MIN_BLOCK - 1)]).blocks, 1);
+        assert_eq!(profile(&[&plain(BLOCK_SIZE + MIN_BLOCK)]).blocks, 2);
+    }
+
+    #[test]
+    fn fully_encrypted_file() {
+        let p = profile(&[&noise(4 * BLOCK_SIZE, 5)]);
+        assert_eq!(p.blocks, 4);
+        assert_eq!(p.high_blocks, 4);
+        assert!(p.looks_encrypted());
+        assert!(p.looks_packed());
+    }
+
+    #[test]
+    fn intermittent_encryption_shows_in_the_head() {
+        let data = [noise(BLOCK_SIZE, 9), plain(5 * BLOCK_SIZE)].concat();
+        let p = profile(&[&data]);
+        assert_eq!(p.high_blocks, 1);
+        assert!(p.entropy < ENCRYPTED_ENTROPY);
+        assert!(p.looks_encrypted());
+        assert!(!p.looks_packed());
+    }
+
+    #[test]
+    fn plain_and_mostly_plain_files_look_neither() {
+        let p = profile(&[&plain(3 * BLOCK_SIZE)]);
+        assert!(!p.looks_encrypted());
+        assert!(!p.looks_packed());
+
+        // Random tail behind a normal head: neither the head nor 90% of blocks
+        let data = [plain(2 * BLOCK_SIZE), noise(2 * BLOCK_SIZE, 11)].concat();
+        let p = profile(&[&data]);
+        assert_eq!(p.high_blocks, 2);
+        assert!(!p.looks_encrypted());
+        assert!(!p.looks_packed());
+    }
+}
@@ -0,0 +1,162 @@
+// Thin wrapper over the Linux fanotify API (needs CAP_SYS_ADMIN).
+//
+// Unlike inotify, every fanotify event carries the PID of the process behind
+// it and an open descriptor of the file, which is what process attribution
+// builds on. Permission events go further: the opener waits for our answer,
+// which is how on-access blocking denies malicious files.
+
+use std::{
+    ffi::CString,
+    fs, io, mem,
+    os::{
+        fd::{AsRawFd, FromRawFd, OwnedFd},
+        unix::ffi::OsStrExt,
+    },
+    path::{Path, PathBuf},
+    ptr,
+};
+
+use libc::c_uint;
+
+pub(crate) struct Fanotify {
+    fd: OwnedFd,
+}
+
+pub(crate) struct FanEvent {
+    pub(crate) mask: u64,
+    pub(crate) pid: i32,
+    // Closed on drop; None for queue overflow events
+    file: Option<OwnedFd>,
+}
+
+impl FanEvent {
+    /// Path of the file the event is about, as the kernel resolves it now.
+    pub(crate) fn path(&self) -> Option<PathBuf> {
+        let fd = self.file.as_ref()?;
+        fs::read_link(format!("/proc/self/fd/{}", fd.as_raw_fd())).ok()
+    }
+
+    /// The file itself. Reading through it raises no further fanotify events,
+    /// unlike opening `path()` again.
+    pub(crate) fn file(&self) -> Option<fs::File> {
+        let fd = self.file.as_ref()?.try_clone().ok()?;
+        Some(fs::File::from(fd))
+    }
+}
+
+impl Fanotify {
+    /// `class` is one of the `FAN_CLASS_*` flags.
+    pub(crate) fn new(class: c_uint) -> io::Result<Self> {
+        let event_flags = (libc::O_RDONLY | libc::O_LARGEFILE | libc::O_CLOEXEC) as c_uint;
+        // SAFETY: plain syscall; the returned descriptor is owned below.
+        let fd = unsafe { libc::fanotify_init(class | libc::FAN_CLOEXEC, event_flags) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: `fd` is a fresh descriptor nobody else owns.
+        Ok(Fanotify {
+            fd: unsafe { OwnedFd::from_raw_fd(fd) },
+        })
+    }
+
+    fn mark(&self, flags: c_uint, path: Option<&Path>, mask: u64) -> io::Result<()> {
+        let c_path = path
+            .map(|p| CString::new(p.as_os_str().as_bytes()))
+            .transpose()
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "path contains NUL"))?;
+        let path_ptr = c_path.as_ref().map_or(ptr::null(), |p| p.as_ptr());
+        // SAFETY: `c_path` outlives the call; the kernel copies it.
+        let rc = unsafe {
+            libc::fanotify_mark(self.fd.as_raw_fd(), flags, mask, libc::AT_FDCWD, path_ptr)
+        };
+        if rc < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Subscribes to `mask` events on the whole mount containing `path`.
+    pub(crate) fn mark_mount(&self, path: &Path, mask: u64) -> io::Result<()> {
+        self.mark(libc::FAN_MARK_ADD | libc::FAN_MARK_MOUNT, Some(path), mask)
+    }
+
+    /// Subscribes to `mask` events on the files directly inside directory `dir`.
+    pub(crate) fn mark_dir(&self, dir: &Path, mask: u64) -> io::Result<()> {
+        let flags = libc::FAN_MARK_ADD | libc::FAN_MARK_ONLYDIR;
+        self.mark(flags, Some(dir), mask | libc::FAN_EVENT_ON_CHILD)
+    }
+
+    /// Drops every directory and file mark (mount marks stay).
+    pub(crate) fn clear_dir_marks(&self) -> io::Result<()> {
+        self.mark(libc::FAN_MARK_FLUSH, None, 0)
+    }
+
+    /// Answers a permission event; the opener stays blocked until we do.
+    pub(crate) fn respond(&self, event: &FanEvent, allow: bool) -> io::Result<()> {
+        let Some(fd) = event.file.as_ref() else {
+            return Ok(());
+        };
+        let response = libc::fanotify_response {
+            fd: fd.as_raw_fd(),
+            response: if allow {
+                libc::FAN_ALLOW
+            } else {
+                libc::FAN_DENY
+            },
+        };
+        let len = mem::size_of::<libc::fanotify_response>();
+        // SAFETY: `response` is a plain struct valid for `len` bytes.
+        let n = unsafe { libc::write(self.fd.as_raw_fd(), ptr::addr_of!(response).cast(), len) };
+        if n < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Blocks until at least one event is available.
+    pub(crate) fn read_events(&self) -> io::Result<Vec<FanEvent>> {
+        let mut buf = [0u8; 16 * 1024];
+        let n = loop {
+            // SAFETY: `buf` is valid for writes of its full length.
+            let n = unsafe { libc::read(self.fd.as_raw_fd(), buf.as_mut_ptr().cast(), buf.len()) };
+            if n >= 0 {
+                break n as usize;
+            }
+            let err = io::Error::last_os_error();
+            if err.kind() != io::ErrorKind::Interrupted {
+                return Err(err);
+            }
+        };
+
+        let header = mem::size_of::<libc::fanotify_event_metadata>();
+        let mut events = Vec::new();
+        let mut offset = 0;
+        while offset + header <= n {
+            // SAFETY: at least `header` bytes remain; the buffer may be unaligned.
+            let meta: libc::fanotify_event_metadata =
+                unsafe { ptr::read_unaligned(buf[offset..].as_ptr().cast()) };
+            if meta.vers != libc::FANOTIFY_METADATA_VERSION {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "unexpected fanotify metadata version",
+                ));
+            }
+
+            let len = meta.event_len as usize;
+            if len < header || offset + len > n {
+                break;
+            }
+
+            // SAFETY: the kernel handed us this descriptor with the event.
+            let file = (meta.fd >= 0).then(|| unsafe { OwnedFd::from_raw_fd(meta.fd) });
+            events.push(FanEvent {
+                mask: meta.mask,
+                pid: meta.pid,
+                file,
+            });
+            offset += len;
+        }
+
+        Ok(events)
+    }
+}
@@ -0,0 +1,391 @@
+// Content-based file type identification (magic bytes).
+//
+// `path.extension()` is whatever the sender chose; the first few KB of the
+// file are not. The detected type goes to the API as `file_type`, orders quick
+// scans and flags executables dressed up as documents or media.
+
+use std::{fs, io::Read, path::Path};
+
+use crate::pe;
+
+// Enough for every magic we check, including tar's at 257 and zip entry names.
+const SNIFF_BYTES: u64 = 4096;
+// A DOS header may point further in for the PE signature; followed up to here
+const MAX_PE_HEADER_OFFSET: u64 = 64 * 1024;
+/// `sniff` on this many leading bytes sees everything `detect` would read.
+pub(crate) const DETECT_BYTES: u64 = MAX_PE_HEADER_OFFSET;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum FileType {
+    Pe,
+    // DOS header without a PE header: a 16-bit DOS program
+    DosExe,
+    Elf,
+    MachO,
+    Script,
+    OfficeOle,
+    OfficeOoxml,
+    Pdf,
+    Rtf,
+    Zip,
+    Jar,
+    Apk,
+    Gzip,
+    Tar,
+    SevenZip,
+    Rar,
+    Png,
+    Jpeg,
+    Gif,
+    Bmp,
+    Webp,
+    Text,
+    Unknown,
+}
+
+impl FileType {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            FileType::Pe => "pe",
+            FileType::DosExe => "dos_exe",
+            FileType::Elf => "elf",
+            FileType::MachO => "macho",
+            FileType::Script => "script",
+            FileType::OfficeOle => "office_ole",
+            FileType::OfficeOoxml => "office_ooxml",
+            FileType::Pdf => "pdf",
+            FileType::Rtf => "rtf",
+            FileType::Zip => "zip",
+            FileType::Jar => "jar",
+            FileType::Apk => "apk",
+            FileType::Gzip => "gzip",
+            FileType::Tar => "tar",
+            FileType::SevenZip => "7z",
+            FileType::Rar => "rar",
+            FileType::Png => "png",
+            FileType::Jpeg => "jpeg",
+            FileType::Gif => "gif",
+            FileType::Bmp => "bmp",
+            FileType::Webp => "webp",
+            FileType::Text => "text",
+            FileType::Unknown => "unknown",
+        }
+    }
+
+    /// Content the OS (or an interpreter) will run directly.
+    pub(crate) fn is_executable(self) -> bool {
+        matches!(
+            self,
+            FileType::Pe
+                | FileType::DosExe
+                | FileType::Elf
+                | FileType::MachO
+                | FileType::Script
+                | FileType::Jar
+        )
+    }
+}
+
+fn sniff_zip(head: &[u8]) -> FileType {
+    let contains = |needle: &[u8]| head.windows(needle.len()).any(|w| w == needle);
+
+    if contains(b"AndroidManifest.xml") || contains(b"classes.dex") {
+        FileType::Apk
+    } else if contains(b"META-INF/MANIFEST.MF") || contains(b".class") {
+        FileType::Jar
+    } else if contains(b"[Content_Types].xml")
+        || contains(b"word/")
+        || contains(b"xl/")
+        || contains(b"ppt/")
+    {
+        FileType::OfficeOoxml
+    } else {
+        FileType::Zip
+    }
+}
+
+fn looks_like_text(head: &[u8]) -> bool {
+    if head.is_empty() {
+        return false;
+    }
+    // UTF-16 BOMs count as text
+    if head.starts_with(&[0xFF, 0xFE]) || head.starts_with(&[0xFE, 0xFF]) {
+        return true;
+    }
+    let printable = head
+        .iter()
+        .filter(|b| b.is_ascii_graphic() || b.is_ascii_whitespace() || **b >= 0x80)
+        .count();
+    !head.contains(&0) && printable * 100 / head.len() >= 95
+}
+
+/// Classifies raw leading bytes of a file.
+pub(crate) fn sniff(head: &[u8]) -> FileType {
+    let starts = |magic: &[u8]| head.starts_with(magic);
+
+    if pe::has_nt_header(head) {
+        return FileType::Pe;
+    }
+    if pe::has_dos_header(head) {
+        return FileType::DosExe;
+    }
+    if starts(b"\x7fELF") {
+        return FileType::Elf;
+    }
+    if starts(&[0xFE, 0xED, 0xFA, 0xCE])
+        || starts(&[0xFE, 0xED, 0xFA, 0xCF])
+        || starts(&[0xCE, 0xFA, 0xED, 0xFE])
+        || starts(&[0xCF, 0xFA, 0xED, 0xFE])
+    {
+        return FileType::MachO;
+    }
+    // 0xCAFEBABE is both Java class files and Mach-O fat binaries; fat headers
+    // carry a small architecture count where class files have a version >= 45.
+    if starts(&[0xCA, 0xFE, 0xBA, 0xBE]) {
+        return match head.get(4..8) {
+            Some(&[a, b, c, d]) if u32::from_be_bytes([a, b, c, d]) < 45 => FileType::MachO,
+            _ => FileType::Jar,
+        };
+    }
+    if starts(b"#!") {
+        return FileType::Script;
+    }
+    if starts(&[0xD0, 0xCF, 0x11, 0xE0, 0xA1, 0xB1, 0x1A, 0xE1]) {
+        return FileType::OfficeOle;
+    }
+    if starts(b"%PDF-") {
+        return FileType::Pdf;
+    }
+    if starts(b"{\\rtf") {
+        return FileType::Rtf;
+    }
+    if starts(b"PK\x03\x04") || starts(b"PK\x05\x06") {
+        return sniff_zip(head);
+    }
+    if starts(&[0x1F, 0x8B]) {
+        return FileType::Gzip;
+    }
+    if head.get(257..262) == Some(b"ustar") {
+        return FileType::Tar;
+    }
+    if starts(&[b'7', b'z', 0xBC, 0xAF, 0x27, 0x1C]) {
+        return FileType::SevenZip;
+    }
+    if starts(b"Rar!\x1a\x07") {
+        return FileType::Rar;
+    }
+    if starts(b"\x89PNG\r\n\x1a\n") {
+        return FileType::Png;
+    }
+    if starts(&[0xFF, 0xD8, 0xFF]) {
+        return FileType::Jpeg;
+    }
+    if starts(b"GIF87a") || starts(b"GIF89a") {
+        return FileType::Gif;
+    }
+    // Reserved header words are zero; keeps text starting with "BM" out
+    if starts(b"BM") && head.get(6..10) == Some(&[0, 0, 0, 0]) {
+        return FileType::Bmp;
+    }
+    if starts(b"RIFF") && head.get(8..12) == Some(b"WEBP") {
+        return FileType::Webp;
+    }
+    if looks_like_text(head) {
+        return FileType::Text;
+    }
+    FileType::Unknown
+}
+
+pub(crate) fn detect(path: &Path) -> FileType {
+    let Ok(file) = fs::File::open(path) else {
+        return FileType::Unknown;
+    };
+
+    let mut head = Vec::with_capacity(SNIFF_BYTES as usize);
+    if (&file).take(SNIFF_BYTES).read_to_end(&mut head).is_err() {
+        return FileType::Unknown;
+    }
+
+    // Read on to the PE signature when the DOS header points past the head
+    let nt_end = pe::nt_header_offset(&head).map(|off| off + 4);
+    if let Some(end) = nt_end.filter(|e| *e > SNIFF_BYTES && *e <= MAX_PE_HEADER_OFFSET) {
+        let more = end - head.len() as u64;
+        if (&file).take(more).read_to_end(&mut head).is_err() {
+            return FileType::Unknown;
+        }
+    }
+    sniff(&head)
+}
+
+/// Types an extension promises. `None` = extension we don't have an opinion on.
+fn expected_types(ext: &str) -> Option<&'static [FileType]> {
+    use FileType::*;
+
+    let types: &'static [FileType] = match ext {
+        "exe" => &[Pe, DosExe],
+        "dll" | "scr" | "sys" | "cpl" | "ocx" | "efi" => &[Pe],
+        "pdf" => &[Pdf],
+        "doc" | "xls" | "ppt" | "msg" => &[OfficeOle, Rtf],
+        "docx" | "xlsx" | "pptx" | "docm" | "xlsm" | "pptm" => &[OfficeOoxml, Zip],
+        "rtf" => &[Rtf],
+        "zip" => &[Zip, OfficeOoxml, Jar, Apk],
+        "jar" => &[Jar, Zip],
+        "apk" => &[Apk, Zip, Jar],
+        "gz" | "tgz" => &[Gzip],
+        "tar" => &[Tar],
+        "7z" => &[SevenZip],
+        "rar" => &[Rar],
+        "png" => &[Png],
+        "jpg" | "jpeg" => &[Jpeg],
+        "gif" => &[Gif],
+        "bmp" => &[Bmp],
+        "webp" => &[Webp],
+        "txt" | "csv" | "log" | "md" | "json" | "xml" | "html" | "htm" => &[Text],
+        "sh" | "bash" | "py" | "pl" | "rb" | "ps1" | "js" => &[Script, Text],
+        _ => return None,
+    };
+    Some(types)
+}
+
+fn extension_of(path: &Path) -> Option<String> {
+    path.extension()
+        .and_then(|s| s.to_str())
+        .map(|s| s.trim().to_lowercase())
+}
+
+/// True when the content contradicts the file's extension.
+pub(crate) fn extension_mismatch(path: &Path, actual: FileType) -> bool {
+    if actual == FileType::Unknown {
+        return false;
+    }
+    match extension_of(path).as_deref().and_then(expected_types) {
+        Some(expected) => !expected.contains(&actual),
+        None => false,
+    }
+}
+
+/// Whether the extension names a format we recognise by its magic bytes.
+pub(crate) fn is_known_extension(path: &Path) -> bool {
+    extension_of(path)
+        .as_deref()
+        .and_then(expected_types)
+        .is_some()
+}
+
+/// `report.pdf` or `report.pdf.locked`: the name says a format we know.
+pub(crate) fn claims_known_format(path: &Path) -> bool {
+    is_known_extension(path)
+        || path
+            .file_stem()
+            .is_some_and(|stem| is_known_extension(Path::new(stem)))
+}
+
+/// An executable hiding behind a non-executable extension (`report.pdf` that is a PE).
+pub(crate) fn is_disguised_executable(path: &Path, actual: FileType) -> bool {
+    actual.is_executable()
+        && actual != FileType::Script
+        && extension_mismatch(path, actual)
+        && extension_of(path)
+            .as_deref()
+            .and_then(expected_types)
+            .is_some_and(|expected| !expected.iter().any(|t| t.is_executable()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // DOS header as linkers write it for a stub: 0x90 bytes in the last of 3
+    // pages, a 4-paragraph header and an empty relocation table at 0x40
+    fn mz_pointing_at(e_lfanew: u32, len: usize) -> Vec<u8> {
+        let mut data = vec![0u8; len];
+        data[..2].copy_from_slice(b"MZ");
+        data[0x02..0x04].copy_from_slice(&0x90u16.to_le_bytes());
+        data[0x04..0x06].copy_from_slice(&3u16.to_le_bytes());
+        data[0x08..0x0a].copy_from_slice(&4u16.to_le_bytes());
+        data[0x18..0x1a].copy_from_slice(&0x40u16.to_le_bytes());
+        data[0x3c..0x40].copy_from_slice(&e_lfanew.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn claims_known_format_looks_behind_one_extra_extension() {
+        let cases: &[(&str, bool)] = &[
+            ("report.pdf", true),
+            ("Report.DOCX", true),
+            ("report.pdf.locked", true),
+            ("photo.jpg.encrypted", true),
+            ("archive.tar.gz", true),
+            ("report.locked", false),
+            ("report.pdf.locked.again", false),
+            ("notes", false),
+            (".pdf", false),
+        ];
+        for &(name, claims) in cases {
+            assert_eq!(claims_known_format(Path::new(name)), claims, "{name}");
+        }
+    }
+
+    #[test]
+    fn mz_needs_a_pe_signature() {
+        let mut pe = mz_pointing_at(0x80, 0x200);
+        pe[0x80..0x84].copy_from_slice(b"PE\0\0");
+        assert_eq!(sniff(&pe), FileType::Pe);
+
+        assert_eq!(sniff(&mz_pointing_at(0x80, 0x200)), FileType::DosExe);
+        assert_eq!(sniff(&mz_pointing_at(0xFFFF_FFF0, 0x200)), FileType::DosExe);
+    }
+
+    #[test]
+    fn mz_needs_a_plausible_dos_header() {
+        let set = |offset: usize, value: u16| {
+            let mut data = mz_pointing_at(0x80, 0x200);
+            data[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+            data
+        };
+        let cases: &[(&str, Vec<u8>)] = &[
+            (
+                "shorter than a header",
+                mz_pointing_at(0x80, 0x200)[..0x3f].to_vec(),
+            ),
+            ("only MZ", b"MZ".to_vec()),
+            ("zeroed header", {
+                let mut data = vec![0u8; 0x200];
+                data[..2].copy_from_slice(b"MZ");
+                data
+            }),
+            ("last page past a page", set(0x02, 512)),
+            ("no pages", set(0x04, 0)),
+            ("header under 32 bytes", set(0x08, 1)),
+            ("header past the image", set(0x08, 0x100)),
+            ("relocations inside the fixed fields", set(0x18, 0x10)),
+            ("relocations past the header", set(0x06, 1)),
+        ];
+        for (what, data) in cases {
+            assert_ne!(sniff(data), FileType::DosExe, "{what}");
+        }
+
+        let text = b"MZ is how this text starts, and it goes on long enough to cover a DOS header";
+        assert_eq!(sniff(text), FileType::Text);
+        assert!(!is_disguised_executable(
+            Path::new("notes.pdf"),
+            sniff(text)
+        ));
+    }
+
+    #[test]
+    fn detect_follows_a_far_pe_header() {
+        let mut pe = mz_pointing_at(0x2000, 0x2100);
+        pe[0x2000..0x2004].copy_from_slice(b"PE\0\0");
+        let path = std::env::temp_dir().join(format!("stellar-sniff-{}.exe", std::process::id()));
+        fs::write(&path, &pe).unwrap();
+        let detected = detect(&path);
+        let _ = fs::remove_file(&path);
+        assert_eq!(detected, FileType::Pe);
+        assert!(!extension_mismatch(Path::new("a.exe"), FileType::DosExe));
+        assert!(is_disguised_executable(
+            Path::new("a.pdf"),
+            FileType::DosExe
+        ));
+    }
+}
@@ -0,0 +1,659 @@
+// Fuzzy (context-triggered piecewise) hashes, ssdeep compatible, and the
+// similarity database for them and for TLSH (see `tlsh.rs`).
+//
+// A one-byte change defeats SHA-256; ssdeep digests of two variants still share
+// most of their characters. Both fuzzy digests are computed in the same read
+// as the SHA-256, sent to the API, and compared locally against
+// `<data dir>/fuzzy_hashes.json`:
+//
+//   { "max_distance": 30, "max_tlsh_distance": 30,
+//     "hashes": [ { "ssdeep": "96:abc...:xyz...", "tlsh": "T1...", "name": "Trojan.Foo",
+//                   "severity": "high" } ] }
+//
+// An entry needs at least one of the two digests. ssdeep distance is
+// `100 - similarity`, TLSH distance is its own unbounded scale; a file matches
+// an entry when either distance is within its threshold. ssdeep matches are
+// preferred, being the more specific of the two.
+
+use std::{
+    fs,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use serde::Deserialize;
+
+use crate::{app_data_dir, tlsh};
+
+// Below this ssdeep has too few blocks to compare meaningfully
+pub(crate) const MIN_FUZZY_SIZE: u64 = 4096;
+const DEFAULT_MAX_DISTANCE: u32 = 30;
+// The TLSH authors' cut-off for a low false-positive rate
+const DEFAULT_MAX_TLSH_DISTANCE: u32 = 30;
+
+const ROLLING_WINDOW: usize = 7;
+const MIN_BLOCKSIZE: u64 = 3;
+const SPAMSUM_LENGTH: usize = 64;
+const NUM_BLOCKHASHES: usize = 31;
+const HASH_PRIME: u32 = 0x0100_0193;
+const HASH_INIT: u32 = 0x2802_1967;
+const B64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+// Similarity DB cache, refreshed when the file changes on disk.
+static CACHE: Mutex<Option<(Option<SystemTime>, Arc<FuzzyDb>)>> = Mutex::new(None);
+
+// ---- Hashing ----
+
+fn block_size(i: usize) -> u64 {
+    MIN_BLOCKSIZE << i
+}
+
+fn sum_hash(c: u8, h: u32) -> u32 {
+    h.wrapping_mul(HASH_PRIME) ^ c as u32
+}
+
+#[derive(Default)]
+struct Roll {
+    window: [u8; ROLLING_WINDOW],
+    h1: u32,
+    h2: u32,
+    h3: u32,
+    n: usize,
+}
+
+impl Roll {
+    fn push(&mut self, c: u8) {
+        let slot = self.n % ROLLING_WINDOW;
+        self.h2 = self
+            .h2
+            .wrapping_sub(self.h1)
+            .wrapping_add(ROLLING_WINDOW as u32 * c as u32);
+        self.h1 = self
+            .h1
+            .wrapping_add(c as u32)
+            .wrapping_sub(self.window[slot] as u32);
+        self.window[slot] = c;
+        self.n = self.n.wrapping_add(1);
+        self.h3 = (self.h3 << 5) ^ c as u32;
+    }
+
+    fn sum(&self) -> u32 {
+        self.h1.wrapping_add(self.h2).wrapping_add(self.h3)
+    }
+}
+
+#[derive(Clone)]
+struct BlockHash {
+    h: u32,
+    half_h: u32,
+    digest: Vec<u8>,
+    // Character emitted at the last trigger once `digest` is full
+    tail: Option<u8>,
+    half_digest: Option<u8>,
+}
+
+impl BlockHash {
+    fn new() -> Self {
+        BlockHash {
+            h: HASH_INIT,
+            half_h: HASH_INIT,
+            digest: Vec::with_capacity(SPAMSUM_LENGTH),
+            tail: None,
+            half_digest: None,
+        }
+    }
+}
+
+/// Streaming ssdeep: feed chunks with `update`, then `finish`.
+pub(crate) struct FuzzyHasher {
+    roll: Roll,
+    blocks: Vec<BlockHash>,
+    total: u64,
+}
+
+impl FuzzyHasher {
+    pub(crate) fn new() -> Self {
+        FuzzyHasher {
+            roll: Roll::default(),
+            blocks: vec![BlockHash::new()],
+            total: 0,
+        }
+    }
+
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        self.total += data.len() as u64;
+        for &c in data {
+            self.step(c);
+        }
+    }
+
+    fn step(&mut self, c: u8) {
+        self.roll.push(c);
+        let h = self.roll.sum() as u64;
+
+        for b in self.blocks.iter_mut() {
+            b.h = sum_hash(c, b.h);
+            b.half_h = sum_hash(c, b.half_h);
+        }
+
+        // A block forked below is checked in this same step, as in libfuzzy
+        let mut i = 0;
+        while i < self.blocks.len() {
+            let bs = block_size(i);
+            // Block sizes double, so a miss here is a miss for every larger one
+            if h % bs != bs - 1 {
+                break;
+            }
+            if self.blocks[i].digest.is_empty()
+                && self.blocks.len() < NUM_BLOCKHASHES
+                && i == self.blocks.len() - 1
+            {
+                let last = &self.blocks[i];
+                let mut fork = BlockHash::new();
+                fork.h = last.h;
+                fork.half_h = last.half_h;
+                self.blocks.push(fork);
+            }
+
+            let b = &mut self.blocks[i];
+            let ch = B64[(b.h % 64) as usize];
+            b.half_digest = Some(B64[(b.half_h % 64) as usize]);
+            if b.digest.len() < SPAMSUM_LENGTH - 1 {
+                b.digest.push(ch);
+                b.tail = None;
+                b.h = HASH_INIT;
+                if b.digest.len() < SPAMSUM_LENGTH / 2 {
+                    b.half_h = HASH_INIT;
+                    b.half_digest = None;
+                }
+            } else {
+                b.tail = Some(ch);
+            }
+            i += 1;
+        }
+    }
+
+    /// `blocksize:digest:double_digest`, or `None` for files too large to hash.
+    pub(crate) fn finish(self) -> Option<String> {
+        let rolling = self.roll.sum();
+        let end = self.blocks.len();
+
+        let mut bi = 0;
+        while block_size(bi) * (SPAMSUM_LENGTH as u64) < self.total {
+            bi += 1;
+            if bi >= NUM_BLOCKHASHES {
+                return None;
+            }
+        }
+        bi = bi.min(end - 1);
+        while bi > 0 && self.blocks[bi].digest.len() < SPAMSUM_LENGTH / 2 {
+            bi -= 1;
+        }
+
+        let mut out = format!("{}:", block_size(bi)).into_bytes();
+        let b = &self.blocks[bi];
+        out.extend_from_slice(&b.digest);
+        if rolling != 0 {
+            out.push(B64[(b.h % 64) as usize]);
+        } else if let Some(t) = b.tail {
+            out.push(t);
+        }
+        out.push(b':');
+
+        if bi + 1 < end {
+            let b = &self.blocks[bi + 1];
+            let keep = b.digest.len().min(SPAMSUM_LENGTH / 2 - 1);
+            out.extend_from_slice(&b.digest[..keep]);
+            if rolling != 0 {
+                out.push(B64[(b.half_h % 64) as usize]);
+            } else if let Some(t) = b.half_digest {
+                out.push(t);
+            }
+        } else if rolling != 0 {
+            out.push(B64[(self.blocks[bi].h % 64) as usize]);
+        }
+
+        String::from_utf8(out).ok()
+    }
+}
+
+// ---- Comparison ----
+
+struct Parsed {
+    block_size: u64,
+    first: Vec<u8>,
+    second: Vec<u8>,
+}
+
+/// Runs of more than three identical characters carry no information.
+fn squeeze(s: &str) -> Vec<u8> {
+    let mut out: Vec<u8> = Vec::with_capacity(s.len());
+    for b in s.bytes() {
+        if out.len() >= 3 && out[out.len() - 3..].iter().all(|c| *c == b) {
+            continue;
+        }
+        out.push(b);
+    }
+    out
+}
+
+fn parse(digest: &str) -> Option<Parsed> {
+    let mut parts = digest.trim().splitn(3, ':');
+    let block_size = parts.next()?.parse::<u64>().ok()?;
+    let first = parts.next()?;
+    // Tools append `,"filename"`; ignore it
+    let second = parts.next()?.split(',').next()?;
+    Some(Parsed {
+        block_size,
+        first: squeeze(first),
+        second: squeeze(second),
+    })
+}
+
+fn has_common_substring(a: &[u8], b: &[u8]) -> bool {
+    if a.len() < ROLLING_WINDOW || b.len() < ROLLING_WINDOW {
+        return false;
+    }
+    a.windows(ROLLING_WINDOW)
+        .any(|w| b.windows(ROLLING_WINDOW).any(|v| v == w))
+}
+
+/// Levenshtein with substitutions costing 2 (insert + delete), as ssdeep does.
+fn edit_distance(a: &[u8], b: &[u8]) -> usize {
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut cur = vec![0; b.len() + 1];
+    for (i, ca) in a.iter().enumerate() {
+        cur[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let sub = prev[j] + if ca == cb { 0 } else { 2 };
+            cur[j + 1] = sub.min(prev[j + 1] + 1).min(cur[j] + 1);
+        }
+        std::mem::swap(&mut prev, &mut cur);
+    }
+    prev[b.len()]
+}
+
+fn score_strings(a: &[u8], b: &[u8], block_size: u64) -> u32 {
+    if a.len() > SPAMSUM_LENGTH || b.len() > SPAMSUM_LENGTH || !has_common_substring(a, b) {
+        return 0;
+    }
+
+    let dist = edit_distance(a, b) * SPAMSUM_LENGTH / (a.len() + b.len());
+    let dist = 100 * dist / SPAMSUM_LENGTH;
+    if dist >= 100 {
+        return 0;
+    }
+    let score = 100 - dist as u64;
+
+    // Small block sizes: don't claim more similarity than the digests can show
+    let cap_from = (99 + ROLLING_WINDOW as u64) / ROLLING_WINDOW as u64 * MIN_BLOCKSIZE;
+    if block_size >= cap_from {
+        return score as u32;
+    }
+    let cap = block_size / MIN_BLOCKSIZE * a.len().min(b.len()) as u64;
+    score.min(cap) as u32
+}
+
+/// ssdeep similarity, 0 (unrelated) to 100 (identical).
+pub(crate) fn similarity(a: &str, b: &str) -> u32 {
+    let (Some(a), Some(b)) = (parse(a), parse(b)) else {
+        return 0;
+    };
+
+    if a.block_size == b.block_size {
+        if a.first == b.first {
+            return 100;
+        }
+        score_strings(&a.first, &b.first, a.block_size).max(score_strings(
+            &a.second,
+            &b.second,
+            a.block_size * 2,
+        ))
+    } else if a.block_size == b.block_size * 2 {
+        score_strings(&a.first, &b.second, a.block_size)
+    } else if b.block_size == a.block_size * 2 {
+        score_strings(&a.second, &b.first, b.block_size)
+    } else {
+        0
+    }
+}
+
+// ---- Similarity database ----
+
+#[derive(Deserialize)]
+struct DbFile {
+    #[serde(default = "default_max_distance")]
+    max_distance: u32,
+    #[serde(default = "default_max_tlsh_distance")]
+    max_tlsh_distance: u32,
+    #[serde(default)]
+    hashes: Vec<DbEntry>,
+}
+
+#[derive(Deserialize)]
+struct DbEntry {
+    #[serde(default)]
+    ssdeep: Option<String>,
+    #[serde(default)]
+    tlsh: Option<String>,
+    name: String,
+    #[serde(default = "default_severity")]
+    severity: String,
+}
+
+fn default_max_distance() -> u32 {
+    DEFAULT_MAX_DISTANCE
+}
+
+fn default_max_tlsh_distance() -> u32 {
+    DEFAULT_MAX_TLSH_DISTANCE
+}
+
+fn default_severity() -> String {
+    "medium".to_string()
+}
+
+pub(crate) struct FuzzyDb {
+    max_distance: u32,
+    max_tlsh_distance: u32,
+    entries: Vec<DbEntry>,
+}
+
+pub(crate) struct FuzzyMatch {
+    pub(crate) name: String,
+    pub(crate) severity: String,
+    /// "ssdeep" or "tlsh"
+    pub(crate) algorithm: &'static str,
+    pub(crate) distance: u32,
+}
+
+impl FuzzyMatch {
+    fn of(entry: &DbEntry, algorithm: &'static str, distance: u32) -> Self {
+        FuzzyMatch {
+            name: entry.name.clone(),
+            severity: entry.severity.to_lowercase(),
+            algorithm,
+            distance,
+        }
+    }
+
+    /// Why the file was flagged, for the detection record.
+    pub(crate) fn reason(&self) -> String {
+        match self.algorithm {
+            "ssdeep" => format!(
+                "ssdeep {}% similar to a known sample (distance {})",
+                100 - self.distance,
+                self.distance
+            ),
+            algorithm => format!(
+                "{} distance {} to a known sample",
+                algorithm.to_uppercase(),
+                self.distance
+            ),
+        }
+    }
+}
+
+impl FuzzyDb {
+    /// Closest entry within the distance thresholds, by ssdeep first and TLSH
+    /// otherwise.
+    pub(crate) fn best_match(
+        &self,
+        ssdeep: Option<&str>,
+        tlsh_digest: Option<&str>,
+    ) -> Option<FuzzyMatch> {
+        let by_ssdeep = ssdeep.and_then(|digest| {
+            self.entries
+                .iter()
+                .filter_map(|e| Some((e, 100 - similarity(digest, e.ssdeep.as_deref()?))))
+                .filter(|(_, distance)| *distance <= self.max_distance)
+                .min_by_key(|(_, distance)| *distance)
+                .map(|(e, distance)| FuzzyMatch::of(e, "ssdeep", distance))
+        });
+
+        by_ssdeep.or_else(|| {
+            let digest = tlsh_digest?;
+            self.entries
+                .iter()
+                .filter_map(|e| Some((e, tlsh::distance(digest, e.tlsh.as_deref()?)?)))
+                .filter(|(_, distance)| *distance <= self.max_tlsh_distance)
+                .min_by_key(|(_, distance)| *distance)
+                .map(|(e, distance)| FuzzyMatch::of(e, "tlsh", distance))
+        })
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+fn db_path() -> PathBuf {
+    app_data_dir().join("fuzzy_hashes.json")
+}
+
+fn db_mtime() -> Option<SystemTime> {
+    fs::metadata(db_path()).and_then(|m| m.modified()).ok()
+}
+
+/// Missing file means an empty database; a broken one is an error.
+fn load_db() -> Result<FuzzyDb, String> {
+    let file = match fs::read(db_path()) {
+        Ok(bytes) => serde_json::from_slice::<DbFile>(&bytes)
+            .map_err(|e| format!("Failed to parse fuzzy_hashes.json: {e}"))?,
+        Err(_) => DbFile {
+            max_distance: DEFAULT_MAX_DISTANCE,
+            max_tlsh_distance: DEFAULT_MAX_TLSH_DISTANCE,
+            hashes: vec![],
+        },
+    };
+
+    // Unusable digests are dropped; an entry needs at least one usable one
+    let entries: Vec<DbEntry> = file
+        .hashes
+        .into_iter()
+        .map(|mut e| {
+            e.ssdeep = e.ssdeep.filter(|d| parse(d).is_some());
+            e.tlsh = e.tlsh.filter(|d| tlsh::is_valid(d));
+            e
+        })
+        .filter(|e| e.ssdeep.is_some() || e.tlsh.is_some())
+        .collect();
+
+    Ok(FuzzyDb {
+        max_distance: file.max_distance.min(100),
+        max_tlsh_distance: file.max_tlsh_distance,
+        entries,
+    })
+}
+
+/// Current database; a broken file logs and keeps the last good one.
+pub(crate) fn current() -> Arc<FuzzyDb> {
+    let mtime = db_mtime();
+    let mut cache = CACHE.lock().unwrap_or_else(|e| e.into_inner());
+
+    if let Some((cached_mtime, db)) = cache.as_ref() {
+        if *cached_mtime == mtime {
+            return db.clone();
+        }
+    }
+
+    let db = match load_db() {
+        Ok(db) => Arc::new(db),
+        Err(e) => {
+            eprintln!("[FUZZY] {e}");
+            match cache.as_ref() {
+                Some((_, previous)) => previous.clone(),
+                None => Arc::new(FuzzyDb {
+                    max_distance: DEFAULT_MAX_DISTANCE,
+                    max_tlsh_distance: DEFAULT_MAX_TLSH_DISTANCE,
+                    entries: vec![],
+                }),
+            }
+        }
+    };
+
+    *cache = Some((mtime, db.clone()));
+    db
+}
+
+// ---- Commands ----
+
+/// Re-reads the similarity database, returning the number of entries or the
+/// parse error.
+#[tauri::command]
+pub(crate) fn reload_fuzzy_hashes() -> Result<usize, String> {
+    let db = load_db()?;
+    let count = db.len();
+
+    let mut cache = CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    *cache = Some((db_mtime(), Arc::new(db)));
+
+    println!("[FUZZY] reloaded: {count} fuzzy hash(es)");
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Examples from the python-ssdeep README, produced by libfuzzy
+    const CTPH_MIXED: &str =
+        "Also called fuzzy hashes, Ctph can match inputs that have homologies.";
+    const CTPH_UPPER: &str =
+        "Also called fuzzy hashes, CTPH can match inputs that have homologies.";
+    const CTPH_MIXED_DIGEST: &str = "3:AXGBicFlgVNhBGcL6wCrFQEv:AXGHsNhxLsr2C";
+    const CTPH_UPPER_DIGEST: &str = "3:AXGBicFlIHBGcL6wCrFQEv:AXGH6xLsr2C";
+
+    fn digest(data: &[u8]) -> String {
+        let mut h = FuzzyHasher::new();
+        h.update(data);
+        h.finish().unwrap()
+    }
+
+    // Deterministic xorshift bytes; enough triggers to fill every block
+    fn noise(len: usize) -> Vec<u8> {
+        let mut x: u32 = 0x9e37_79b9;
+        (0..len)
+            .map(|_| {
+                x ^= x << 13;
+                x ^= x >> 17;
+                x ^= x << 5;
+                x as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn small_inputs_match_libfuzzy() {
+        assert_eq!(digest(b""), "3::");
+        assert_eq!(digest(CTPH_MIXED.as_bytes()), CTPH_MIXED_DIGEST);
+        assert_eq!(digest(CTPH_UPPER.as_bytes()), CTPH_UPPER_DIGEST);
+    }
+
+    #[test]
+    fn scores_match_libfuzzy() {
+        assert_eq!(similarity(CTPH_MIXED_DIGEST, CTPH_UPPER_DIGEST), 22);
+        assert_eq!(similarity(CTPH_UPPER_DIGEST, CTPH_MIXED_DIGEST), 22);
+        assert_eq!(similarity(CTPH_MIXED_DIGEST, CTPH_MIXED_DIGEST), 100);
+        // ssdeep's `,"filename"` suffix is ignored
+        let listed = format!("{CTPH_UPPER_DIGEST},\"homologies.txt\"");
+        assert_eq!(similarity(CTPH_MIXED_DIGEST, &listed), 22);
+    }
+
+    #[test]
+    fn long_digests_keep_the_tail_character() {
+        // Both inputs trigger more often than the digests can hold
+        for len in [10_000, 1_000_000] {
+            let data = noise(len);
+            let d = digest(&data);
+            let parts: Vec<&str> = d.split(':').collect();
+            assert_eq!(parts.len(), 3, "{d}");
+            assert_eq!(parts[1].len(), SPAMSUM_LENGTH, "{d}");
+            assert_eq!(parts[2].len(), SPAMSUM_LENGTH / 2, "{d}");
+
+            // Streaming in odd chunks gives the same digest
+            let mut h = FuzzyHasher::new();
+            for chunk in data.chunks(4093) {
+                h.update(chunk);
+            }
+            assert_eq!(h.finish().unwrap(), d);
+        }
+    }
+
+    #[test]
+    fn block_sizes_must_match_or_double() {
+        let (_, tail) = CTPH_MIXED_DIGEST.split_once(':').unwrap();
+        let (_, second) = tail.split_once(':').unwrap();
+
+        // Unrelated sizes never compare, even with identical digests
+        assert_eq!(similarity(CTPH_MIXED_DIGEST, &format!("12:{tail}")), 0);
+        assert_eq!(similarity(CTPH_MIXED_DIGEST, &format!("96:{tail}")), 0);
+
+        // Double size: the double digest meets the other side's first digest.
+        // At block size 6 the score is capped at 6 / 3 * 13 characters.
+        let doubled = format!("6:{second}:AAAA");
+        assert_eq!(similarity(CTPH_MIXED_DIGEST, &doubled), 26);
+        assert_eq!(similarity(&doubled, CTPH_MIXED_DIGEST), 26);
+    }
+
+    #[test]
+    fn database_matches_by_ssdeep_then_tlsh() {
+        let tlsh_of = |data: &[u8]| {
+            let mut h = tlsh::TlshHasher::new();
+            h.update(data);
+            h.finish().unwrap()
+        };
+        let entry = |ssdeep: Option<&str>, tlsh: Option<String>, name: &str| DbEntry {
+            ssdeep: ssdeep.map(str::to_string),
+            tlsh,
+            name: name.to_string(),
+            severity: "high".to_string(),
+        };
+
+        let sample = noise(20_000);
+        let mut variant = sample.clone();
+        variant[5000..5200].fill(0);
+        let db = FuzzyDb {
+            max_distance: DEFAULT_MAX_DISTANCE,
+            max_tlsh_distance: DEFAULT_MAX_TLSH_DISTANCE,
+            entries: vec![
+                entry(Some(CTPH_MIXED_DIGEST), None, "Text.Sample"),
+                entry(None, Some(tlsh_of(&sample)), "Noise.Sample"),
+            ],
+        };
+
+        let hit = db.best_match(Some(CTPH_UPPER_DIGEST), None);
+        assert!(hit.is_none(), "similarity 22 is past the ssdeep threshold");
+        let hit = db.best_match(Some(CTPH_MIXED_DIGEST), None).unwrap();
+        assert_eq!(
+            (hit.name.as_str(), hit.algorithm, hit.distance),
+            ("Text.Sample", "ssdeep", 0)
+        );
+        assert_eq!(
+            hit.reason(),
+            "ssdeep 100% similar to a known sample (distance 0)"
+        );
+
+        let hit = db
+            .best_match(Some(&digest(&variant)), Some(&tlsh_of(&variant)))
+            .unwrap();
+        assert_eq!((hit.name.as_str(), hit.algorithm), ("Noise.Sample", "tlsh"));
+        assert!(hit.distance <= DEFAULT_MAX_TLSH_DISTANCE);
+        assert!(hit.reason().starts_with("TLSH distance "));
+
+        let unrelated: Vec<u8> = (0..20_000u32).map(|i| (i * i % 251) as u8).collect();
+        assert!(db.best_match(None, Some(&tlsh_of(&unrelated))).is_none());
+    }
+
+    #[test]
+    fn oversized_digests_do_not_score() {
+        let first =
+            String::from_utf8(noise(65).iter().map(|b| B64[(b % 64) as usize]).collect()).unwrap();
+        let a = format!("96:{first}:");
+        let b = format!("96:{}x:", &first[..64]);
+        assert_eq!(similarity(&a, &b), 0);
+        assert_eq!(similarity(&a, &a), 100);
+        assert_eq!(similarity("not a digest", CTPH_MIXED_DIGEST), 0);
+    }
+}
@@ -0,0 +1,177 @@
+// File digests: MD5, SHA-1, SHA-256, ssdeep and TLSH from a single read, plus the
+// entropy profile of the same bytes. The read can also keep the head of the
+// file, so type sniffing, structure analysis and signatures need no re-read.
+//
+// SHA-256 is what our API keys on; MD5 and SHA-1 are what most imported intel
+// feeds use, and the fuzzy hashes catch near-copies. Reading a file once for all of
+// them keeps full scans I/O bound rather than paying per digest.
+
+use std::{fs::File, io::Read, path::Path};
+
+use md5::Md5;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+use crate::{entropy, filetype, fuzzy, tlsh};
+
+/// Every digest we take of a file's content (lowercase hex), plus its entropy.
+#[derive(Clone)]
+pub(crate) struct FileHashes {
+    pub(crate) md5: String,
+    pub(crate) sha1: String,
+    pub(crate) sha256: String,
+    pub(crate) ssdeep: Option<String>, // None below fuzzy::MIN_FUZZY_SIZE
+    pub(crate) tlsh: Option<String>,   // None when too short or uniform
+    pub(crate) entropy: entropy::EntropyProfile,
+}
+
+/// Feeds one stream of bytes to every digest.
+pub(crate) struct MultiHasher {
+    md5: Md5,
+    sha1: Sha1,
+    sha256: Sha256,
+    fuzzy: fuzzy::FuzzyHasher,
+    tlsh: tlsh::TlshHasher,
+    entropy: entropy::EntropyCounter,
+    total: u64,
+}
+
+impl MultiHasher {
+    pub(crate) fn new() -> Self {
+        MultiHasher {
+            md5: Md5::new(),
+            sha1: Sha1::new(),
+            sha256: Sha256::new(),
+            fuzzy: fuzzy::FuzzyHasher::new(),
+            tlsh: tlsh::TlshHasher::new(),
+            entropy: entropy::EntropyCounter::new(),
+            total: 0,
+        }
+    }
+
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        self.md5.update(data);
+        self.sha1.update(data);
+        self.sha256.update(data);
+        self.fuzzy.update(data);
+        self.tlsh.update(data);
+        self.entropy.update(data);
+        self.total += data.len() as u64;
+    }
+
+    pub(crate) fn finish(self) -> FileHashes {
+        FileHashes {
+            md5: hex::encode(self.md5.finalize()),
+            sha1: hex::encode(self.sha1.finalize()),
+            sha256: hex::encode(self.sha256.finalize()),
+            ssdeep: (self.total >= fuzzy::MIN_FUZZY_SIZE)
+                .then(|| self.fuzzy.finish())
+                .flatten(),
+            tlsh: self.tlsh.finish(),
+            entropy: self.entropy.finish(),
+        }
+    }
+}
+
+pub(crate) fn hash_bytes(data: &[u8]) -> FileHashes {
+    let mut hasher = MultiHasher::new();
+    hasher.update(data);
+    hasher.finish()
+}
+
+/// Digests of a whole file plus the leading bytes kept from the same read.
+pub(crate) struct HashedContent {
+    pub(crate) hashes: FileHashes,
+    /// Bytes hashed, i.e. the file size as read.
+    pub(crate) size: u64,
+    pub(crate) data: Vec<u8>,
+}
+
+impl HashedContent {
+    /// Whether `data` is the whole file rather than a prefix of it.
+    pub(crate) fn complete(&self) -> bool {
+        self.data.len() as u64 == self.size
+    }
+}
+
+/// Hashes `path` and keeps a prefix of it for content checks. `keep` sees the
+/// first `filetype::DETECT_BYTES` (or the whole file if smaller) and the size
+/// from metadata, and returns how many bytes to keep.
+pub(crate) fn hash_file_keeping(
+    path: &Path,
+    keep: impl FnOnce(&[u8], u64) -> u64,
+) -> Option<HashedContent> {
+    let mut file = File::open(path).ok()?;
+    let size = file.metadata().ok()?.len();
+    let mut hasher = MultiHasher::new();
+
+    let mut data = Vec::new();
+    (&mut file)
+        .take(filetype::DETECT_BYTES)
+        .read_to_end(&mut data)
+        .ok()?;
+    hasher.update(&data);
+    let mut total = data.len() as u64;
+
+    let limit = usize::try_from(keep(&data, size)).unwrap_or(usize::MAX);
+    data.truncate(limit);
+    let expected = usize::try_from(size).unwrap_or(usize::MAX);
+    data.reserve(limit.min(expected).saturating_sub(data.len()));
+
+    let mut buf = [0u8; 64 * 1024];
+    loop {
+        let n = file.read(&mut buf).ok()?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+        total += n as u64;
+        let room = limit.saturating_sub(data.len()).min(n);
+        data.extend_from_slice(&buf[..room]);
+    }
+
+    Some(HashedContent {
+        hashes: hasher.finish(),
+        size: total,
+        data,
+    })
+}
+
+pub(crate) fn hash_file(path: &Path) -> Option<FileHashes> {
+    hash_file_keeping(path, |_, _| 0).map(|c| c.hashes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn one_read_hashes_all_and_keeps_the_head() {
+        let data: Vec<u8> = (0..300_000u32).map(|i| (i % 251) as u8).collect();
+        let path = std::env::temp_dir().join(format!("stellar-hash-{}.bin", std::process::id()));
+        std::fs::write(&path, &data).unwrap();
+
+        let mut seen = (0, 0);
+        let head = hash_file_keeping(&path, |head, size| {
+            seen = (head.len(), size);
+            1000
+        });
+        let whole = hash_file_keeping(&path, |_, size| size);
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(seen, (filetype::DETECT_BYTES as usize, data.len() as u64));
+        let (head, whole) = (head.unwrap(), whole.unwrap());
+        assert_eq!(head.data, data[..1000]);
+        assert!(!head.complete());
+        assert_eq!(whole.data, data);
+        assert!(whole.complete());
+
+        let expected = hash_bytes(&data);
+        for got in [&head.hashes, &whole.hashes] {
+            assert_eq!(got.sha256, expected.sha256);
+            assert_eq!(got.md5, expected.md5);
+            assert_eq!(got.ssdeep, expected.ssdeep);
+            assert_eq!(got.tlsh, expected.tlsh);
+        }
+    }
+}
@@ -1,19 +1,25 @@
 use std::{
+    collections::HashMap,
     fs,
-    io::Read,
+    io::Write,
     path::{Path, PathBuf},
     sync::{
         atomic::{AtomicBool, Ordering},
-        mpsc,
+        mpsc, Arc, Mutex,
     },
     thread,
-    time::Duration,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
-use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use ed25519_dalek::{Signature, VerifyingKey};
+use flate2::{write::GzEncoder, Compression};
+use notify::{
+    event::{AccessKind, AccessMode, CreateKind, ModifyKind, RenameMode},
+    Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher,
+};
 use reqwest::blocking::Client;
 use serde::{Deserialize, Serialize};
-use sha2::{Digest, Sha256};
 use tauri::{
     menu::{MenuBuilder, MenuItemBuilder},
     tray::TrayIconBuilder,
@@ -23,11 +29,52 @@ use tauri_plugin_autostart::MacosLauncher;
 use tauri_plugin_notification::NotificationExt;
 use walkdir::WalkDir;
 
+mod allowlist;
+mod archive;
+mod behaviour;
+mod canary;
+mod dispatch;
+mod documents;
+mod eicar;
+mod elf;
+mod entropy;
+#[cfg(target_os = "linux")]
+mod fanotify;
+mod filetype;
+mod fuzzy;
+mod hashing;
+mod onaccess;
+mod pe;
+mod pinning;
+mod process;
+mod rules;
+mod scripts;
+mod settle;
+mod signatures;
+mod submission;
+mod tlsh;
+
 // ---- Global state ----
 
 static REALTIME_ENABLED: AtomicBool = AtomicBool::new(true);
 static ALLOW_EXIT: AtomicBool = AtomicBool::new(false);
 
+// Bearer token of the signed-in account. Memory only: the frontend owns the
+// persisted copy and hands it over on startup / login.
+static SESSION_TOKEN: Mutex<Option<String>> = Mutex::new(None);
+
+// API client built from the network settings; see `http_client`.
+static HTTP_CLIENT: Mutex<Option<Client>> = Mutex::new(None);
+
+// Set once the threat API advertised `Accept-Encoding: gzip` on a response.
+static API_ACCEPTS_GZIP: AtomicBool = AtomicBool::new(false);
+
+// How often the realtime watcher checks whether pending writes have finished
+const REALTIME_SETTLE_TICK: Duration = Duration::from_millis(250);
+
+// A tampered canary alerts once per this window, then is only re-planted
+const CANARY_ALERT_COOLDOWN: Duration = Duration::from_secs(30);
+
 // Detect autostart launches (so release builds can boot silently)
 const AUTOSTART_ARG: &str = "--autostart";
 
@@ -42,6 +89,22 @@ const HTTP_CONNECT_TIMEOUT_SECS: u64 = 10;
 const HTTP_TOTAL_TIMEOUT_SECS: u64 = 45;
 const HTTP_RETRIES: usize = 1;
 
+// Error returned by the API helpers when the backend rejected our session token.
+const SESSION_EXPIRED_ERR: &str = "Session expired";
+
+// ---- Response integrity ----
+
+// Prefix of every error raised because a verdict could not be trusted.
+const INTEGRITY_ERR_PREFIX: &str = "Threat API integrity check failed";
+// Detached Ed25519 signature (base64) over "{path}\n{timestamp}\n" + body.
+const RESPONSE_SIGNATURE_HEADER: &str = "x-stellar-signature";
+// Unix seconds the server signed at; part of the signed message
+const RESPONSE_TIMESTAMP_HEADER: &str = "x-stellar-signature-timestamp";
+// Signed responses older (or further ahead) than this are rejected as replays
+const RESPONSE_MAX_AGE_SECS: u64 = 5 * 60;
+// Base64 Ed25519 public key baked in at build time.
+const RESPONSE_SIGNING_PUBLIC_KEY: Option<&str> = option_env!("STELLAR_RESPONSE_SIGNING_PUBKEY");
+
 // ---- Scan tuning ----
 
 const QUICK_MAX_FILE_BYTES: u64 = 25 * 1024 * 1024; // 25 MB
@@ -53,6 +116,14 @@ const FULL_MAX_FILE_BYTES: u64 = 200 * 1024 * 1024; // 200 MB
 struct RuntimeConfig {
     realtime_enabled: bool,
     shown_background_hint: bool,
+    #[serde(default)]
+    network: NetworkConfig,
+    #[serde(default)]
+    sample_submission: submission::SampleSubmissionConfig,
+    /// Deny opening files with malicious verdicts (Linux fanotify); otherwise
+    /// realtime protection only observes.
+    #[serde(default)]
+    on_access_blocking: bool,
 }
 
 impl Default for RuntimeConfig {
@@ -60,18 +131,58 @@ impl Default for RuntimeConfig {
         Self {
             realtime_enabled: true,
             shown_background_hint: false,
+            network: NetworkConfig::default(),
+            sample_submission: submission::SampleSubmissionConfig::default(),
+            on_access_blocking: false,
         }
     }
 }
 
-fn config_path() -> PathBuf {
+/// Outbound network settings for corporate environments.
+#[derive(Serialize, Deserialize, Clone, Default)]
+struct NetworkConfig {
+    /// Explicit proxy; when unset reqwest falls back to the system proxy env vars.
+    #[serde(default)]
+    proxy: Option<ProxyConfig>,
+    /// PEM files with extra root CAs (e.g. a TLS-inspecting corporate CA).
+    #[serde(default)]
+    extra_ca_files: Vec<String>,
+    /// `sha256/<base64>` hashes of the threat API leaf certificate's SPKI.
+    /// Empty = no pinning.
+    #[serde(default)]
+    spki_pins: Vec<String>,
+    /// Reject threat API responses without a valid detached signature.
+    #[serde(default)]
+    require_signed_responses: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct ProxyConfig {
+    /// `http://`, `https://`, `socks5://` or `socks5h://` URL.
+    url: String,
+    #[serde(default)]
+    username: Option<String>,
+    /// Stored as entered; never sent back to the UI (see `get_network_config`).
+    #[serde(default)]
+    password: Option<String>,
+    /// Reported to the UI in place of the password.
+    #[serde(default, skip_deserializing)]
+    password_set: bool,
+    /// Hosts / domains / CIDRs that bypass the proxy (same syntax as `NO_PROXY`).
+    #[serde(default)]
+    no_proxy: Vec<String>,
+}
+
+fn app_data_dir() -> PathBuf {
     let base_dir = dirs::data_dir()
         .or_else(dirs::home_dir)
         .unwrap_or_else(|| PathBuf::from("."));
 
-    base_dir
-        .join("StellarAntivirus")
-        .join("runtime_config.json")
+    base_dir.join("StellarAntivirus")
+}
+
+fn config_path() -> PathBuf {
+    app_data_dir().join("runtime_config.json")
 }
 
 fn load_runtime_config() -> RuntimeConfig {
@@ -105,7 +216,153 @@ struct ScanProgressPayload {
 
 #[derive(Serialize, Clone)]
 struct ScanFinishedPayload {
-    threats: Vec<(String, String)>, // (threat_name, file_path)
+    threats: Vec<(String, String)>,    // (threat_name, file_path)
+    suspicious: Vec<(String, String)>, // same, for heuristic-only findings
+    groups: Vec<ThreatGroup>,          // same detections, grouped by content hash
+    detections: Vec<DetectionRecord>,
+    files: Vec<ScanFileRecord>, // every scanned file; empty for realtime events
+}
+
+/// Findings of the realtime watcher. `blocked` says whether on-access mode now
+/// denies opening the files; otherwise they were only observed.
+#[derive(Serialize, Clone)]
+struct RealtimeThreatPayload {
+    #[serde(flatten)]
+    scan: ScanFinishedPayload,
+    blocked: bool,
+}
+
+/// Mass rename/rewrite of user files in one folder, or a tampered decoy.
+/// `paths` are the files being damaged, never candidates for quarantine;
+/// `canary` is the decoy that tripped, which is restored rather than listed.
+#[derive(Serialize, Clone)]
+struct RansomwareActivityPayload {
+    directory: String,
+    reason: String,
+    paths: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    canary: Option<String>,
+    process: Option<process::ProcessInfo>,
+}
+
+/// What a scan learned about one file, threat or not.
+#[derive(Serialize, Clone)]
+struct ScanFileRecord {
+    path: String,
+    sha256: Option<String>,
+    md5: Option<String>,
+    sha1: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ssdeep: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tlsh: Option<String>,
+    size: Option<u64>,
+    file_type: String,
+    extension_mismatch: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    elf: Option<elf::ElfReport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pe: Option<pe::PeReport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    script: Option<scripts::ScriptReport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    entropy: Option<entropy::EntropyProfile>,
+}
+
+/// One finding for one file, from a cloud verdict or a local engine.
+#[derive(Serialize, Clone)]
+struct DetectionRecord {
+    name: String,
+    path: String,
+    source: String, // "cloud" | "local"
+    rule_id: Option<String>,
+    severity: Option<String>,
+    sha256: Option<String>,
+    md5: Option<String>,
+    sha1: Option<String>,
+    container: Option<String>, // file on disk when `path` is an archive member
+    verdict: String,           // "malicious" | "suspicious" | cloud verdict
+    reason: Option<String>,
+    // Who wrote the file; realtime detections only
+    #[serde(skip_serializing_if = "Option::is_none")]
+    process: Option<process::ProcessInfo>,
+}
+
+impl DetectionRecord {
+    /// Known malware or a non-clean cloud verdict. A local heuristic alone only
+    /// makes a file suspicious.
+    fn is_threat(&self) -> bool {
+        self.verdict == "malicious" || self.source == "cloud"
+    }
+
+    /// Marks a finding as active content worth a look rather than known malware.
+    fn suspicious(mut self, reason: &str) -> Self {
+        self.verdict = "suspicious".to_string();
+        self.reason = Some(reason.to_string());
+        self
+    }
+}
+
+#[derive(Serialize, Clone)]
+struct ThreatGroup {
+    sha256: String,
+    name: String,
+    paths: Vec<String>,
+}
+
+impl ScanFinishedPayload {
+    fn from_detections(detections: Vec<DetectionRecord>) -> Self {
+        let mut threats: Vec<(String, String)> = Vec::new();
+        let mut suspicious: Vec<(String, String)> = Vec::new();
+        let mut groups: Vec<ThreatGroup> = Vec::new();
+
+        for d in &detections {
+            // The UI acts on files on disk: archive members are listed under their
+            // container, named after the member (same as EICAR-in-zip)
+            let (name, file) = match &d.container {
+                Some(container) => (
+                    format!("{} in {}", d.name, archive::member_name(&d.path, container)),
+                    container,
+                ),
+                None => (d.name.clone(), &d.path),
+            };
+
+            let listed = if d.is_threat() {
+                &mut threats
+            } else {
+                &mut suspicious
+            };
+            // The UI lists one entry per path; the first (strongest) finding wins
+            if !listed.iter().any(|(_, p)| p == file) {
+                listed.push((name, file.clone()));
+            }
+
+            if let Some(hash) = &d.sha256 {
+                match groups
+                    .iter_mut()
+                    .find(|g| &g.sha256 == hash && g.name == d.name)
+                {
+                    Some(g) if !g.paths.contains(&d.path) => g.paths.push(d.path.clone()),
+                    Some(_) => {}
+                    None => groups.push(ThreatGroup {
+                        sha256: hash.clone(),
+                        name: d.name.clone(),
+                        paths: vec![d.path.clone()],
+                    }),
+                }
+            }
+        }
+
+        suspicious.retain(|(_, p)| !threats.iter().any(|(_, t)| t == p));
+
+        Self {
+            threats,
+            suspicious,
+            groups,
+            detections,
+            files: Vec::new(),
+        }
+    }
 }
 
 #[derive(Serialize, Clone)]
@@ -114,6 +371,11 @@ struct RealtimeFilePayload {
     event: String,
 }
 
+#[derive(Serialize, Clone)]
+struct ApiErrorPayload {
+    message: String,
+}
+
 #[derive(Deserialize)]
 struct RestoreItem {
     #[serde(rename = "fileName", alias = "file_name")]
@@ -137,6 +399,18 @@ struct ThreatApiFile {
     sha256: String,
     size: Option<u64>,
     extension: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    file_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pe: Option<pe::PeMetadata>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    md5: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sha1: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ssdeep: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tlsh: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -172,49 +446,180 @@ struct ThreatApiResponse {
 // ---- Helper paths ----
 
 fn quarantine_root() -> PathBuf {
-    let base_dir = dirs::data_dir()
-        .or_else(dirs::home_dir)
-        .unwrap_or_else(|| PathBuf::from("."));
+    app_data_dir().join("Quarantine")
+}
 
-    base_dir.join("StellarAntivirus").join("Quarantine")
+// ---- Session helpers ----
+
+fn session_token() -> Option<String> {
+    SESSION_TOKEN.lock().ok().and_then(|t| t.clone())
 }
 
-fn is_test_filename(path: &Path) -> bool {
-    if let Some(name) = path.file_name().and_then(|s| s.to_str()) {
-        let lower = name.to_lowercase();
-        return lower == "stellar-test.bin" || lower == "stellar_test.bin";
+fn set_session(token: Option<String>) {
+    if let Ok(mut guard) = SESSION_TOKEN.lock() {
+        *guard = token;
     }
-    false
 }
 
-// ---- Hash helper ----
+/// A 401 on a request that carried our token ends the session: the token is
+/// dropped and `SESSION_EXPIRED_ERR` returned. Every API call goes through this.
+fn check_session(status: reqwest::StatusCode, sent_token: bool) -> Result<(), String> {
+    if status == reqwest::StatusCode::UNAUTHORIZED && sent_token {
+        set_session(None);
+        return Err(SESSION_EXPIRED_ERR.to_string());
+    }
+    Ok(())
+}
 
-fn sha256_of_file(path: &Path) -> Option<String> {
-    let mut file = std::fs::File::open(path).ok()?;
-    let mut hasher = Sha256::new();
-    let mut buf = [0u8; 8192];
+/// Surfaces API failures the UI has to react to (expired session, untrusted verdicts).
+fn notify_api_error_state(app: &AppHandle, err: &str) {
+    if err == SESSION_EXPIRED_ERR {
+        let _ = app.emit("session_expired", ());
+    } else if err.starts_with(INTEGRITY_ERR_PREFIX) {
+        let _ = app.emit(
+            "threat_api_integrity_error",
+            ApiErrorPayload {
+                message: err.to_string(),
+            },
+        );
+    }
+}
 
-    loop {
-        let n = file.read(&mut buf).ok()?;
-        if n == 0 {
-            break;
-        }
-        hasher.update(&buf[..n]);
+// ---- Integrity helpers ----
+
+fn response_verifying_key() -> Result<VerifyingKey, String> {
+    let b64 = RESPONSE_SIGNING_PUBLIC_KEY
+        .ok_or_else(|| "This build has no response signing key bundled".to_string())?;
+    let bytes: [u8; 32] = BASE64
+        .decode(b64.trim())
+        .ok()
+        .and_then(|b| b.try_into().ok())
+        .ok_or_else(|| "Bundled response signing key is malformed".to_string())?;
+    VerifyingKey::from_bytes(&bytes)
+        .map_err(|e| format!("Bundled response signing key is invalid: {e}"))
+}
+
+/// Checks the server's signature over `path`, its timestamp and `body`. Binding
+/// the path and time stops a captured "clean" response being replayed for
+/// another request or long after it was issued.
+fn verify_response_signature(
+    headers: &reqwest::header::HeaderMap,
+    path: &str,
+    body: &[u8],
+) -> Result<(), String> {
+    let key = response_verifying_key().map_err(|e| format!("{INTEGRITY_ERR_PREFIX}: {e}"))?;
+
+    let sig_b64 = headers
+        .get(RESPONSE_SIGNATURE_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .ok_or_else(|| format!("{INTEGRITY_ERR_PREFIX}: response is not signed"))?;
+
+    let sig = BASE64
+        .decode(sig_b64.trim())
+        .ok()
+        .and_then(|b| Signature::from_slice(&b).ok())
+        .ok_or_else(|| format!("{INTEGRITY_ERR_PREFIX}: malformed response signature"))?;
+
+    let timestamp = headers
+        .get(RESPONSE_TIMESTAMP_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .ok_or_else(|| format!("{INTEGRITY_ERR_PREFIX}: response has no signing time"))?;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    if now.abs_diff(timestamp) > RESPONSE_MAX_AGE_SECS {
+        return Err(format!(
+            "{INTEGRITY_ERR_PREFIX}: response signed at {timestamp}, too far from now ({now})"
+        ));
     }
 
-    Some(hex::encode(hasher.finalize()))
+    let mut message = format!("{path}\n{timestamp}\n").into_bytes();
+    message.extend_from_slice(body);
+    key.verify_strict(&message, &sig)
+        .map_err(|_| format!("{INTEGRITY_ERR_PREFIX}: response signature mismatch"))
 }
 
 // ---- HTTP / API helpers ----
 
-fn build_http_client() -> Result<Client, String> {
-    Client::builder()
+fn build_proxy(cfg: &ProxyConfig) -> Result<reqwest::Proxy, String> {
+    let url = cfg.url.trim();
+    let supported = ["http://", "https://", "socks5://", "socks5h://"];
+    if !supported.iter().any(|p| url.to_lowercase().starts_with(p)) {
+        return Err(format!("Unsupported proxy URL: {url}"));
+    }
+
+    let mut proxy =
+        reqwest::Proxy::all(url).map_err(|e| format!("Invalid proxy URL {url}: {e}"))?;
+
+    if let Some(user) = cfg.username.as_deref().filter(|u| !u.is_empty()) {
+        proxy = proxy.basic_auth(user, cfg.password.as_deref().unwrap_or(""));
+    }
+
+    if !cfg.no_proxy.is_empty() {
+        proxy = proxy.no_proxy(reqwest::NoProxy::from_string(&cfg.no_proxy.join(",")));
+    }
+
+    Ok(proxy)
+}
+
+fn validate_network_config(cfg: &NetworkConfig) -> Result<(), String> {
+    if let Some(p) = &cfg.proxy {
+        build_proxy(p)?;
+    }
+    pinning::tls_config(cfg)?;
+    if cfg.require_signed_responses {
+        response_verifying_key()?;
+    }
+    Ok(())
+}
+
+/// The shared API client. Built on first use and replaced by
+/// `set_network_config`, not per request (it owns the connection pool and the
+/// parsed CA bundle).
+fn http_client() -> Result<Client, String> {
+    let Ok(mut cached) = HTTP_CLIENT.lock() else {
+        return build_http_client(&load_runtime_config().network);
+    };
+    if let Some(client) = cached.as_ref() {
+        return Ok(client.clone());
+    }
+    let client = build_http_client(&load_runtime_config().network)?;
+    *cached = Some(client.clone());
+    Ok(client)
+}
+
+fn build_http_client(net: &NetworkConfig) -> Result<Client, String> {
+    let mut builder = Client::builder()
         .connect_timeout(Duration::from_secs(HTTP_CONNECT_TIMEOUT_SECS))
         .timeout(Duration::from_secs(HTTP_TOTAL_TIMEOUT_SECS))
+        .use_preconfigured_tls(pinning::tls_config(net)?);
+
+    if let Some(p) = &net.proxy {
+        builder = builder.proxy(build_proxy(p)?);
+    }
+
+    builder
         .build()
         .map_err(|e| format!("Failed to build HTTP client: {e}"))
 }
 
+fn gzip_bytes(data: &[u8]) -> Result<Vec<u8>, String> {
+    let mut enc = GzEncoder::new(Vec::with_capacity(data.len() / 4), Compression::default());
+    enc.write_all(data)
+        .and_then(|_| enc.finish())
+        .map_err(|e| format!("Failed to gzip request body: {e}"))
+}
+
+fn server_accepts_gzip(headers: &reqwest::header::HeaderMap) -> bool {
+    headers
+        .get_all(reqwest::header::ACCEPT_ENCODING)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .any(|v| v.to_lowercase().contains("gzip"))
+}
+
 fn build_client_payload() -> ThreatApiClient {
     ThreatApiClient {
         product: "Stellar Antivirus Desktop".to_string(),
@@ -230,7 +635,8 @@ fn call_threat_api_batch(files: Vec<ThreatApiFile>) -> Result<Vec<ThreatApiResul
     }
 
     let url = format!("{}{}", API_BASE_URL, API_HASH_CHECK_PATH);
-    let client = build_http_client()?;
+    let net = load_runtime_config().network;
+    let client = http_client()?;
 
     let mut all_results: Vec<ThreatApiResult> = Vec::new();
     const CHUNK_SIZE: usize = 1000;
@@ -242,6 +648,8 @@ fn call_threat_api_batch(files: Vec<ThreatApiFile>) -> Result<Vec<ThreatApiResul
             client: build_client_payload(),
             files: chunk.to_vec(),
         };
+        let body = serde_json::to_vec(&req)
+            .map_err(|e| format!("Failed to serialize API request: {e}"))?;
 
         let mut last_err: Option<String> = None;
 
@@ -256,13 +664,53 @@ fn call_threat_api_batch(files: Vec<ThreatApiFile>) -> Result<Vec<ThreatApiResul
             );
 
             let started = std::time::Instant::now();
-            let resp = client.post(&url).json(&req).send();
+            let token = session_token();
+            let gzip = API_ACCEPTS_GZIP.load(Ordering::SeqCst);
+            let mut builder = client
+                .post(&url)
+                .header(reqwest::header::CONTENT_TYPE, "application/json");
+            if gzip {
+                let compressed = gzip_bytes(&body)?;
+                println!(
+                    "[HTTP] gzip request body {} -> {} bytes",
+                    body.len(),
+                    compressed.len()
+                );
+                builder = builder
+                    .header(reqwest::header::CONTENT_ENCODING, "gzip")
+                    .body(compressed);
+            } else {
+                builder = builder.body(body.clone());
+            }
+            if let Some(t) = &token {
+                builder = builder.bearer_auth(t);
+            }
+            let resp = builder.send();
 
             match resp {
                 Ok(r) => {
                     let status = r.status();
                     let elapsed = started.elapsed();
 
+                    if let Err(e) = check_session(status, token.is_some()) {
+                        println!(
+                            "[HTTP] status={} in {:?} (chunk {}) – session token rejected",
+                            status,
+                            elapsed,
+                            chunk_index + 1
+                        );
+                        return Err(e);
+                    }
+
+                    if status == reqwest::StatusCode::UNSUPPORTED_MEDIA_TYPE && gzip {
+                        // Server stopped taking gzip; fall back to plain JSON
+                        API_ACCEPTS_GZIP.store(false, Ordering::SeqCst);
+                        if attempt < HTTP_RETRIES {
+                            last_err = Some(format!("API returned HTTP {}", status));
+                            continue;
+                        }
+                    }
+
                     if !status.is_success() {
                         let msg = format!("API returned HTTP {}", status);
                         last_err = Some(msg.clone());
@@ -290,6 +738,10 @@ fn call_threat_api_batch(files: Vec<ThreatApiFile>) -> Result<Vec<ThreatApiResul
 
                     // Clone headers before consuming body
                     let headers = r.headers().clone();
+                    if !gzip && server_accepts_gzip(&headers) {
+                        println!("[HTTP] server accepts gzip request bodies");
+                        API_ACCEPTS_GZIP.store(true, Ordering::SeqCst);
+                    }
                     let content_type = headers
                         .get(reqwest::header::CONTENT_TYPE)
                         .and_then(|v| v.to_str().ok())
@@ -306,6 +758,10 @@ fn call_threat_api_batch(files: Vec<ThreatApiFile>) -> Result<Vec<ThreatApiResul
                         bytes.len()
                     );
 
+                    if net.require_signed_responses {
+                        verify_response_signature(&headers, API_HASH_CHECK_PATH, &bytes)?;
+                    }
+
                     let parsed: ThreatApiResponse = serde_json::from_slice(&bytes).map_err(|e| {
                         let preview_len = bytes.len().min(200);
                         let preview = String::from_utf8_lossy(&bytes[..preview_len]);
@@ -317,6 +773,10 @@ fn call_threat_api_batch(files: Vec<ThreatApiFile>) -> Result<Vec<ThreatApiResul
                     break;
                 }
                 Err(e) => {
+                    // A host failing the pin won't pass on a retry either
+                    if let Some(msg) = pinning::pin_failure(&e) {
+                        return Err(msg);
+                    }
                     let msg = format!("API request error: {e}");
                     last_err = Some(msg.clone());
 
@@ -336,19 +796,453 @@ fn call_threat_api_batch(files: Vec<ThreatApiFile>) -> Result<Vec<ThreatApiResul
     Ok(all_results)
 }
 
-fn call_threat_api_single(hash: &str) -> Result<Option<ThreatApiResult>, String> {
+fn call_threat_api_single(
+    hashes: &hashing::FileHashes,
+    facts: &FileFacts,
+) -> Result<Option<ThreatApiResult>, String> {
     let file = ThreatApiFile {
-        sha256: hash.to_string(),
+        sha256: hashes.sha256.clone(),
         size: None,
         extension: None,
+        file_type: Some(facts.file_type.as_str().to_string()),
+        pe: facts.pe.as_ref().map(pe::PeReport::metadata),
+        md5: Some(hashes.md5.clone()),
+        sha1: Some(hashes.sha1.clone()),
+        ssdeep: hashes.ssdeep.clone(),
+        tlsh: hashes.tlsh.clone(),
     };
 
     let results = call_threat_api_batch(vec![file])?;
     Ok(results.into_iter().next())
 }
 
+// ---- Local detection ----
+
+// Structural analysis reads the whole file; bigger executables are skipped
+// rather than analysed from a truncated (misleading) prefix.
+const MAX_STATIC_ANALYSIS_BYTES: u64 = 64 * 1024 * 1024; // 64 MB
+
+/// One structural red flag from a static analyzer; weights add up to a score.
+#[derive(Serialize, Clone)]
+struct Indicator {
+    id: &'static str,
+    weight: u32,
+    detail: String,
+}
+
+/// Sum of indicator weights, capped at 100.
+fn indicator_score(indicators: &[Indicator]) -> u32 {
+    indicators.iter().map(|i| i.weight).sum::<u32>().min(100)
+}
+
+/// Short reason for a detection name, e.g. "packed, wx_segment".
+fn indicator_summary(indicators: &[Indicator]) -> String {
+    indicators
+        .iter()
+        .map(|i| i.id)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Score and indicator details, as the reason on a heuristic finding.
+fn indicator_reason(score: u32, indicators: &[Indicator]) -> String {
+    let details = indicators
+        .iter()
+        .map(|i| i.detail.as_str())
+        .collect::<Vec<_>>()
+        .join("; ");
+    format!("Heuristic score {score}/100: {details}")
+}
+
+/// Content facts gathered once per file, shared by the local engines and the
+/// scan record.
+struct FileFacts {
+    file_type: filetype::FileType,
+    elf: Option<elf::ElfReport>,
+    pe: Option<pe::PeReport>,
+    document: Vec<documents::Finding>,
+    script: Option<scripts::ScriptReport>,
+}
+
+impl FileFacts {
+    fn analysable(path: &Path, file_type: filetype::FileType) -> bool {
+        matches!(
+            file_type,
+            filetype::FileType::Elf
+                | filetype::FileType::Pe
+                | filetype::FileType::OfficeOle
+                | filetype::FileType::OfficeOoxml
+                | filetype::FileType::Pdf
+                | filetype::FileType::Script
+        ) || (file_type == filetype::FileType::Text
+            && scripts::language_from_extension(path).is_some())
+    }
+
+    /// Hashes `path` and gathers its facts from that same read. The content
+    /// is handed back for the signature scan: the whole file if it was
+    /// analysed, otherwise its head.
+    fn gather(path: &Path) -> (Option<hashing::HashedContent>, Self) {
+        let mut file_type = filetype::FileType::Unknown;
+        let content = hashing::hash_file_keeping(path, |head, size| {
+            file_type = filetype::sniff(head);
+            if size <= MAX_STATIC_ANALYSIS_BYTES && Self::analysable(path, file_type) {
+                size
+            } else {
+                signatures::MAX_CONTENT_SCAN_BYTES
+            }
+        });
+
+        let facts = match &content {
+            Some(c) if c.complete() && Self::analysable(path, file_type) => {
+                Self::analyze(path, file_type, &c.data)
+            }
+            _ => Self {
+                file_type,
+                elf: None,
+                pe: None,
+                document: vec![],
+                script: None,
+            },
+        };
+        (content, facts)
+    }
+
+    fn from_bytes(path: &Path, data: &[u8]) -> Self {
+        Self::analyze(path, filetype::sniff(data), data)
+    }
+
+    fn analyze(path: &Path, file_type: filetype::FileType, data: &[u8]) -> Self {
+        Self {
+            file_type,
+            elf: (file_type == filetype::FileType::Elf)
+                .then(|| elf::analyze(data))
+                .flatten(),
+            pe: (file_type == filetype::FileType::Pe)
+                .then(|| pe::analyze(data))
+                .flatten(),
+            document: documents::analyze(file_type, data),
+            script: matches!(
+                file_type,
+                filetype::FileType::Script | filetype::FileType::Text
+            )
+            .then(|| scripts::analyze(path, data))
+            .flatten(),
+        }
+    }
+
+    fn record(
+        &self,
+        path: String,
+        hashes: Option<&hashing::FileHashes>,
+        size: Option<u64>,
+    ) -> ScanFileRecord {
+        ScanFileRecord {
+            extension_mismatch: filetype::extension_mismatch(Path::new(&path), self.file_type),
+            path,
+            sha256: hashes.map(|h| h.sha256.clone()),
+            md5: hashes.map(|h| h.md5.clone()),
+            sha1: hashes.map(|h| h.sha1.clone()),
+            ssdeep: hashes.and_then(|h| h.ssdeep.clone()),
+            tlsh: hashes.and_then(|h| h.tlsh.clone()),
+            size,
+            file_type: self.file_type.as_str().to_string(),
+            elf: self.elf.clone(),
+            pe: self.pe.clone(),
+            script: self.script.clone(),
+            entropy: hashes.map(|h| h.entropy.clone()),
+        }
+    }
+}
+
+/// Disguised and heuristically odd executables, active content, obfuscated
+/// scripts, entropy anomalies and near-copies of known samples: reported as
+/// suspicious with a reason.
+fn suspicious_findings(
+    path: &Path,
+    hashes: Option<&hashing::FileHashes>,
+    facts: &FileFacts,
+) -> Vec<(rules::RuleMatch, String)> {
+    let mut out: Vec<(rules::RuleMatch, String)> = facts
+        .document
+        .iter()
+        .map(|f| {
+            let m = rules::RuleMatch {
+                rule_id: f.rule_id.to_string(),
+                name: f.name.to_string(),
+                severity: f.severity.to_string(),
+            };
+            (m, f.reason.clone())
+        })
+        .collect();
+
+    if let Some(report) = facts
+        .script
+        .as_ref()
+        .filter(|r| r.score >= scripts::DETECTION_SCORE)
+    {
+        let m = rules::RuleMatch {
+            rule_id: "STL-SCR-0001".to_string(),
+            name: format!(
+                "Stellar.Suspicious.Script.{} ({})",
+                report.language.label(),
+                indicator_summary(&report.indicators)
+            ),
+            severity: if report.score >= 80 { "high" } else { "medium" }.to_string(),
+        };
+        out.push((m, report.reason()));
+    }
+
+    if filetype::is_disguised_executable(path, facts.file_type) {
+        let m = rules::RuleMatch {
+            rule_id: "STL-FT-0001".to_string(),
+            name: format!(
+                "Stellar.Heur.DisguisedExecutable ({})",
+                facts.file_type.as_str()
+            ),
+            severity: "high".to_string(),
+        };
+        let reason = format!(
+            "Executable content ({}) behind a non-executable extension",
+            facts.file_type.as_str()
+        );
+        out.push((m, reason));
+    }
+
+    // Packers and stripped symbols or imports are common in legitimate
+    // binaries too, so a high score is a reason to look, not a verdict
+    if let Some(report) = facts
+        .elf
+        .as_ref()
+        .filter(|r| r.score >= elf::DETECTION_SCORE)
+    {
+        let m = rules::RuleMatch {
+            rule_id: "STL-ELF-0001".to_string(),
+            name: format!(
+                "{} ({})",
+                elf::DETECTION_NAME,
+                indicator_summary(&report.indicators)
+            ),
+            severity: "medium".to_string(),
+        };
+        out.push((m, indicator_reason(report.score, &report.indicators)));
+    }
+
+    if let Some(report) = facts.pe.as_ref().filter(|r| r.score >= pe::DETECTION_SCORE) {
+        let m = rules::RuleMatch {
+            rule_id: "STL-PE-0001".to_string(),
+            name: format!(
+                "{} ({})",
+                pe::DETECTION_NAME,
+                indicator_summary(&report.indicators)
+            ),
+            severity: "medium".to_string(),
+        };
+        out.push((m, indicator_reason(report.score, &report.indicators)));
+    }
+
+    if let Some(profile) = hashes.map(|h| &h.entropy) {
+        let executable = matches!(
+            facts.file_type,
+            filetype::FileType::Pe | filetype::FileType::Elf | filetype::FileType::MachO
+        );
+        // Signed installers carry compressed payloads legitimately
+        let signed = facts.pe.as_ref().is_some_and(|pe| pe.signed);
+        if executable && !signed && profile.looks_packed() {
+            let m = rules::RuleMatch {
+                rule_id: "STL-ENT-0001".to_string(),
+                name: "Stellar.Suspicious.PackedExecutable".to_string(),
+                severity: "medium".to_string(),
+            };
+            let reason = format!(
+                "Entropy {:.2} with {}/{} high-entropy blocks; likely packed or encrypted",
+                profile.entropy, profile.high_blocks, profile.blocks
+            );
+            out.push((m, reason));
+        }
+
+        // A document or picture by name whose bytes are noise
+        if facts.file_type == filetype::FileType::Unknown
+            && filetype::claims_known_format(path)
+            && profile.looks_encrypted()
+        {
+            let m = rules::RuleMatch {
+                rule_id: "STL-ENT-0002".to_string(),
+                name: "Stellar.Suspicious.EncryptedContent".to_string(),
+                severity: "high".to_string(),
+            };
+            let reason = format!(
+                "Content is near-random (entropy {:.2}, first block {:.2}) but named as a document or media file; typical of ransomware output",
+                profile.entropy, profile.head
+            );
+            out.push((m, reason));
+        }
+    }
+
+    if let Some(hit) =
+        hashes.and_then(|h| fuzzy::current().best_match(h.ssdeep.as_deref(), h.tlsh.as_deref()))
+    {
+        let reason = hit.reason();
+        let m = rules::RuleMatch {
+            rule_id: "STL-FUZZ-0001".to_string(),
+            name: format!("{} (variant)", hit.name),
+            severity: hit.severity,
+        };
+        out.push((m, reason));
+    }
+
+    out
+}
+
+/// Findings that need no API round-trip (EICAR, structural heuristics, rule
+/// engine, content signatures, active content, scripts, fuzzy hash matches).
+fn local_detections(
+    path: &Path,
+    content: Option<&hashing::HashedContent>,
+    facts: &FileFacts,
+) -> Vec<DetectionRecord> {
+    let hashes = content.map(|c| &c.hashes);
+    let size = content.map(|c| c.size);
+    let file = path.to_string_lossy().to_string();
+    let record = |name: String, rule_id: String, severity: String| DetectionRecord {
+        name,
+        path: file.clone(),
+        source: "local".to_string(),
+        rule_id: Some(rule_id),
+        severity: Some(severity),
+        sha256: hashes.map(|h| h.sha256.clone()),
+        md5: hashes.map(|h| h.md5.clone()),
+        sha1: hashes.map(|h| h.sha1.clone()),
+        container: None,
+        verdict: "malicious".to_string(),
+        reason: None,
+        process: None,
+    };
+
+    let mut out: Vec<DetectionRecord> = Vec::new();
+
+    if let Some(location) = eicar::detect(path) {
+        let name = if location.is_empty() {
+            eicar::EICAR_DETECTION_NAME.to_string()
+        } else {
+            format!("{} in {location}", eicar::EICAR_DETECTION_NAME)
+        };
+        out.push(record(name, "eicar".to_string(), "low".to_string()));
+    }
+
+    out.extend(
+        rules::current()
+            .evaluate(path, size, hashes.map(|h| h.entropy.entropy))
+            .into_iter()
+            .map(|m| record(m.name, m.rule_id, m.severity)),
+    );
+
+    if let Some(c) = content {
+        out.extend(
+            signatures::current()
+                .scan_bytes(&c.data, c.size)
+                .into_iter()
+                .map(|m| record(m.rule.clone(), format!("sig:{}", m.rule), m.severity)),
+        );
+    }
+
+    // Suspicious, not malicious: listed after the hard findings
+    out.extend(
+        suspicious_findings(path, hashes, facts)
+            .into_iter()
+            .map(|(m, reason)| record(m.name, m.rule_id, m.severity).suspicious(&reason)),
+    );
+
+    out
+}
+
+/// Local findings for an archive member held in memory. EICAR is left to
+/// `local_detections` on the container, which already looks inside archives.
+fn member_detections(
+    member: &archive::Member,
+    container: &str,
+    hashes: &hashing::FileHashes,
+    facts: &FileFacts,
+) -> Vec<DetectionRecord> {
+    let path = Path::new(&member.path);
+    let size = member.data.len() as u64;
+    let record = |name: String, rule_id: String, severity: String| DetectionRecord {
+        name,
+        path: member.path.clone(),
+        source: "local".to_string(),
+        rule_id: Some(rule_id),
+        severity: Some(severity),
+        sha256: Some(hashes.sha256.clone()),
+        md5: Some(hashes.md5.clone()),
+        sha1: Some(hashes.sha1.clone()),
+        container: Some(container.to_string()),
+        verdict: "malicious".to_string(),
+        reason: None,
+        process: None,
+    };
+
+    let mut out: Vec<DetectionRecord> = Vec::new();
+
+    out.extend(
+        rules::current()
+            .evaluate(path, Some(size), Some(hashes.entropy.entropy))
+            .into_iter()
+            .map(|m| record(m.name, m.rule_id, m.severity)),
+    );
+
+    out.extend(
+        signatures::current()
+            .scan_bytes(member.data, size)
+            .into_iter()
+            .map(|m| record(m.rule.clone(), format!("sig:{}", m.rule), m.severity)),
+    );
+
+    // Suspicious, not malicious: listed after the hard findings
+    out.extend(
+        suspicious_findings(path, Some(hashes), facts)
+            .into_iter()
+            .map(|(m, reason)| record(m.name, m.rule_id, m.severity).suspicious(&reason)),
+    );
+
+    out
+}
+
+fn cloud_detection(
+    result: &ThreatApiResult,
+    path: &str,
+    hashes: Option<&hashing::FileHashes>,
+) -> DetectionRecord {
+    let sig = result.signature.as_ref();
+
+    DetectionRecord {
+        name: sig
+            .map(|s| s.name.clone())
+            .unwrap_or_else(|| "Unknown threat".to_string()),
+        path: path.to_string(),
+        source: "cloud".to_string(),
+        rule_id: sig.map(|s| s.id.clone()),
+        severity: sig.map(|s| s.severity.clone()),
+        sha256: Some(result.sha256.to_lowercase()),
+        md5: hashes.map(|h| h.md5.clone()),
+        sha1: hashes.map(|h| h.sha1.clone()),
+        container: None,
+        verdict: result.verdict.to_lowercase(),
+        reason: None,
+        process: None,
+    }
+}
+
 // ---- Shared scan routine ----
 
+/// A file on disk or an archive member that hashed successfully.
+struct HashedFile {
+    path: String,
+    hashes: hashing::FileHashes,
+    size: Option<u64>,
+    file_type: filetype::FileType,
+    pe: Option<pe::PeMetadata>,
+    container: Option<String>,
+}
+
 fn run_hash_lookup_scan(
     app: AppHandle,
     paths_to_scan: Vec<PathBuf>,
@@ -356,7 +1250,10 @@ fn run_hash_lookup_scan(
 ) -> Result<(), String> {
     let total = paths_to_scan.len();
     if total == 0 {
-        let _ = app.emit("scan_finished", ScanFinishedPayload { threats: vec![] });
+        let _ = app.emit(
+            "scan_finished",
+            ScanFinishedPayload::from_detections(vec![]),
+        );
         return Ok(());
     }
 
@@ -364,7 +1261,10 @@ fn run_hash_lookup_scan(
 
     let started_hash = std::time::Instant::now();
 
-    let mut index_to_path: Vec<(usize, PathBuf, String)> = Vec::with_capacity(total);
+    let mut hashed: Vec<HashedFile> = Vec::with_capacity(total);
+    let mut file_records: Vec<ScanFileRecord> = Vec::with_capacity(total);
+    let mut local_found: Vec<DetectionRecord> = Vec::new();
+    let allowlisted = allowlist::allowlisted_hashes();
 
     for (i, path) in paths_to_scan.iter().enumerate() {
         let file_str = path.to_string_lossy().to_string();
@@ -378,8 +1278,71 @@ fn run_hash_lookup_scan(
             },
         );
 
-        if let Some(hash) = sha256_of_file(path) {
-            index_to_path.push((i, path.clone(), hash));
+        let (content, facts) = FileFacts::gather(path);
+        let file_type = facts.file_type;
+        let size = content.as_ref().map(|c| c.size);
+        let skip = content
+            .as_ref()
+            .is_some_and(|c| allowlisted.contains(&c.hashes.sha256));
+        if !skip {
+            local_found.extend(local_detections(path, content.as_ref(), &facts));
+        }
+
+        file_records.push(facts.record(
+            file_str.clone(),
+            content.as_ref().map(|c| &c.hashes),
+            size,
+        ));
+
+        if let Some(content) = content {
+            hashed.push(HashedFile {
+                path: file_str.clone(),
+                hashes: content.hashes,
+                size,
+                file_type,
+                pe: facts.pe.as_ref().map(pe::PeReport::metadata),
+                container: None,
+            });
+        }
+
+        // Allowlisting an archive trusts everything in it
+        if !skip && archive::is_container(file_type) {
+            let summary = archive::walk(path, file_type, &archive::SCAN_LIMITS, |member| {
+                let member_hashes = hashing::hash_bytes(member.data);
+                let member_facts = FileFacts::from_bytes(Path::new(&member.path), member.data);
+                let member_size = member.data.len() as u64;
+
+                if !allowlisted.contains(&member_hashes.sha256) {
+                    local_found.extend(member_detections(
+                        &member,
+                        &file_str,
+                        &member_hashes,
+                        &member_facts,
+                    ));
+                }
+
+                file_records.push(member_facts.record(
+                    member.path.clone(),
+                    Some(&member_hashes),
+                    Some(member_size),
+                ));
+
+                hashed.push(HashedFile {
+                    path: member.path,
+                    hashes: member_hashes,
+                    size: Some(member_size),
+                    file_type: member_facts.file_type,
+                    pe: member_facts.pe.as_ref().map(pe::PeReport::metadata),
+                    container: Some(file_str.clone()),
+                });
+            });
+
+            if summary.truncated {
+                println!(
+                    "[SCAN] archive limits reached for {:?}: members={} expanded_bytes={}",
+                    path, summary.members, summary.expanded_bytes
+                );
+            }
         }
 
         if i % 100 == 0 {
@@ -388,34 +1351,59 @@ fn run_hash_lookup_scan(
     }
 
     println!(
-        "[SCAN] {} hashing done in {:?}. hashed_ok={}/{}",
+        "[SCAN] {} hashing done in {:?}. hashed_ok={}/{} local_detections={}",
         notification_label,
         started_hash.elapsed(),
-        index_to_path.len(),
-        total
+        hashed.len(),
+        total,
+        local_found.len()
     );
 
+    // Same content in several places only needs one lookup; user overrides need none
+    let mut seen_hashes: std::collections::HashSet<String> = std::collections::HashSet::new();
     let mut files_for_api: Vec<ThreatApiFile> = Vec::new();
-    for (_idx, path, hash) in &index_to_path {
-        let ext = path
+    for f in &hashed {
+        let hash_lower = f.hashes.sha256.to_lowercase();
+        if allowlisted.contains(&hash_lower) || !seen_hashes.insert(hash_lower) {
+            continue;
+        }
+
+        let ext = Path::new(&f.path)
             .extension()
             .and_then(|s| s.to_str())
             .map(|s| s.to_lowercase());
-        let size = fs::metadata(path).ok().map(|m| m.len());
 
         files_for_api.push(ThreatApiFile {
-            sha256: hash.clone(),
-            size,
+            sha256: f.hashes.sha256.clone(),
+            size: f.size,
             extension: ext,
+            file_type: Some(f.file_type.as_str().to_string()),
+            pe: f.pe.clone(),
+            md5: Some(f.hashes.md5.clone()),
+            sha1: Some(f.hashes.sha1.clone()),
+            ssdeep: f.hashes.ssdeep.clone(),
+            tlsh: f.hashes.tlsh.clone(),
         });
     }
 
+    println!(
+        "[SCAN] {} unique hashes={} (of {} hashed files)",
+        notification_label,
+        files_for_api.len(),
+        hashed.len()
+    );
+
     let api_results = match call_threat_api_batch(files_for_api) {
         Ok(r) => r,
         Err(e) => {
             eprintln!("[SCAN] {} API error: {}", notification_label, e);
+            notify_api_error_state(&app, &e);
 
-            let _ = app.emit("scan_finished", ScanFinishedPayload { threats: vec![] });
+            // Local findings don't depend on the API; still report them
+            onaccess::remember(&local_found);
+            let mut payload = ScanFinishedPayload::from_detections(local_found);
+            payload.files = file_records;
+            let _ = app.emit("scan_finished", payload);
 
             let _ = app
                 .notification()
@@ -428,46 +1416,63 @@ fn run_hash_lookup_scan(
         }
     };
 
-    use std::collections::HashMap;
-    let mut hash_to_path: HashMap<String, String> = HashMap::new();
-    for (_idx, path, hash) in &index_to_path {
-        hash_to_path.insert(hash.to_lowercase(), path.to_string_lossy().to_string());
+    // One hash can live at several paths; every copy is its own detection
+    let mut hash_to_paths: HashMap<String, Vec<&HashedFile>> = HashMap::new();
+    for f in &hashed {
+        hash_to_paths
+            .entry(f.hashes.sha256.to_lowercase())
+            .or_default()
+            .push(f);
     }
 
-    let mut threats_vec: Vec<(String, String)> = Vec::new();
+    let mut detections: Vec<DetectionRecord> = Vec::new();
+    let mut unknown_hashes: Vec<String> = Vec::new();
 
     for r in api_results {
         let verdict = r.verdict.to_lowercase();
-        if verdict == "clean" || verdict == "unknown" {
+        if verdict == "unknown" {
+            unknown_hashes.push(r.sha256.to_lowercase());
+            continue;
+        }
+        if verdict == "clean" {
             continue;
         }
 
-        if let Some(path_str) = hash_to_path.get(&r.sha256.to_lowercase()) {
-            let name = r
-                .signature
-                .as_ref()
-                .map(|s| s.name.clone())
-                .unwrap_or_else(|| "Unknown threat".to_string());
-
-            threats_vec.push((name, path_str.clone()));
+        if let Some(files) = hash_to_paths.get(&r.sha256.to_lowercase()) {
+            for f in files {
+                let mut d = cloud_detection(&r, &f.path, Some(&f.hashes));
+                d.container = f.container.clone();
+                detections.push(d);
+            }
         }
     }
 
-    let _ = app.emit(
-        "scan_finished",
-        ScanFinishedPayload {
-            threats: threats_vec.clone(),
-        },
-    );
+    // Cloud verdicts first so they name the threat when a rule also fired
+    detections.extend(local_found);
+    onaccess::remember(&detections);
+
+    let mut payload = ScanFinishedPayload::from_detections(detections);
+    payload.files = file_records;
+    let threat_count = payload.threats.len();
+    let suspicious_count = payload.suspicious.len();
+    let _ = app.emit("scan_finished", payload);
 
-    if !threats_vec.is_empty() {
+    if threat_count > 0 {
         let _ = app
             .notification()
             .builder()
             .title("Stellar Antivirus")
             .body(format!(
-                "{notification_label} completed – {} threat(s) found.",
-                threats_vec.len()
+                "{notification_label} completed – {threat_count} threat(s) found."
+            ))
+            .show();
+    } else if suspicious_count > 0 {
+        let _ = app
+            .notification()
+            .builder()
+            .title("Stellar Antivirus")
+            .body(format!(
+                "{notification_label} completed – {suspicious_count} suspicious file(s) to review."
             ))
             .show();
     } else {
@@ -479,6 +1484,17 @@ fn run_hash_lookup_scan(
             .show();
     }
 
+    // Opt-in: hand never-seen executables to the backend for analysis. Only
+    // files on disk can be uploaded, not archive members.
+    for hash in &unknown_hashes {
+        let on_disk = hash_to_paths
+            .get(hash)
+            .and_then(|files| files.iter().find(|f| f.container.is_none()));
+        if let Some(f) = on_disk {
+            submission::maybe_submit_unknown(&app, Path::new(&f.path), hash);
+        }
+    }
+
     Ok(())
 }
 
@@ -493,6 +1509,11 @@ fn try_add_path(
         return false;
     }
 
+    // Decoys are ours; scanning them is noise and quarantining them disarms them
+    if canary::is_canary(&p) {
+        return true;
+    }
+
     if let Some(limit) = max_file_bytes {
         match fs::metadata(&p) {
             Ok(m) => {
@@ -680,6 +1701,16 @@ fn probe_fs_access() -> Vec<FsAccessProbe> {
 }
 
 
+/// Orders paths so detected executables come first, then truncates to `max`.
+fn prioritise_executables(paths: Vec<PathBuf>, max: usize) -> Vec<PathBuf> {
+    let (mut executables, rest): (Vec<PathBuf>, Vec<PathBuf>) = paths
+        .into_iter()
+        .partition(|p| filetype::detect(p).is_executable());
+
+    executables.extend(rest);
+    executables.truncate(max);
+    executables
+}
 
 #[tauri::command]
 async fn fake_full_scan(app: AppHandle) -> Result<(), String> {
@@ -705,7 +1736,10 @@ async fn quick_scan(app: AppHandle, max_bytes: Option<u64>) -> Result<(), String
 
     let app2 = app.clone();
     tauri::async_runtime::spawn_blocking(move || {
-        let paths_to_scan = collect_paths(MAX_DEPTH, MAX_FILES, false, true, Some(limit));
+        // Over-collect, then keep the executables first so the budget goes where
+        // the risk is
+        let candidates = collect_paths(MAX_DEPTH, MAX_FILES * 4, false, true, Some(limit));
+        let paths_to_scan = prioritise_executables(candidates, MAX_FILES);
         run_hash_lookup_scan(app2, paths_to_scan, "Quick scan")
     })
     .await
@@ -718,7 +1752,7 @@ fn get_realtime_enabled() -> bool {
 }
 
 #[tauri::command]
-fn set_realtime_enabled(enabled: bool) {
+async fn set_realtime_enabled(enabled: bool) -> Result<(), String> {
     REALTIME_ENABLED.store(enabled, Ordering::SeqCst);
 
     let mut cfg = load_runtime_config();
@@ -726,6 +1760,88 @@ fn set_realtime_enabled(enabled: bool) {
     save_runtime_config(&cfg);
 
     println!("Realtime protection set to: {enabled}");
+
+    // Marking the watched trees walks them; keep that off the UI thread. Quick
+    // toggles can finish out of order, so each run applies the latest state.
+    tauri::async_runtime::spawn_blocking(|| {
+        static APPLY: Mutex<()> = Mutex::new(());
+        let _applying = APPLY.lock().unwrap_or_else(|e| e.into_inner());
+
+        let enabled = REALTIME_ENABLED.load(Ordering::SeqCst);
+        process::set_tracking(enabled);
+
+        // Canaries may have gone missing while nobody was watching
+        if enabled {
+            canary::plant(&canary_dirs());
+        }
+    })
+    .await
+    .map_err(|e| format!("Realtime toggle task failed: {e}"))
+}
+
+#[tauri::command]
+fn get_on_access_status() -> onaccess::OnAccessStatus {
+    onaccess::status()
+}
+
+#[tauri::command]
+fn set_on_access_blocking(enabled: bool) -> onaccess::OnAccessStatus {
+    onaccess::set_enabled(enabled, realtime_watch_dirs());
+
+    let mut cfg = load_runtime_config();
+    cfg.on_access_blocking = enabled;
+    save_runtime_config(&cfg);
+
+    println!("On-access blocking set to: {enabled}");
+    onaccess::status()
+}
+
+#[tauri::command]
+fn set_session_token(token: Option<String>) {
+    let token = token.filter(|t| !t.trim().is_empty());
+    println!("Session token {}", if token.is_some() { "set" } else { "cleared" });
+    set_session(token);
+}
+
+/// The proxy password is redacted; `password_set` says whether one is stored.
+#[tauri::command]
+fn get_network_config() -> NetworkConfig {
+    let mut net = load_runtime_config().network;
+    if let Some(p) = net.proxy.as_mut() {
+        p.password_set = p.password.take().is_some_and(|pw| !pw.is_empty());
+    }
+    net
+}
+
+/// A missing proxy password keeps the stored one (the UI never sees it);
+/// an empty one clears it.
+#[tauri::command]
+fn set_network_config(mut config: NetworkConfig) -> Result<(), String> {
+    let mut cfg = load_runtime_config();
+    let stored = cfg.network.proxy.as_ref().and_then(|p| p.password.clone());
+    if let Some(p) = config.proxy.as_mut() {
+        p.password = match p.password.take() {
+            None => stored,
+            Some(pw) if pw.is_empty() => None,
+            new => new,
+        };
+    }
+
+    validate_network_config(&config)?;
+    let client = build_http_client(&config)?;
+
+    cfg.network = config;
+    save_runtime_config(&cfg);
+    if let Ok(mut cached) = HTTP_CLIENT.lock() {
+        *cached = Some(client);
+    }
+
+    println!(
+        "Network config updated: proxy={} extra_ca_files={}",
+        cfg.network.proxy.is_some(),
+        cfg.network.extra_ca_files.len()
+    );
+    Ok(())
 }
 
 fn validate_quarantine_name(name: &str) -> Result<(), String> {
@@ -755,6 +1871,11 @@ async fn quarantine_files(paths: Vec<String>) -> Result<(), String> {
             continue;
         }
 
+        if canary::is_canary(&src) {
+            eprintln!("Canary file, skipping: {original}");
+            continue;
+        }
+
         let fname = src
             .file_name()
             .unwrap_or_else(|| std::ffi::OsStr::new("unknown"));
@@ -869,27 +1990,264 @@ async fn delete_files(paths: Vec<String>) -> Result<(), String> {
 
 // ---- Realtime watcher ----
 
-fn start_realtime_watcher(app_handle: AppHandle) {
-    thread::spawn(move || {
-        let mut watch_paths: Vec<PathBuf> = Vec::new();
+/// One suspicious record per affected file, so the UI lists them all.
+fn raise_ransomware_alert(app: &AppHandle, alert: behaviour::RansomwareAlert) {
+    let directory = alert.directory.to_string_lossy().to_string();
+    eprintln!(
+        "[Realtime] ransomware activity in {}: {} ({} file(s))",
+        directory,
+        alert.reason,
+        alert.paths.len()
+    );
+
+    let process = process::attribute_any(&alert.paths);
+    let by = process
+        .as_ref()
+        .map(|p| format!(" Likely cause: {}.", p.describe()))
+        .unwrap_or_default();
 
-        if let Some(downloads) = dirs::download_dir() {
-            watch_paths.push(downloads);
+    // The affected files are the victim's, not threats: nothing to quarantine
+    let _ = app.emit(
+        "ransomware_activity",
+        RansomwareActivityPayload {
+            directory: directory.clone(),
+            reason: alert.reason.clone(),
+            paths: alert
+                .paths
+                .iter()
+                .map(|p| p.to_string_lossy().to_string())
+                .collect(),
+            canary: None,
+            process,
+        },
+    );
+
+    let _ = app
+        .notification()
+        .builder()
+        .title("Stellar Antivirus – possible ransomware")
+        .body(format!(
+            "{} in {directory}. {} file(s) affected.{by}",
+            alert.reason,
+            alert.paths.len()
+        ))
+        .show();
+}
+
+fn realtime_watch_dirs() -> Vec<PathBuf> {
+    [
+        dirs::download_dir(),
+        dirs::document_dir(),
+        dirs::desktop_dir(),
+    ]
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+fn canary_dirs() -> Vec<PathBuf> {
+    [dirs::document_dir(), dirs::desktop_dir()]
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+/// Checks the canaries an event touched; alerts and re-plants on tampering.
+fn handle_canary_event(app: &AppHandle, event: &Event, alerted: &mut HashMap<PathBuf, Instant>) {
+    let renamed = match (&event.kind, event.paths.as_slice()) {
+        (EventKind::Modify(ModifyKind::Name(RenameMode::Both)), [from, to])
+            if canary::is_canary(from) =>
+        {
+            Some((from, to))
         }
-        if let Some(documents) = dirs::document_dir() {
-            watch_paths.push(documents);
+        _ => None,
+    };
+
+    let now = Instant::now();
+    for path in event.paths.iter().filter(|p| canary::is_canary(p)) {
+        let renamed_to = renamed
+            .filter(|(from, _)| *from == path)
+            .map(|(_, to)| to.as_path());
+        let Some(tamper) = canary::check(path, renamed_to) else {
+            continue;
+        };
+
+        // One alert per canary per cooldown; a wiper loop would flood otherwise
+        let quiet = alerted
+            .get(path)
+            .is_some_and(|t| now.duration_since(*t) < CANARY_ALERT_COOLDOWN);
+        if !quiet {
+            alerted.insert(path.clone(), now);
+            raise_canary_alert(app, path, &tamper);
         }
-        if let Some(desktop) = dirs::desktop_dir() {
-            watch_paths.push(desktop);
+
+        if let Err(e) = canary::restore(path) {
+            eprintln!("[CANARY] failed to restore {:?}: {e}", path);
+        }
+    }
+}
+
+fn raise_canary_alert(app: &AppHandle, path: &Path, tamper: &canary::Tamper) {
+    let file = path.to_string_lossy().to_string();
+    let what = tamper.describe();
+    eprintln!("[Realtime] canary {} was {}", file, what);
+
+    let process = process::attribute(path).or_else(|| match tamper {
+        canary::Tamper::Renamed(to) => process::attribute(to),
+        _ => None,
+    });
+    let by = process
+        .as_ref()
+        .map(|p| format!(" Likely cause: {}.", p.describe()))
+        .unwrap_or_default();
+
+    // The decoy is ours and already being restored: it is the evidence, not
+    // a file finding
+    let directory = path
+        .parent()
+        .map(|d| d.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let _ = app.emit(
+        "ransomware_activity",
+        RansomwareActivityPayload {
+            directory,
+            reason: format!(
+                "decoy file {} was {what}; nothing legitimate touches it",
+                path.file_name().unwrap_or_default().to_string_lossy()
+            ),
+            paths: Vec::new(),
+            canary: Some(file.clone()),
+            process,
+        },
+    );
+
+    let _ = app
+        .notification()
+        .builder()
+        .title("Stellar Antivirus – possible ransomware")
+        .body(format!(
+            "A decoy file was {what}: {file}. Something may be encrypting or wiping your files.{by}"
+        ))
+        .show();
+}
+
+/// Scans one settled file from the realtime watcher and reports what it finds.
+/// Runs on the dispatch workers.
+fn scan_realtime_file(
+    app_handle: &AppHandle,
+    path: &Path,
+    behaviour: &Mutex<behaviour::BehaviourMonitor>,
+) {
+    let file = path.to_string_lossy().to_string();
+    let (content, facts) = FileFacts::gather(path);
+
+    if content
+        .as_ref()
+        .is_some_and(|c| allowlist::is_allowlisted(&c.hashes.sha256))
+    {
+        return;
+    }
+
+    let mut detections = local_detections(path, content.as_ref(), &facts);
+    let hashes = content.map(|c| c.hashes);
+
+    let alert = hashes.as_ref().and_then(|h| {
+        let mut behaviour = behaviour.lock().ok()?;
+        behaviour.record_write(path, facts.file_type, &h.entropy, Instant::now())
+    });
+    if let Some(alert) = alert {
+        raise_ransomware_alert(app_handle, alert);
+    }
+
+    // Suspicious local findings still get the hash verdict; only a hard local
+    // hit makes the lookup moot
+    if !detections.iter().any(DetectionRecord::is_threat) {
+        if let Some(hashes) = &hashes {
+            match call_threat_api_single(hashes, &facts) {
+                Ok(Some(result)) => {
+                    let verdict = result.verdict.to_lowercase();
+                    if verdict == "unknown" {
+                        submission::maybe_submit_unknown(app_handle, path, &hashes.sha256);
+                    } else if verdict != "clean" {
+                        detections.push(cloud_detection(&result, &file, Some(hashes)));
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    eprintln!("[Realtime] API error for {}: {e}", file);
+                    notify_api_error_state(app_handle, &e);
+                }
+            }
         }
+    }
+
+    if detections.is_empty() {
+        return;
+    }
+
+    let process = process::attribute(path);
+    let by = process
+        .as_ref()
+        .map(|p| format!(" (written by {})", p.describe()))
+        .unwrap_or_default();
+    for d in &mut detections {
+        d.process = process.clone();
+    }
+
+    // The watcher sees files after the fact; only on-access mode stops them
+    let blocked = onaccess::remember(&detections);
+    let body = if blocked {
+        format!("Real-time protection blocked access to: {}{}", file, by)
+    } else if detections.iter().any(DetectionRecord::is_threat) {
+        format!("Real-time protection detected a threat: {}{}", file, by)
+    } else {
+        format!(
+            "Real-time protection flagged a suspicious file: {}{}",
+            file, by
+        )
+    };
+
+    let _ = app_handle.emit(
+        "realtime_threat_detected",
+        RealtimeThreatPayload {
+            scan: ScanFinishedPayload::from_detections(detections),
+            blocked,
+        },
+    );
+
+    let _ = app_handle
+        .notification()
+        .builder()
+        .title("Stellar Antivirus")
+        .body(body)
+        .show();
+}
+
+fn start_realtime_watcher(app_handle: AppHandle) {
+    thread::spawn(move || {
+        let watch_paths = realtime_watch_dirs();
 
         let quarantine_dir = quarantine_root();
+        process::start_tracking(&watch_paths);
 
-        use std::collections::HashMap;
-        use std::time::{Duration as StdDuration, Instant};
+        let mut settle = settle::SettleQueue::new();
+        let mut last_settle_poll = Instant::now();
+        let behaviour = Arc::new(Mutex::new(behaviour::BehaviourMonitor::new()));
+        let mut rename_from: Option<PathBuf> = None;
+        let mut canary_alerts: HashMap<PathBuf, Instant> = HashMap::new();
 
-        let mut recent_hits: HashMap<String, Instant> = HashMap::new();
-        let suppress_window = StdDuration::from_secs(2);
+        let canaries = canary::plant(&canary_dirs());
+        println!("[CANARY] planted {} decoy file(s)", canaries.len());
+
+        {
+            let app_handle = app_handle.clone();
+            let behaviour = Arc::clone(&behaviour);
+            dispatch::start_workers(move |path: &Path| {
+                if REALTIME_ENABLED.load(Ordering::SeqCst) {
+                    scan_realtime_file(&app_handle, path, &behaviour);
+                }
+            });
+        }
 
         let (tx, rx) = mpsc::channel::<Event>();
 
@@ -910,7 +2268,28 @@ fn start_realtime_watcher(app_handle: AppHandle) {
 
         println!("Realtime watcher started on {:?}", watch_paths);
 
-        for event in rx {
+        loop {
+            // Wake up regularly even without events, to scan files that settled
+            let event = match rx.recv_timeout(REALTIME_SETTLE_TICK) {
+                Ok(event) => Some(event),
+                Err(mpsc::RecvTimeoutError::Timeout) => None,
+                Err(mpsc::RecvTimeoutError::Disconnected) => break,
+            };
+
+            if last_settle_poll.elapsed() >= REALTIME_SETTLE_TICK {
+                last_settle_poll = Instant::now();
+                for path in settle.due(last_settle_poll) {
+                    // Queue full: keep the file pending instead of losing the scan
+                    if !dispatch::submit(path.clone()) {
+                        settle.requeue(&path, last_settle_poll);
+                    }
+                }
+            }
+
+            let Some(event) = event else {
+                continue;
+            };
+
             if !REALTIME_ENABLED.load(Ordering::SeqCst) {
                 continue;
             }
@@ -928,6 +2307,15 @@ fn start_realtime_watcher(app_handle: AppHandle) {
                 continue;
             }
 
+            // Our own re-planting goes through a staging file; ignore it
+            if event.paths.iter().any(|p| canary::is_staging(p)) {
+                continue;
+            }
+            if event.paths.iter().any(|p| canary::is_canary(p)) {
+                handle_canary_event(&app_handle, &event, &mut canary_alerts);
+                continue;
+            }
+
             let file = path.to_string_lossy().to_string();
 
             let kind_str = match &event.kind {
@@ -947,69 +2335,50 @@ fn start_realtime_watcher(app_handle: AppHandle) {
                 },
             );
 
-            let relevant =
-                matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_) | EventKind::Any);
-
-            if !relevant {
-                continue;
-            }
-
-            thread::sleep(Duration::from_millis(20));
+            // Backends report renames as one event with both names or as a from/to pair
+            let rename = match (&event.kind, event.paths.as_slice()) {
+                (EventKind::Modify(ModifyKind::Name(RenameMode::Both)), [from, to]) => {
+                    Some((from.clone(), to.clone()))
+                }
+                (EventKind::Modify(ModifyKind::Name(RenameMode::From)), [from]) => {
+                    rename_from = Some(from.clone());
+                    None
+                }
+                (EventKind::Modify(ModifyKind::Name(RenameMode::To)), [to]) => {
+                    rename_from.take().map(|from| (from, to.clone()))
+                }
+                _ => None,
+            };
 
             let now = Instant::now();
-            if let Some(last) = recent_hits.get(&file) {
-                if now.duration_since(*last) < suppress_window {
-                    continue;
+            if let Some((from, to)) = &rename {
+                let alert = behaviour
+                    .lock()
+                    .ok()
+                    .and_then(|mut b| b.record_rename(from, to, now));
+                if let Some(alert) = alert {
+                    raise_ransomware_alert(&app_handle, alert);
                 }
-            }
-            recent_hits.insert(file.clone(), now);
-
-            if recent_hits.len() > 256 {
-                let cutoff = Instant::now() - suppress_window;
-                recent_hits.retain(|_, t| *t >= cutoff);
-            }
-
-            let mut detected_name: Option<String> = None;
-
-            if is_test_filename(path) {
-                detected_name = Some("Stellar.Test.FileNameRule".to_string());
-            } else if let Some(hash) = sha256_of_file(path) {
-                let hash_lower = hash.to_lowercase();
-
-                match call_threat_api_single(&hash_lower) {
-                    Ok(Some(result)) => {
-                        let verdict = result.verdict.to_lowercase();
-                        if verdict != "clean" && verdict != "unknown" {
-                            let name = result
-                                .signature
-                                .as_ref()
-                                .map(|s| s.name.clone())
-                                .unwrap_or_else(|| "Unknown threat".to_string());
-
-                            detected_name = Some(name);
-                        }
-                    }
-                    Ok(None) => {}
-                    Err(e) => {
-                        eprintln!("[Realtime] API error for {}: {e}", file);
-                    }
+                settle.renamed(from, to, now);
+                if to.is_dir() {
+                    process::track_dir(to);
                 }
+                continue;
             }
 
-            if detected_name.is_some() {
-                let _ = app_handle.emit(
-                    "realtime_threat_detected",
-                    ScanFinishedPayload {
-                        threats: vec![("Threat detected".to_string(), file.clone())],
-                    },
-                );
-
-                let _ = app_handle
-                    .notification()
-                    .builder()
-                    .title("Stellar Antivirus")
-                    .body(format!("Real-time protection blocked: {}", file))
-                    .show();
+            // Files are scanned once written, not on every event
+            match event.kind {
+                EventKind::Create(CreateKind::Folder) => process::track_dir(path),
+                EventKind::Access(AccessKind::Close(AccessMode::Write)) => {
+                    settle.closed(path, now)
+                }
+                EventKind::Remove(_) => settle.removed(path),
+                // The other half of a rename pair is still to come
+                EventKind::Modify(ModifyKind::Name(RenameMode::From)) => {}
+                EventKind::Create(_) | EventKind::Modify(_) | EventKind::Any => {
+                    settle.written(path, now)
+                }
+                _ => {}
             }
         }
     });
@@ -1123,6 +2492,21 @@ pub fn run() {
             quick_scan,
             get_realtime_enabled,
             set_realtime_enabled,
+            get_on_access_status,
+            set_on_access_blocking,
+            dispatch::get_realtime_queue_status,
+            set_session_token,
+            get_network_config,
+            set_network_config,
+            submission::get_sample_submission_config,
+            submission::set_sample_submission_consent,
+            submission::get_pending_submissions,
+            rules::reload_local_rules,
+            signatures::reload_signatures,
+            fuzzy::reload_fuzzy_hashes,
+            allowlist::report_false_positive,
+            allowlist::revoke_false_positive,
+            allowlist::get_allowlist,
             quarantine_files,
             restore_from_quarantine,
             delete_quarantine_files,
@@ -1141,6 +2525,9 @@ pub fn run() {
             let cfg = load_runtime_config();
             REALTIME_ENABLED.store(cfg.realtime_enabled, Ordering::SeqCst);
             println!("[BOOT] realtime_enabled={}", cfg.realtime_enabled);
+            if cfg.on_access_blocking {
+                onaccess::set_enabled(true, realtime_watch_dirs());
+            }
 
             // Tray so app can live in background
             init_tray(app)?;
@@ -1149,6 +2536,9 @@ pub fn run() {
             let handle = app.handle().clone();
             start_realtime_watcher(handle);
 
+            // Follow up on samples uploaded for analysis
+            submission::start_submission_rechecker(app.handle().clone());
+
             // If launched by autostart, boot silently (hidden + no Dock icon on macOS)
             if is_autostart {
                 hide_main_window(app.handle());
@@ -0,0 +1,421 @@
+// Blocking on-access protection via fanotify permission events (Linux only).
+//
+// The notify watcher learns about a file after it was written, so on its own
+// it can only report. With permission events the kernel holds every open and
+// exec on the watched mounts until we answer, which lets us deny files we
+// already have a malicious verdict for. Verdicts come from the watcher and from
+// scans: the opener is stalled while we decide, so the handler never calls the
+// API. Opens outside the protected folders are answered straight away; small
+// files inside them are hashed on worker threads, and an open still undecided
+// at the deadline is allowed.
+
+use std::path::PathBuf;
+
+use serde::Serialize;
+
+use crate::DetectionRecord;
+
+#[derive(Serialize, Clone)]
+pub(crate) struct OnAccessStatus {
+    /// The user asked for blocking.
+    enabled: bool,
+    /// Permission events are being enforced; false means observe-only.
+    active: bool,
+    error: Option<String>,
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::{
+        collections::{HashMap, HashSet},
+        fs,
+        io::Read,
+        os::unix::fs::MetadataExt,
+        path::PathBuf,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            mpsc::{self, Receiver, TrySendError},
+            Arc, Mutex, OnceLock,
+        },
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use sha2::{Digest, Sha256};
+
+    use crate::{
+        fanotify::{FanEvent, Fanotify},
+        DetectionRecord, REALTIME_ENABLED,
+    };
+
+    // Larger files are let through unless already known by identity
+    const MAX_HASH_BYTES: u64 = 4 * 1024 * 1024;
+    // An open still undecided after this long is let through
+    const HASH_DEADLINE: Duration = Duration::from_millis(1500);
+    const HASH_WORKERS: usize = 2;
+    const HASH_QUEUE: usize = 256;
+    const MAX_CHECKED_FILES: usize = 16384;
+
+    pub(super) static ENABLED: AtomicBool = AtomicBool::new(false);
+    pub(super) static ACTIVE: AtomicBool = AtomicBool::new(false);
+    pub(super) static LAST_ERROR: Mutex<Option<String>> = Mutex::new(None);
+    static STARTED: AtomicBool = AtomicBool::new(false);
+
+    /// One version of one file: rewriting it changes size or mtime.
+    #[derive(Hash, PartialEq, Eq, Clone, Copy)]
+    struct FileKey {
+        dev: u64,
+        ino: u64,
+        size: u64,
+        mtime: i64,
+        mtime_nsec: i64,
+    }
+
+    impl FileKey {
+        fn of(meta: &fs::Metadata) -> Self {
+            FileKey {
+                dev: meta.dev(),
+                ino: meta.ino(),
+                size: meta.size(),
+                mtime: meta.mtime(),
+                mtime_nsec: meta.mtime_nsec(),
+            }
+        }
+    }
+
+    /// An open waiting for its file to be hashed.
+    struct HashJob {
+        event: FanEvent,
+        file: fs::File,
+        key: FileKey,
+        deadline: Instant,
+    }
+
+    enum Decision {
+        Allow,
+        Deny,
+        Hash(fs::File, FileKey),
+    }
+
+    #[derive(Default)]
+    struct Verdicts {
+        blocked_hashes: HashSet<String>,
+        // Content hash when known, so a false-positive report can lift the block
+        blocked_files: HashMap<FileKey, Option<String>>,
+        // Files hashed on open and found clean of known hashes
+        checked: HashMap<FileKey, bool>,
+    }
+
+    fn verdicts() -> &'static Mutex<Verdicts> {
+        static VERDICTS: OnceLock<Mutex<Verdicts>> = OnceLock::new();
+        VERDICTS.get_or_init(|| Mutex::new(Verdicts::default()))
+    }
+
+    pub(super) fn remember(detections: &[DetectionRecord]) -> bool {
+        let Ok(mut v) = verdicts().lock() else {
+            return false;
+        };
+
+        let mut any = false;
+        for d in detections.iter().filter(|d| d.verdict == "malicious") {
+            // Archive members are blocked through their container
+            let file = d.container.as_deref().unwrap_or(&d.path);
+            let sha256 = d
+                .sha256
+                .as_ref()
+                .filter(|_| d.container.is_none())
+                .map(|h| h.to_lowercase());
+            if let Ok(meta) = fs::metadata(file) {
+                v.blocked_files.insert(FileKey::of(&meta), sha256.clone());
+            }
+            if let Some(sha256) = sha256 {
+                v.blocked_hashes.insert(sha256);
+            }
+            any = true;
+        }
+        if any {
+            v.checked.clear();
+        }
+        any
+    }
+
+    pub(super) fn forget(sha256: &str) {
+        if let Ok(mut v) = verdicts().lock() {
+            v.blocked_hashes.remove(sha256);
+            v.blocked_files.retain(|_, h| h.as_deref() != Some(sha256));
+            v.checked.clear();
+        }
+    }
+
+    /// None when the deadline passes first.
+    fn sha256_of(mut file: &fs::File, deadline: Instant) -> Option<String> {
+        let mut hasher = Sha256::new();
+        let mut buf = [0u8; 64 * 1024];
+        loop {
+            if Instant::now() > deadline {
+                return None;
+            }
+            let n = file.read(&mut buf).ok()?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buf[..n]);
+        }
+        Some(hex::encode(hasher.finalize()))
+    }
+
+    /// Answers what can be answered without reading the file. The path is
+    /// resolved last: it costs a readlink and is only needed to decide whether
+    /// an unchecked file is worth hashing.
+    fn decide(event: &FanEvent, dirs: &[PathBuf]) -> Decision {
+        if !REALTIME_ENABLED.load(Ordering::SeqCst) || !ENABLED.load(Ordering::SeqCst) {
+            return Decision::Allow;
+        }
+        let Some(file) = event.file() else {
+            return Decision::Allow;
+        };
+        let Ok(meta) = file.metadata() else {
+            return Decision::Allow;
+        };
+        if !meta.is_file() {
+            return Decision::Allow;
+        }
+        let key = FileKey::of(&meta);
+
+        {
+            let Ok(v) = verdicts().lock() else {
+                return Decision::Allow;
+            };
+            // A blocked file stays blocked when moved out of the protected folders
+            if v.blocked_files.contains_key(&key) {
+                return Decision::Deny;
+            }
+            match v.checked.get(&key) {
+                Some(true) => return Decision::Allow,
+                Some(false) => return Decision::Deny,
+                // Nothing to compare against, or too big to hash while the opener waits
+                None if v.blocked_hashes.is_empty() || meta.len() > MAX_HASH_BYTES => {
+                    return Decision::Allow
+                }
+                None => {}
+            }
+        }
+
+        // Mount marks see the whole filesystem; only protected folders get hashed
+        let watched = event
+            .path()
+            .is_some_and(|p| dirs.iter().any(|d| p.starts_with(d)));
+        if watched {
+            Decision::Hash(file, key)
+        } else {
+            Decision::Allow
+        }
+    }
+
+    /// Records the hash of an opened file; false when it matches a blocked hash.
+    fn check_hash(key: FileKey, sha256: String) -> bool {
+        let Ok(mut v) = verdicts().lock() else {
+            return true;
+        };
+        // Copies and renames of a known-bad file carry its hash
+        let clean = !v.blocked_hashes.contains(&sha256);
+        if v.checked.len() >= MAX_CHECKED_FILES {
+            v.checked.clear();
+        }
+        v.checked.insert(key, clean);
+        if !clean {
+            v.blocked_files.insert(key, Some(sha256));
+        }
+        clean
+    }
+
+    fn answer(fan: &Fanotify, event: &FanEvent, allowed: bool) {
+        if !allowed {
+            println!(
+                "[ONACCESS] denied PID {} access to {:?}",
+                event.pid,
+                event.path()
+            );
+        }
+        if let Err(e) = fan.respond(event, allowed) {
+            eprintln!("[ONACCESS] failed to answer permission event: {e}");
+        }
+    }
+
+    fn hash_worker(fan: Arc<Fanotify>, jobs: Arc<Mutex<Receiver<HashJob>>>) {
+        loop {
+            // Ends once the reader thread is gone and drops the sender
+            let job = match jobs.lock() {
+                Ok(rx) => rx.recv(),
+                Err(_) => return,
+            };
+            let Ok(job) = job else {
+                return;
+            };
+            // Out of time means allowed; the watcher still scans the file
+            let allowed = match sha256_of(&job.file, job.deadline) {
+                Some(sha256) => check_hash(job.key, sha256),
+                None => true,
+            };
+            answer(&fan, &job.event, allowed);
+        }
+    }
+
+    fn set_error(message: String) {
+        eprintln!("[ONACCESS] {message}; staying observe-only");
+        if let Ok(mut e) = LAST_ERROR.lock() {
+            *e = Some(message);
+        }
+    }
+
+    /// Starts the permission handler once; later calls only flip `ENABLED`.
+    pub(super) fn start(dirs: Vec<PathBuf>) {
+        if STARTED.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        let fan = match Fanotify::new(libc::FAN_CLASS_CONTENT) {
+            Ok(f) => f,
+            Err(e) => {
+                STARTED.store(false, Ordering::SeqCst);
+                return set_error(format!("fanotify unavailable ({e})"));
+            }
+        };
+
+        // Exec permission events need Linux 5.0; an exec is still an open before that
+        let mut marked = 0;
+        for dir in &dirs {
+            let result = fan
+                .mark_mount(dir, libc::FAN_OPEN_PERM | libc::FAN_OPEN_EXEC_PERM)
+                .or_else(|_| fan.mark_mount(dir, libc::FAN_OPEN_PERM));
+            match result {
+                Ok(()) => marked += 1,
+                Err(e) => eprintln!("[ONACCESS] fanotify mark failed for {:?}: {e}", dir),
+            }
+        }
+        if marked == 0 {
+            STARTED.store(false, Ordering::SeqCst);
+            return set_error("could not watch any folder with fanotify".to_string());
+        }
+
+        if let Ok(mut e) = LAST_ERROR.lock() {
+            *e = None;
+        }
+        ACTIVE.store(true, Ordering::SeqCst);
+        println!("[ONACCESS] blocking on-access protection active");
+
+        let fan = Arc::new(fan);
+        let (jobs_tx, jobs_rx) = mpsc::sync_channel::<HashJob>(HASH_QUEUE);
+        let jobs_rx = Arc::new(Mutex::new(jobs_rx));
+        for _ in 0..HASH_WORKERS {
+            let (fan, jobs_rx) = (fan.clone(), jobs_rx.clone());
+            thread::spawn(move || hash_worker(fan, jobs_rx));
+        }
+
+        let me = std::process::id() as i32;
+        thread::spawn(move || loop {
+            let events = match fan.read_events() {
+                Ok(events) => events,
+                Err(e) => {
+                    // Dropping the fanotify fd lets every pending open through
+                    ACTIVE.store(false, Ordering::SeqCst);
+                    STARTED.store(false, Ordering::SeqCst);
+                    return set_error(format!("fanotify read failed ({e})"));
+                }
+            };
+
+            for event in events {
+                // Our own scans and hashing must never wait on ourselves
+                if event.pid == me {
+                    answer(&fan, &event, true);
+                    continue;
+                }
+                let (file, key) = match decide(&event, &dirs) {
+                    Decision::Allow => {
+                        answer(&fan, &event, true);
+                        continue;
+                    }
+                    Decision::Deny => {
+                        answer(&fan, &event, false);
+                        continue;
+                    }
+                    Decision::Hash(file, key) => (file, key),
+                };
+
+                let job = HashJob {
+                    event,
+                    file,
+                    key,
+                    deadline: Instant::now() + HASH_DEADLINE,
+                };
+                // Hashers saturated: let the open through rather than stall it
+                if let Err(TrySendError::Full(job) | TrySendError::Disconnected(job)) =
+                    jobs_tx.try_send(job)
+                {
+                    answer(&fan, &job.event, true);
+                }
+            }
+        });
+    }
+}
+
+/// Records malicious verdicts for on-access enforcement. True when those files
+/// are now actually blocked, not just reported.
+#[cfg(target_os = "linux")]
+pub(crate) fn remember(detections: &[DetectionRecord]) -> bool {
+    use std::sync::atomic::Ordering;
+
+    linux::remember(detections)
+        && linux::ENABLED.load(Ordering::SeqCst)
+        && linux::ACTIVE.load(Ordering::SeqCst)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn remember(_detections: &[DetectionRecord]) -> bool {
+    false
+}
+
+/// Lifts the block on content the user declared clean.
+#[cfg(target_os = "linux")]
+pub(crate) fn forget(sha256: &str) {
+    linux::forget(sha256)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn forget(_sha256: &str) {}
+
+/// Turns blocking on or off; falls back to observe-only when fanotify is not
+/// available.
+#[cfg(target_os = "linux")]
+pub(crate) fn set_enabled(enabled: bool, dirs: Vec<PathBuf>) {
+    use std::sync::atomic::Ordering;
+
+    linux::ENABLED.store(enabled, Ordering::SeqCst);
+    if enabled {
+        linux::start(dirs);
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn set_enabled(_enabled: bool, _dirs: Vec<PathBuf>) {}
+
+#[cfg(target_os = "linux")]
+pub(crate) fn status() -> OnAccessStatus {
+    use std::sync::atomic::Ordering;
+
+    let enabled = linux::ENABLED.load(Ordering::SeqCst);
+    OnAccessStatus {
+        enabled,
+        active: enabled && linux::ACTIVE.load(Ordering::SeqCst),
+        error: linux::LAST_ERROR.lock().ok().and_then(|e| e.clone()),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn status() -> OnAccessStatus {
+    OnAccessStatus {
+        enabled: false,
+        active: false,
+        error: Some("On-access blocking needs Linux with fanotify".to_string()),
+    }
+}
@@ -0,0 +1,744 @@
+// PE (Windows executable) static analysis: headers, imports, sections,
+// overlay, Authenticode presence and compile timestamp sanity.
+//
+// Pure byte parsing, so Linux and macOS hosts can judge the Windows installers
+// that pile up in Downloads. Like the ELF analyzer, out-of-bounds reads end
+// that part of the walk instead of failing the whole file.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+
+use crate::{entropy, indicator_score, Indicator};
+
+/// Score from which the scanner reports the file as suspicious.
+pub(crate) const DETECTION_SCORE: u32 = 60;
+pub(crate) const DETECTION_NAME: &str = "Stellar.Heur.PE.Suspicious";
+
+const MAX_SECTIONS: usize = 96; // loader limit
+const MAX_IMPORT_DLLS: usize = 256;
+const MAX_IMPORTS: usize = 8192;
+const MAX_REPORTED_IMPORTS: usize = 256;
+
+const IMAGE_FILE_DLL: u16 = 0x2000;
+const SCN_MEM_EXECUTE: u32 = 0x2000_0000;
+const SCN_MEM_WRITE: u32 = 0x8000_0000;
+
+const DIR_IMPORT: usize = 1;
+const DIR_SECURITY: usize = 4;
+const DIR_CLR: usize = 14;
+
+// 1995-01-01: nothing PE32 was linked before Windows 95
+const OLDEST_PLAUSIBLE_TIMESTAMP: u64 = 788_918_400;
+
+// Section names left behind by common packers / protectors
+const PACKER_SECTIONS: &[(&str, &str)] = &[
+    ("UPX0", "UPX"),
+    ("UPX1", "UPX"),
+    ("UPX2", "UPX"),
+    (".aspack", "ASPack"),
+    (".adata", "ASPack"),
+    ("MPRESS1", "MPRESS"),
+    ("MPRESS2", "MPRESS"),
+    (".petite", "Petite"),
+    (".nsp0", "NsPack"),
+    (".nsp1", "NsPack"),
+    (".themida", "Themida"),
+    (".winlice", "WinLicense"),
+    (".vmp0", "VMProtect"),
+    (".vmp1", "VMProtect"),
+    (".enigma1", "Enigma"),
+    ("PEC2", "PECompact"),
+    ("pec1", "PECompact"),
+];
+
+#[derive(Serialize, Clone)]
+pub(crate) struct PeSection {
+    name: String,
+    virtual_size: u32,
+    raw_size: u32,
+    entropy: f64,
+    executable: bool,
+    writable: bool,
+}
+
+#[derive(Serialize, Clone)]
+pub(crate) struct PeReport {
+    class: &'static str, // "pe32" | "pe32+"
+    machine: String,
+    subsystem: String,
+    is_dll: bool,
+    is_dotnet: bool,
+    timestamp: u32,
+    entry_point: u32,
+    entry_section: Option<String>,
+    import_dlls: Vec<String>,
+    imports: Vec<String>, // "kernel32.dll!CreateFileW"
+    import_count: usize,
+    sections: Vec<PeSection>,
+    overlay_size: u64,
+    pub(crate) signed: bool,
+    packer: Option<String>,
+    pub(crate) score: u32,
+    pub(crate) indicators: Vec<Indicator>,
+}
+
+/// Compact subset of the report sent with the hash lookup.
+#[derive(Serialize, Clone)]
+pub(crate) struct PeMetadata {
+    machine: String,
+    subsystem: String,
+    is_dll: bool,
+    timestamp: u32,
+    signed: bool,
+    packer: Option<String>,
+    overlay_size: u64,
+    import_count: usize,
+    section_names: Vec<String>,
+    score: u32,
+}
+
+// ---- Byte access ----
+
+fn bytes<const N: usize>(data: &[u8], off: u64) -> Option<[u8; N]> {
+    let off = usize::try_from(off).ok()?;
+    data.get(off..off.checked_add(N)?)?.try_into().ok()
+}
+
+fn u16_at(data: &[u8], off: u64) -> Option<u16> {
+    bytes(data, off).map(u16::from_le_bytes)
+}
+
+fn u32_at(data: &[u8], off: u64) -> Option<u32> {
+    bytes(data, off).map(u32::from_le_bytes)
+}
+
+fn u64_at(data: &[u8], off: u64) -> Option<u64> {
+    bytes(data, off).map(u64::from_le_bytes)
+}
+
+fn cstr_at(data: &[u8], off: u64, max: usize) -> Option<String> {
+    let start = usize::try_from(off).ok()?;
+    let rest = data.get(start..)?;
+    let rest = &rest[..rest.len().min(max)];
+    let end = memchr::memchr(0, rest)?;
+    Some(String::from_utf8_lossy(&rest[..end]).to_string())
+}
+
+// ---- Parsed tables ----
+
+struct Section {
+    name: String,
+    virtual_size: u32,
+    virtual_address: u32,
+    raw_size: u32,
+    raw_pointer: u32,
+    characteristics: u32,
+}
+
+impl Section {
+    fn contains_rva(&self, rva: u32) -> bool {
+        let span = self.virtual_size.max(self.raw_size);
+        rva >= self.virtual_address && rva - self.virtual_address < span
+    }
+
+    fn raw_end(&self) -> u64 {
+        u64::from(self.raw_pointer) + u64::from(self.raw_size)
+    }
+}
+
+fn rva_to_offset(sections: &[Section], rva: u32) -> Option<u64> {
+    let s = sections.iter().find(|s| s.contains_rva(rva))?;
+    Some(u64::from(s.raw_pointer) + u64::from(rva - s.virtual_address))
+}
+
+fn machine_name(machine: u16) -> String {
+    match machine {
+        0x014c => "x86".to_string(),
+        0x8664 => "x86_64".to_string(),
+        0x01c0 | 0x01c4 => "arm".to_string(),
+        0xaa64 => "arm64".to_string(),
+        0x0200 => "ia64".to_string(),
+        other => format!("0x{other:04x}"),
+    }
+}
+
+fn subsystem_name(subsystem: u16) -> String {
+    match subsystem {
+        1 => "native".to_string(),
+        2 => "windows_gui".to_string(),
+        3 => "windows_cui".to_string(),
+        9 => "windows_ce".to_string(),
+        10..=13 => "efi".to_string(),
+        other => format!("{other}"),
+    }
+}
+
+/// `(dll, function)` pairs; ordinals come out as `#123`.
+fn parse_imports(
+    data: &[u8],
+    sections: &[Section],
+    dir_rva: u32,
+    pe32_plus: bool,
+) -> Vec<(String, String)> {
+    let mut out: Vec<(String, String)> = Vec::new();
+    let Some(dir_off) = rva_to_offset(sections, dir_rva) else {
+        return out;
+    };
+
+    let thunk_size: u64 = if pe32_plus { 8 } else { 4 };
+    let ordinal_flag: u64 = if pe32_plus { 1 << 63 } else { 1 << 31 };
+
+    for i in 0..MAX_IMPORT_DLLS as u64 {
+        let desc = dir_off + i * 20;
+        let (Some(original_thunk), Some(name_rva), Some(first_thunk)) = (
+            u32_at(data, desc),
+            u32_at(data, desc + 12),
+            u32_at(data, desc + 16),
+        ) else {
+            break;
+        };
+        if name_rva == 0 && first_thunk == 0 {
+            break;
+        }
+
+        let dll = rva_to_offset(sections, name_rva)
+            .and_then(|o| cstr_at(data, o, 256))
+            .unwrap_or_default()
+            .to_lowercase();
+
+        // Bound imports may have rewritten FirstThunk; the original list is cleaner
+        let thunk_rva = if original_thunk != 0 {
+            original_thunk
+        } else {
+            first_thunk
+        };
+        let Some(thunk_off) = rva_to_offset(sections, thunk_rva) else {
+            continue;
+        };
+
+        for j in 0.. {
+            if out.len() >= MAX_IMPORTS {
+                return out;
+            }
+            let at = thunk_off + j * thunk_size;
+            let entry = if pe32_plus {
+                u64_at(data, at)
+            } else {
+                u32_at(data, at).map(u64::from)
+            };
+            let Some(entry) = entry.filter(|e| *e != 0) else {
+                break;
+            };
+
+            let func = if entry & ordinal_flag != 0 {
+                format!("#{}", entry & 0xffff)
+            } else {
+                let hint_name = u32::try_from(entry & 0x7fff_ffff).ok();
+                match hint_name.and_then(|rva| rva_to_offset(sections, rva)) {
+                    Some(off) => cstr_at(data, off + 2, 512).unwrap_or_default(),
+                    None => break,
+                }
+            };
+            out.push((dll.clone(), func));
+        }
+    }
+
+    out
+}
+
+fn detect_packer(sections: &[Section]) -> Option<String> {
+    sections.iter().find_map(|s| {
+        PACKER_SECTIONS
+            .iter()
+            .find(|(name, _)| s.name.eq_ignore_ascii_case(name))
+            .map(|(_, packer)| packer.to_string())
+    })
+}
+
+// ---- Heuristics ----
+
+fn imports_any(imports: &[(String, String)], names: &[&str]) -> bool {
+    imports.iter().any(|(_, f)| {
+        names
+            .iter()
+            .any(|n| f == n || f.strip_suffix(['A', 'W']) == Some(n))
+    })
+}
+
+fn score_indicators(
+    report: &PeReport,
+    imports: &[(String, String)],
+    file_len: u64,
+) -> Vec<Indicator> {
+    let mut out: Vec<Indicator> = Vec::new();
+    let mut add = |id: &'static str, weight: u32, detail: String| {
+        out.push(Indicator { id, weight, detail });
+    };
+
+    if let Some(packer) = &report.packer {
+        add("packed", 30, format!("Packed with {packer}"));
+    }
+
+    if let Some(s) = report
+        .sections
+        .iter()
+        .find(|s| s.executable && s.entropy > 7.2)
+    {
+        add(
+            "high_entropy_code",
+            25,
+            format!("Executable section {} has entropy {:.2}", s.name, s.entropy),
+        );
+    }
+
+    if let Some(s) = report.sections.iter().find(|s| s.executable && s.writable) {
+        add(
+            "wx_section",
+            25,
+            format!("Section {} is writable and executable", s.name),
+        );
+    }
+
+    if !report.is_dll && report.entry_point != 0 {
+        let in_code = report.entry_section.as_ref().is_some_and(|name| {
+            report
+                .sections
+                .iter()
+                .any(|s| &s.name == name && s.executable)
+        });
+        if !in_code {
+            add(
+                "entry_outside_code",
+                20,
+                "Entry point outside executable sections".to_string(),
+            );
+        }
+    }
+
+    // .NET assemblies import only mscoree; that's normal
+    if !report.is_dotnet && report.import_count <= 3 && !report.is_dll {
+        add(
+            "minimal_imports",
+            15,
+            format!("Only {} imported function(s)", report.import_count),
+        );
+    }
+
+    if imports_any(imports, &["VirtualAllocEx"])
+        && imports_any(imports, &["WriteProcessMemory"])
+        && imports_any(
+            imports,
+            &["CreateRemoteThread", "NtCreateThreadEx", "QueueUserAPC"],
+        )
+    {
+        add(
+            "injection_imports",
+            35,
+            "Imports remote allocation, write and thread creation".to_string(),
+        );
+    }
+
+    if imports_any(imports, &["SetWindowsHookEx"])
+        && imports_any(
+            imports,
+            &["GetAsyncKeyState", "GetKeyState", "GetKeyboardState"],
+        )
+    {
+        add(
+            "keylogger_imports",
+            20,
+            "Imports keyboard hook and key state functions".to_string(),
+        );
+    }
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let ts = u64::from(report.timestamp);
+    // Reproducible builds store a hash here, so keep these weights low
+    if ts > now + 24 * 60 * 60 {
+        add(
+            "future_timestamp",
+            10,
+            format!("Compile timestamp {ts} is in the future"),
+        );
+    } else if ts != 0 && ts < OLDEST_PLAUSIBLE_TIMESTAMP {
+        add(
+            "ancient_timestamp",
+            5,
+            format!("Compile timestamp {ts} predates Windows 95"),
+        );
+    }
+
+    // Installers carry payloads in the overlay but are normally signed
+    if !report.signed && file_len > 0 && report.overlay_size * 2 > file_len {
+        add(
+            "unsigned_large_overlay",
+            10,
+            format!(
+                "Unsigned with {} bytes appended after the image",
+                report.overlay_size
+            ),
+        );
+    }
+
+    out
+}
+
+/// Offset of the "PE\0\0" signature a DOS header points to (`e_lfanew`).
+pub(crate) fn nt_header_offset(data: &[u8]) -> Option<u64> {
+    if !data.starts_with(b"MZ") {
+        return None;
+    }
+    u32_at(data, 0x3c).map(u64::from)
+}
+
+/// Whether `data` is a PE image rather than a plain DOS program (or just "MZ").
+pub(crate) fn has_nt_header(data: &[u8]) -> bool {
+    nt_header_offset(data).and_then(|off| bytes::<4>(data, off)) == Some(*b"PE\0\0")
+}
+
+/// Whether an "MZ" file without a PE header has a DOS header a loader would
+/// accept: last-page byte count under a page, the header inside the image and
+/// the relocation table inside the header. Text that happens to start with
+/// "MZ" fails the first test on its own.
+pub(crate) fn has_dos_header(data: &[u8]) -> bool {
+    if !data.starts_with(b"MZ") || data.len() < 0x40 {
+        return false;
+    }
+    let field = |off: u64| u16_at(data, off).map(u64::from).unwrap_or(0);
+    let (last_page, pages) = (field(0x02), field(0x04));
+    let (relocs, header_paras, reloc_table) = (field(0x06), field(0x08), field(0x18));
+
+    if last_page >= 512 || pages == 0 {
+        return false;
+    }
+
+    let image_len = match last_page {
+        0 => pages * 512,
+        n => pages * 512 - (512 - n),
+    };
+    let header_len = header_paras * 16;
+    header_len >= 0x20
+        && header_len <= image_len
+        && reloc_table >= 0x1c
+        && reloc_table + relocs * 4 <= header_len
+}
+
+/// Parses a PE image. `None` if `data` isn't a PE or the headers are unusable.
+pub(crate) fn analyze(data: &[u8]) -> Option<PeReport> {
+    if !has_nt_header(data) {
+        return None;
+    }
+    let pe_off = nt_header_offset(data)?;
+
+    let coff = pe_off + 4;
+    let machine = u16_at(data, coff)?;
+    let num_sections = usize::from(u16_at(data, coff + 2)?).min(MAX_SECTIONS);
+    let timestamp = u32_at(data, coff + 4)?;
+    let opt_size = u64::from(u16_at(data, coff + 16)?);
+    let characteristics = u16_at(data, coff + 18)?;
+
+    let opt = coff + 20;
+    let pe32_plus = match u16_at(data, opt)? {
+        0x10b => false,
+        0x20b => true,
+        _ => return None,
+    };
+    let entry_point = u32_at(data, opt + 16)?;
+    let subsystem = u16_at(data, opt + 68)?;
+    let (num_dirs_off, dirs_off) = if pe32_plus { (108, 112) } else { (92, 96) };
+    let num_dirs = u32_at(data, opt + num_dirs_off).unwrap_or(0) as usize;
+    let dir = |idx: usize| -> (u32, u32) {
+        if idx >= num_dirs {
+            return (0, 0);
+        }
+        let at = opt + dirs_off + idx as u64 * 8;
+        (
+            u32_at(data, at).unwrap_or(0),
+            u32_at(data, at + 4).unwrap_or(0),
+        )
+    };
+
+    let table = opt + opt_size;
+    let sections: Vec<Section> = (0..num_sections as u64)
+        .map_while(|i| {
+            let at = table + i * 40;
+            let raw_name = bytes::<8>(data, at)?;
+            let end = raw_name.iter().position(|b| *b == 0).unwrap_or(8);
+            Some(Section {
+                name: String::from_utf8_lossy(&raw_name[..end]).to_string(),
+                virtual_size: u32_at(data, at + 8)?,
+                virtual_address: u32_at(data, at + 12)?,
+                raw_size: u32_at(data, at + 16)?,
+                raw_pointer: u32_at(data, at + 20)?,
+                characteristics: u32_at(data, at + 36)?,
+            })
+        })
+        .collect();
+
+    let (import_rva, _) = dir(DIR_IMPORT);
+    let imports = if import_rva != 0 {
+        parse_imports(data, &sections, import_rva, pe32_plus)
+    } else {
+        vec![]
+    };
+
+    // The security directory holds a file offset, not an RVA
+    let (cert_off, cert_size) = dir(DIR_SECURITY);
+    let cert_end = u64::from(cert_off) + u64::from(cert_size);
+    let file_len = data.len() as u64;
+    let signed = cert_off != 0 && cert_size >= 8 && cert_end <= file_len;
+
+    let image_end = sections.iter().map(Section::raw_end).max().unwrap_or(0);
+    let overlay_end = if signed && u64::from(cert_off) >= image_end {
+        u64::from(cert_off)
+    } else {
+        file_len
+    };
+    let overlay_size = overlay_end.saturating_sub(image_end);
+
+    let mut import_dlls: Vec<String> = Vec::new();
+    for (dll, _) in &imports {
+        if !import_dlls.contains(dll) {
+            import_dlls.push(dll.clone());
+        }
+    }
+
+    let mut report = PeReport {
+        class: if pe32_plus { "pe32+" } else { "pe32" },
+        machine: machine_name(machine),
+        subsystem: subsystem_name(subsystem),
+        is_dll: characteristics & IMAGE_FILE_DLL != 0,
+        is_dotnet: dir(DIR_CLR).0 != 0,
+        timestamp,
+        entry_point,
+        entry_section: sections
+            .iter()
+            .find(|s| s.contains_rva(entry_point))
+            .map(|s| s.name.clone()),
+        import_dlls,
+        imports: imports
+            .iter()
+            .take(MAX_REPORTED_IMPORTS)
+            .map(|(dll, f)| format!("{dll}!{f}"))
+            .collect(),
+        import_count: imports.len(),
+        sections: sections
+            .iter()
+            .map(|s| {
+                let start = s.raw_pointer as usize;
+                let end = (s.raw_end() as usize).min(data.len());
+                let content = data.get(start..end).unwrap_or(&[]);
+                PeSection {
+                    name: s.name.clone(),
+                    virtual_size: s.virtual_size,
+                    raw_size: s.raw_size,
+                    entropy: entropy::rounded(entropy::shannon(content)),
+                    executable: s.characteristics & SCN_MEM_EXECUTE != 0,
+                    writable: s.characteristics & SCN_MEM_WRITE != 0,
+                }
+            })
+            .collect(),
+        overlay_size,
+        signed,
+        packer: detect_packer(&sections),
+        score: 0,
+        indicators: vec![],
+    };
+
+    report.indicators = score_indicators(&report, &imports, file_len);
+    report.score = indicator_score(&report.indicators);
+
+    Some(report)
+}
+
+impl PeReport {
+    pub(crate) fn metadata(&self) -> PeMetadata {
+        PeMetadata {
+            machine: self.machine.clone(),
+            subsystem: self.subsystem.clone(),
+            is_dll: self.is_dll,
+            timestamp: self.timestamp,
+            signed: self.signed,
+            packer: self.packer.clone(),
+            overlay_size: self.overlay_size,
+            import_count: self.import_count,
+            section_names: self.sections.iter().map(|s| s.name.clone()).collect(),
+            score: self.score,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PE_OFF: usize = 0x40;
+    const COFF: usize = PE_OFF + 4;
+    const OPT: usize = COFF + 20;
+    const OPT_SIZE: usize = 96 + 16 * 8;
+    const TABLE: usize = OPT + OPT_SIZE;
+
+    fn put16(data: &mut [u8], at: usize, v: u16) {
+        data[at..at + 2].copy_from_slice(&v.to_le_bytes());
+    }
+
+    fn put32(data: &mut [u8], at: usize, v: u32) {
+        data[at..at + 4].copy_from_slice(&v.to_le_bytes());
+    }
+
+    fn set_dir(data: &mut [u8], idx: usize, rva: u32, size: u32) {
+        put32(data, OPT + 96 + idx * 8, rva);
+        put32(data, OPT + 96 + idx * 8 + 4, size);
+    }
+
+    fn set_section(data: &mut [u8], idx: usize, name: &[u8], va: u32, raw: (u32, u32)) {
+        let at = TABLE + idx * 40;
+        data[at..at + name.len()].copy_from_slice(name);
+        put32(data, at + 8, raw.1);
+        put32(data, at + 12, va);
+        put32(data, at + 16, raw.1);
+        put32(data, at + 20, raw.0);
+        put32(data, at + 36, SCN_MEM_EXECUTE);
+    }
+
+    /// PE32 with `.text` at RVA 0x1000 (file 0x400..0x600) and one import.
+    fn image() -> Vec<u8> {
+        let mut data = vec![0u8; 0x600];
+        data[..2].copy_from_slice(b"MZ");
+        put32(&mut data, 0x3c, PE_OFF as u32);
+        data[PE_OFF..PE_OFF + 4].copy_from_slice(b"PE\0\0");
+        put16(&mut data, COFF, 0x014c);
+        put16(&mut data, COFF + 2, 1);
+        put16(&mut data, COFF + 16, OPT_SIZE as u16);
+        put16(&mut data, OPT, 0x10b);
+        put32(&mut data, OPT + 16, 0x1000);
+        put16(&mut data, OPT + 68, 2);
+        put32(&mut data, OPT + 92, 16);
+        set_section(&mut data, 0, b".text", 0x1000, (0x400, 0x200));
+
+        // Descriptor at 0x1000, thunks at 0x1040, DLL name at 0x1080
+        set_dir(&mut data, DIR_IMPORT, 0x1000, 40);
+        put32(&mut data, 0x400, 0x1040);
+        put32(&mut data, 0x400 + 12, 0x1080);
+        put32(&mut data, 0x400 + 16, 0x1040);
+        put32(&mut data, 0x440, 0x8000_0007);
+        data[0x480..0x48c].copy_from_slice(b"kernel32.dll");
+        data
+    }
+
+    fn check_bounds(name: &str, data: &[u8]) -> Option<PeReport> {
+        let report = analyze(data)?;
+        assert!(report.sections.len() <= MAX_SECTIONS, "{name}");
+        assert!(report.import_count <= MAX_IMPORTS, "{name}");
+        assert!(report.imports.len() <= MAX_REPORTED_IMPORTS, "{name}");
+        assert!(report.import_dlls.len() <= MAX_IMPORTS, "{name}");
+        assert!(report.overlay_size <= data.len() as u64, "{name}");
+        Some(report)
+    }
+
+    #[test]
+    fn well_formed_image_parses() {
+        let report = check_bounds("image", &image()).unwrap();
+        assert_eq!(report.imports, vec!["kernel32.dll!#7"]);
+        assert_eq!(report.entry_section.as_deref(), Some(".text"));
+        assert_eq!(report.overlay_size, 0);
+    }
+
+    #[test]
+    fn every_truncation_is_handled() {
+        let data = image();
+        for len in 0..=data.len() {
+            let report = check_bounds("truncated", &data[..len]);
+            if len < TABLE {
+                assert!(report.is_none() || report.unwrap().sections.is_empty());
+            }
+        }
+    }
+
+    #[test]
+    fn hostile_headers_stay_bounded() {
+        let edit = |f: &dyn Fn(&mut Vec<u8>)| {
+            let mut data = image();
+            f(&mut data);
+            data
+        };
+
+        let cases: Vec<(&str, Vec<u8>)> = vec![
+            ("e_lfanew past EOF", edit(&|d| put32(d, 0x3c, u32::MAX))),
+            ("e_lfanew inside DOS header", edit(&|d| put32(d, 0x3c, 2))),
+            ("65535 sections", edit(&|d| put16(d, COFF + 2, u16::MAX))),
+            ("table past EOF", edit(&|d| put16(d, COFF + 16, u16::MAX))),
+            ("table overlaps headers", edit(&|d| put16(d, COFF + 16, 0))),
+            ("unknown optional magic", edit(&|d| put16(d, OPT, 0x1234))),
+            (
+                "huge directory count",
+                edit(&|d| put32(d, OPT + 92, u32::MAX)),
+            ),
+            (
+                "raw data past EOF",
+                edit(&|d| {
+                    set_section(d, 0, b".text", 0x1000, (0xffff_fff0, u32::MAX));
+                }),
+            ),
+            (
+                "virtual range wraps",
+                edit(&|d| {
+                    set_section(d, 0, b".text", u32::MAX - 4, (0x400, u32::MAX));
+                    put32(d, OPT + 16, u32::MAX);
+                }),
+            ),
+            (
+                "overlapping sections",
+                edit(&|d| {
+                    put16(d, COFF + 2, 2);
+                    set_section(d, 1, b"UPX1", 0x1000, (0x400, 0x200));
+                }),
+            ),
+            (
+                "import names past EOF",
+                edit(&|d| {
+                    put32(d, 0x400 + 12, 0x11f0);
+                    put32(d, 0x440, 0x11fe);
+                }),
+            ),
+            (
+                "certificate range wraps",
+                edit(&|d| set_dir(d, DIR_SECURITY, 0xffff_fff0, u32::MAX)),
+            ),
+        ];
+
+        for (name, data) in &cases {
+            check_bounds(name, data);
+        }
+
+        let get = |name: &str| {
+            let (_, data) = cases.iter().find(|(n, _)| *n == name).unwrap();
+            analyze(data)
+        };
+        assert!(get("e_lfanew past EOF").is_none());
+        assert!(get("unknown optional magic").is_none());
+        assert!(get("table past EOF").unwrap().sections.is_empty());
+        assert!(!get("certificate range wraps").unwrap().signed);
+        let overlapping = get("overlapping sections").unwrap();
+        assert_eq!(overlapping.entry_section.as_deref(), Some(".text"));
+        assert_eq!(overlapping.packer.as_deref(), Some("UPX"));
+    }
+
+    #[test]
+    fn endless_thunk_arrays_are_capped() {
+        // One section of 64 KB whose thunk array never ends
+        let mut data = image();
+        data.resize(0x400 + 0x10000, 0);
+        set_section(&mut data, 0, b".text", 0x1000, (0x400, 0x10000));
+        for at in (0x440..data.len()).step_by(4) {
+            put32(&mut data, at, 0x8000_0001);
+        }
+        let report = check_bounds("endless thunks", &data).unwrap();
+        assert_eq!(report.import_count, MAX_IMPORTS);
+        assert_eq!(report.imports.len(), MAX_REPORTED_IMPORTS);
+    }
+}
@@ -0,0 +1,336 @@
+// TLS trust for every API client: bundled web roots, user CA files, and
+// optional SPKI pins.
+//
+// Pins are checked inside the handshake by a rustls certificate verifier, so a
+// host that fails them never sees the request (bearer token, hashes, uploads).
+// Checking them on the response instead would be too late.
+
+use std::{fs, sync::Arc};
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use rustls::{
+    client::{
+        danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+        WebPkiServerVerifier,
+    },
+    crypto::{ring, CryptoProvider},
+    pki_types::{pem::PemObject, CertificateDer, ServerName, UnixTime},
+    ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme,
+};
+use sha2::{Digest, Sha256};
+
+use crate::{NetworkConfig, INTEGRITY_ERR_PREFIX};
+
+/// Splits one DER TLV off `buf`: (tag, whole TLV, remainder).
+pub(crate) fn der_next(buf: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let tag = *buf.first()?;
+    let first = *buf.get(1)? as usize;
+
+    let (len, header) = if first < 0x80 {
+        (first, 2)
+    } else {
+        let n = first & 0x7f;
+        if n == 0 || n > 4 {
+            return None;
+        }
+        let mut len = 0usize;
+        for b in buf.get(2..2 + n)? {
+            len = (len << 8) | *b as usize;
+        }
+        (len, 2 + n)
+    };
+
+    let end = header.checked_add(len)?;
+    let tlv = buf.get(..end)?;
+    Some((tag, tlv, &buf[end..]))
+}
+
+fn der_content(tlv: &[u8]) -> &[u8] {
+    let first = tlv.get(1).copied().unwrap_or(0) as usize;
+    let header = if first < 0x80 { 2 } else { 2 + (first & 0x7f) };
+    tlv.get(header..).unwrap_or(&[])
+}
+
+/// Extracts the DER SubjectPublicKeyInfo from an X.509 certificate.
+pub(crate) fn spki_of_certificate(cert_der: &[u8]) -> Option<&[u8]> {
+    let (_, cert, _) = der_next(cert_der)?;
+    let (_, tbs, _) = der_next(der_content(cert))?;
+    let mut rest = der_content(tbs);
+
+    // Optional explicit [0] version
+    if rest.first() == Some(&0xa0) {
+        rest = der_next(rest)?.2;
+    }
+
+    // serialNumber, signature, issuer, validity, subject
+    for _ in 0..5 {
+        rest = der_next(rest)?.2;
+    }
+
+    let (tag, spki, _) = der_next(rest)?;
+    (tag == 0x30).then_some(spki)
+}
+
+fn decode_spki_pin(pin: &str) -> Result<[u8; 32], String> {
+    let b64 = pin.trim().trim_start_matches("sha256/");
+    let bytes = BASE64
+        .decode(b64)
+        .map_err(|e| format!("Invalid SPKI pin {pin}: {e}"))?;
+    bytes
+        .try_into()
+        .map_err(|_| format!("Invalid SPKI pin {pin}: expected a SHA-256 digest"))
+}
+
+fn load_root_store(extra_ca_files: &[String]) -> Result<RootCertStore, String> {
+    let mut roots = RootCertStore {
+        roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+    };
+    for f in extra_ca_files {
+        let pem = fs::read(f).map_err(|e| format!("Failed to read CA file {f}: {e}"))?;
+        let mut found = 0;
+        for cert in CertificateDer::pem_slice_iter(&pem) {
+            let cert = cert.map_err(|e| format!("Invalid PEM in CA file {f}: {e}"))?;
+            roots
+                .add(cert)
+                .map_err(|e| format!("Invalid certificate in CA file {f}: {e}"))?;
+            found += 1;
+        }
+        if found == 0 {
+            return Err(format!("No certificates found in CA file {f}"));
+        }
+    }
+    Ok(roots)
+}
+
+/// Normal WebPKI validation, then the end-entity key must match a pin.
+#[derive(Debug)]
+struct PinnedVerifier {
+    inner: Arc<WebPkiServerVerifier>,
+    pins: Vec<[u8; 32]>,
+}
+
+impl ServerCertVerifier for PinnedVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let verified = self.inner.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            ocsp_response,
+            now,
+        )?;
+
+        let spki = spki_of_certificate(end_entity).ok_or_else(|| {
+            rustls::Error::General(format!(
+                "{INTEGRITY_ERR_PREFIX}: unparseable server certificate"
+            ))
+        })?;
+        let actual: [u8; 32] = Sha256::digest(spki).into();
+        if self.pins.contains(&actual) {
+            return Ok(verified);
+        }
+
+        Err(rustls::Error::General(format!(
+            "{INTEGRITY_ERR_PREFIX}: certificate pin mismatch (got sha256/{})",
+            BASE64.encode(actual)
+        )))
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+/// The rustls configuration for `net`; also how network settings are validated.
+pub(crate) fn tls_config(net: &NetworkConfig) -> Result<ClientConfig, String> {
+    let pins = net
+        .spki_pins
+        .iter()
+        .map(|p| decode_spki_pin(p))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let provider: Arc<CryptoProvider> = Arc::new(ring::default_provider());
+    let roots = load_root_store(&net.extra_ca_files)?;
+    let webpki = WebPkiServerVerifier::builder_with_provider(Arc::new(roots), provider.clone())
+        .build()
+        .map_err(|e| format!("Failed to set up certificate verification: {e}"))?;
+
+    let builder = ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .map_err(|e| format!("Failed to set up TLS: {e}"))?;
+    let config = if pins.is_empty() {
+        builder.with_webpki_verifier(webpki)
+    } else {
+        builder
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(PinnedVerifier {
+                inner: webpki,
+                pins,
+            }))
+    };
+
+    Ok(config.with_no_client_auth())
+}
+
+/// The pin failure behind a failed request, if that is what stopped it.
+pub(crate) fn pin_failure(err: &reqwest::Error) -> Option<String> {
+    let mut source: Option<&dyn std::error::Error> = Some(err);
+    while let Some(e) = source {
+        let msg = e.to_string();
+        if let Some(at) = msg.find(INTEGRITY_ERR_PREFIX) {
+            return Some(msg[at..].to_string());
+        }
+        source = e.source();
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tlv(tag: u8, content: &[u8]) -> Vec<u8> {
+        let mut out = vec![tag];
+        match content.len() {
+            n if n < 0x80 => out.push(n as u8),
+            n if n < 0x100 => out.extend_from_slice(&[0x81, n as u8]),
+            n => out.extend_from_slice(&[0x82, (n >> 8) as u8, n as u8]),
+        }
+        out.extend_from_slice(content);
+        out
+    }
+
+    fn spki() -> Vec<u8> {
+        let alg = tlv(
+            0x30,
+            &tlv(0x06, &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01]),
+        );
+        let key = tlv(0x03, &[0u8; 66]);
+        tlv(0x30, &[alg, key].concat())
+    }
+
+    /// Certificate skeleton: the fields before the SPKI only need to be TLVs.
+    fn certificate(version: bool, spki: &[u8]) -> Vec<u8> {
+        let mut tbs: Vec<u8> = Vec::new();
+        if version {
+            tbs.extend(tlv(0xa0, &tlv(0x02, &[2])));
+        }
+        tbs.extend(tlv(0x02, &[0x01, 0x23]));
+        tbs.extend(tlv(0x30, &tlv(0x06, &[0x2a, 0x86, 0x48])));
+        tbs.extend(tlv(0x30, &[0x55; 200]));
+        tbs.extend(tlv(
+            0x30,
+            &[tlv(0x17, b"250101000000Z"), tlv(0x17, b"350101000000Z")].concat(),
+        ));
+        tbs.extend(tlv(0x30, &[0x55; 40]));
+        tbs.extend_from_slice(spki);
+        let sig_alg = tlv(0x30, &tlv(0x06, &[0x2a, 0x86, 0x48]));
+        let sig = tlv(0x03, &[0u8; 72]);
+        tlv(0x30, &[tlv(0x30, &tbs), sig_alg, sig].concat())
+    }
+
+    #[test]
+    fn der_lengths() {
+        let long = [&[0x04, 0x81, 0x80][..], &[0xab; 0x80]].concat();
+
+        // (tag, TLV length, remainder length)
+        type Split = Option<(u8, usize, usize)>;
+        let cases: &[(&str, &[u8], Split)] = &[
+            ("empty", &[], None),
+            ("tag only", &[0x30], None),
+            ("short form", &[0x02, 0x01, 0x05, 0xff], Some((0x02, 3, 1))),
+            ("zero length", &[0x05, 0x00], Some((0x05, 2, 0))),
+            ("short form past end", &[0x04, 0x05, 0x00], None),
+            ("long form", &long, Some((0x04, 0x83, 0))),
+            ("indefinite length", &[0x30, 0x80, 0x00, 0x00], None),
+            ("five length bytes", &[0x04, 0x85, 0, 0, 0, 0, 1, 0], None),
+            ("length bytes cut off", &[0x04, 0x82, 0x01], None),
+            (
+                "4 GB length",
+                &[0x04, 0x84, 0xff, 0xff, 0xff, 0xff, 0x00],
+                None,
+            ),
+            ("long form past end", &[0x04, 0x82, 0x01, 0x00, 0x00], None),
+        ];
+
+        for (name, input, expected) in cases {
+            let got = der_next(input).map(|(tag, tlv, rest)| (tag, tlv.len(), rest.len()));
+            assert_eq!(got, *expected, "{name}");
+        }
+        assert_eq!(der_content(&long).len(), 0x80);
+        assert!(der_content(&[0x04, 0x84]).is_empty());
+    }
+
+    #[test]
+    fn spki_is_found_in_certificates() {
+        let key = spki();
+        for version in [true, false] {
+            let cert = certificate(version, &key);
+            assert_eq!(spki_of_certificate(&cert), Some(&key[..]));
+
+            // Any cut leaves the outer length pointing past the end
+            for len in 0..cert.len() {
+                assert_eq!(spki_of_certificate(&cert[..len]), None);
+            }
+        }
+    }
+
+    #[test]
+    fn malformed_certificates_are_rejected() {
+        let key = spki();
+        let not_a_sequence = [&[0x31][..], &key[1..]].concat();
+        assert_eq!(
+            spki_of_certificate(&certificate(true, &not_a_sequence)),
+            None
+        );
+
+        // Inner lengths claiming more than the outer TLV holds
+        let mut cert = certificate(true, &key);
+        assert_eq!(&cert[4..6], &[0x30, 0x82]);
+        cert[6] = 0xff;
+        assert_eq!(spki_of_certificate(&cert), None);
+
+        let mut cert = certificate(true, &key);
+        let spki_at = cert.windows(key.len()).position(|w| w == key).unwrap();
+        cert[spki_at + 1] = 0x84;
+        assert_eq!(spki_of_certificate(&cert), None);
+
+        // Fewer fields than a certificate has
+        let short = tlv(0x30, &tlv(0x30, &[tlv(0x02, &[1]), key.clone()].concat()));
+        assert_eq!(spki_of_certificate(&short), None);
+    }
+
+    #[test]
+    fn pins_must_be_sha256() {
+        let digest = [7u8; 32];
+        let pin = format!("sha256/{}", BASE64.encode(digest));
+        assert_eq!(decode_spki_pin(&pin), Ok(digest));
+        assert_eq!(decode_spki_pin(&BASE64.encode(digest)), Ok(digest));
+        assert!(decode_spki_pin(&format!("sha256/{}", BASE64.encode([7u8; 20]))).is_err());
+        assert!(decode_spki_pin("sha256/not base64!").is_err());
+    }
+}
@@ -0,0 +1,347 @@
+// Which process wrote a file, for realtime alerts (Linux only).
+//
+// With fanotify permitted, the kernel reports the PID that closed each file
+// written in the watched directories, and we remember recent writers per path
+// even after they exit. Marks go on each directory rather than the mount, so
+// writes elsewhere on the disk cost nothing; they are dropped while realtime
+// protection is off. Without fanotify (or beyond the mark budget) we fall back
+// to walking /proc for processes that still hold the file open: that catches
+// slow writers working through a folder, not short-lived ones.
+
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+#[cfg(target_os = "linux")]
+use std::{
+    collections::VecDeque,
+    fs,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+#[cfg(target_os = "linux")]
+use walkdir::WalkDir;
+
+#[cfg(target_os = "linux")]
+use crate::{fanotify::Fanotify, REALTIME_ENABLED};
+
+#[cfg(target_os = "linux")]
+const RECENT_WINDOW: Duration = Duration::from_secs(30);
+#[cfg(target_os = "linux")]
+const MAX_RECENT_WRITES: usize = 4096;
+// Ransomware may be handed megabytes of arguments; the UI needs the gist
+#[cfg(target_os = "linux")]
+const MAX_CMDLINE_CHARS: usize = 512;
+// One event per finished write; FAN_MODIFY would fire on every write() call
+#[cfg(target_os = "linux")]
+const TRACK_MASK: u64 = libc::FAN_CLOSE_WRITE;
+// fanotify allows 8192 marks per group by default; leave room for new folders
+#[cfg(target_os = "linux")]
+const MAX_TRACKED_DIRS: usize = 4096;
+
+#[derive(Serialize, Clone)]
+pub(crate) struct ProcessInfo {
+    pub(crate) pid: u32,
+    pub(crate) exe: Option<String>,
+    pub(crate) cmdline: Option<String>,
+}
+
+impl ProcessInfo {
+    pub(crate) fn describe(&self) -> String {
+        let name = self
+            .exe
+            .as_deref()
+            .or(self.cmdline.as_deref())
+            .unwrap_or("unknown process");
+        format!("{name} (PID {})", self.pid)
+    }
+}
+
+/// First attributable process among `paths`, checking at most a few of them.
+pub(crate) fn attribute_any<'a>(
+    paths: impl IntoIterator<Item = &'a PathBuf>,
+) -> Option<ProcessInfo> {
+    paths.into_iter().take(5).find_map(|p| attribute(p))
+}
+
+// ---- Linux ----
+
+#[cfg(target_os = "linux")]
+struct RecentWrite {
+    path: PathBuf,
+    process: ProcessInfo,
+    at: Instant,
+}
+
+/// The fanotify group plus the directories it should be marking.
+#[cfg(target_os = "linux")]
+struct Tracker {
+    fan: Arc<Fanotify>,
+    roots: Vec<PathBuf>,
+    marked: usize,
+}
+
+#[cfg(target_os = "linux")]
+static FANOTIFY_ACTIVE: AtomicBool = AtomicBool::new(false);
+#[cfg(target_os = "linux")]
+static TRACKER: Mutex<Option<Tracker>> = Mutex::new(None);
+#[cfg(target_os = "linux")]
+static RECENT_WRITES: Mutex<VecDeque<RecentWrite>> = Mutex::new(VecDeque::new());
+
+#[cfg(target_os = "linux")]
+fn info(pid: u32) -> Option<ProcessInfo> {
+    let dir = PathBuf::from(format!("/proc/{pid}"));
+    let exe = fs::read_link(dir.join("exe"))
+        .ok()
+        .map(|p| p.to_string_lossy().to_string());
+    let cmdline = fs::read(dir.join("cmdline"))
+        .ok()
+        .filter(|b| !b.is_empty())
+        .map(|b| {
+            let args: Vec<_> = b
+                .split(|&c| c == 0)
+                .filter(|a| !a.is_empty())
+                .map(String::from_utf8_lossy)
+                .collect();
+            args.join(" ").chars().take(MAX_CMDLINE_CHARS).collect()
+        });
+
+    // Both gone: the process exited before we looked
+    if exe.is_none() && cmdline.is_none() {
+        return None;
+    }
+    Some(ProcessInfo { pid, exe, cmdline })
+}
+
+#[cfg(target_os = "linux")]
+fn remember(path: PathBuf, pid: u32) {
+    let now = Instant::now();
+    let known = {
+        let Ok(mut recent) = RECENT_WRITES.lock() else {
+            return;
+        };
+        // A process rewriting one file fires many events; keep one entry
+        if let Some(last) = recent.back_mut() {
+            if last.path == path && last.process.pid == pid {
+                last.at = now;
+                return;
+            }
+        }
+        recent
+            .iter()
+            .rev()
+            .take(64)
+            .find(|w| w.process.pid == pid)
+            .map(|w| w.process.clone())
+    };
+
+    // Resolve outside the lock; /proc reads can be slow
+    let Some(process) = known.or_else(|| info(pid)) else {
+        return;
+    };
+
+    if let Ok(mut recent) = RECENT_WRITES.lock() {
+        recent.push_back(RecentWrite {
+            path,
+            process,
+            at: now,
+        });
+        while recent.len() > MAX_RECENT_WRITES {
+            recent.pop_front();
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn recent_writer(path: &Path) -> Option<ProcessInfo> {
+    let recent = RECENT_WRITES.lock().ok()?;
+    recent
+        .iter()
+        .rev()
+        .take_while(|w| w.at.elapsed() < RECENT_WINDOW)
+        .find(|w| w.path == path)
+        .map(|w| w.process.clone())
+}
+
+/// A process that currently has `path` open.
+#[cfg(target_os = "linux")]
+fn holder(path: &Path) -> Option<ProcessInfo> {
+    let me = std::process::id();
+    for entry in fs::read_dir("/proc").ok()?.flatten() {
+        let Some(pid) = entry
+            .file_name()
+            .to_str()
+            .and_then(|s| s.parse::<u32>().ok())
+        else {
+            continue;
+        };
+        if pid == me {
+            continue;
+        }
+
+        // Other users' processes are unreadable without privileges
+        let Ok(fds) = fs::read_dir(entry.path().join("fd")) else {
+            continue;
+        };
+        if fds
+            .flatten()
+            .any(|fd| fs::read_link(fd.path()).is_ok_and(|target| target == path))
+        {
+            return info(pid);
+        }
+    }
+    None
+}
+
+#[cfg(target_os = "linux")]
+impl Tracker {
+    /// Marks `root` and the directories below it, within the mark budget.
+    fn mark_tree(&mut self, root: &Path) {
+        let dirs = WalkDir::new(root)
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|e| e.file_type().is_dir());
+        for dir in dirs {
+            if self.marked >= MAX_TRACKED_DIRS {
+                eprintln!(
+                    "[PROCESS] folder limit reached at {:?}; using /proc below it",
+                    dir.path()
+                );
+                return;
+            }
+            match self.fan.mark_dir(dir.path(), TRACK_MASK) {
+                Ok(()) => self.marked += 1,
+                Err(e) => eprintln!("[PROCESS] fanotify mark failed for {:?}: {e}", dir.path()),
+            }
+        }
+    }
+
+    fn mark_roots(&mut self) {
+        for root in self.roots.clone() {
+            self.mark_tree(&root);
+        }
+    }
+}
+
+/// Starts recording writers under `dirs` via fanotify, if we are allowed to.
+#[cfg(target_os = "linux")]
+pub(crate) fn start_tracking(dirs: &[PathBuf]) {
+    let fan = match Fanotify::new(libc::FAN_CLASS_NOTIF) {
+        Ok(f) => Arc::new(f),
+        Err(e) => {
+            println!("[PROCESS] fanotify unavailable ({e}); attributing via /proc");
+            return;
+        }
+    };
+
+    let mut tracker = Tracker {
+        fan: Arc::clone(&fan),
+        roots: dirs.to_vec(),
+        marked: 0,
+    };
+    if REALTIME_ENABLED.load(Ordering::SeqCst) {
+        tracker.mark_roots();
+        FANOTIFY_ACTIVE.store(tracker.marked > 0, Ordering::SeqCst);
+    }
+    println!(
+        "[PROCESS] attributing file writes via fanotify ({} folder(s))",
+        tracker.marked
+    );
+    if let Ok(mut slot) = TRACKER.lock() {
+        *slot = Some(tracker);
+    }
+
+    let me = std::process::id();
+    thread::spawn(move || loop {
+        let events = match fan.read_events() {
+            Ok(events) => events,
+            Err(e) => {
+                eprintln!("[PROCESS] fanotify read failed: {e}; attributing via /proc");
+                FANOTIFY_ACTIVE.store(false, Ordering::SeqCst);
+                if let Ok(mut slot) = TRACKER.lock() {
+                    *slot = None;
+                }
+                return;
+            }
+        };
+
+        for event in events {
+            if event.mask & TRACK_MASK == 0 {
+                continue;
+            }
+            let Ok(pid) = u32::try_from(event.pid) else {
+                continue;
+            };
+            if pid == me {
+                continue;
+            }
+            if let Some(path) = event.path() {
+                remember(path, pid);
+            }
+        }
+    });
+}
+
+/// Tracks a folder that appeared under the watched directories after start.
+#[cfg(target_os = "linux")]
+pub(crate) fn track_dir(dir: &Path) {
+    if !FANOTIFY_ACTIVE.load(Ordering::SeqCst) {
+        return;
+    }
+    if let Ok(mut slot) = TRACKER.lock() {
+        if let Some(tracker) = slot.as_mut() {
+            tracker.mark_tree(dir);
+        }
+    }
+}
+
+/// Follows the realtime switch: no marks (and no kernel events) while it is off.
+#[cfg(target_os = "linux")]
+pub(crate) fn set_tracking(enabled: bool) {
+    let Ok(mut slot) = TRACKER.lock() else {
+        return;
+    };
+    let Some(tracker) = slot.as_mut() else {
+        return;
+    };
+
+    FANOTIFY_ACTIVE.store(false, Ordering::SeqCst);
+    if let Err(e) = tracker.fan.clear_dir_marks() {
+        eprintln!("[PROCESS] fanotify flush failed: {e}");
+    }
+    tracker.marked = 0;
+    if enabled {
+        tracker.mark_roots();
+        FANOTIFY_ACTIVE.store(tracker.marked > 0, Ordering::SeqCst);
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn start_tracking(_dirs: &[PathBuf]) {}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn track_dir(_dir: &Path) {}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn set_tracking(_enabled: bool) {}
+
+/// Best guess at the process that last wrote `path`.
+#[cfg(target_os = "linux")]
+pub(crate) fn attribute(path: &Path) -> Option<ProcessInfo> {
+    if FANOTIFY_ACTIVE.load(Ordering::SeqCst) {
+        if let Some(p) = recent_writer(path) {
+            return Some(p);
+        }
+    }
+    holder(path)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn attribute(_path: &Path) -> Option<ProcessInfo> {
+    None
+}
@@ -0,0 +1,548 @@
+// Local rule engine: declarative file name / extension / path / size / entropy
+// heuristics.
+//
+// Built-in rules ship in `rules/local_rules.json`. Users (or admins) can add a
+// `local_rules.json` of the same shape to the data dir; a user rule with the
+// same id replaces the built-in one, `"enabled": false` switches it off.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use globset::{Glob, GlobBuilder, GlobSet, GlobSetBuilder};
+use serde::Deserialize;
+
+use crate::app_data_dir;
+
+const BUILTIN_RULES_JSON: &str = include_str!("../rules/local_rules.json");
+
+// Extensions that actually run when double-clicked.
+const EXECUTABLE_EXTENSIONS: &[&str] = &[
+    "exe", "scr", "com", "pif", "bat", "cmd", "vbs", "vbe", "js", "jse", "wsf", "hta", "ps1",
+    "msi", "jar", "lnk", "cpl", "app", "command", "sh",
+];
+
+// Extensions users trust at a glance; seen as the "fake" part of a double extension.
+const DECOY_EXTENSIONS: &[&str] = &[
+    "pdf", "doc", "docx", "xls", "xlsx", "ppt", "pptx", "txt", "rtf", "csv", "jpg", "jpeg", "png",
+    "gif", "bmp", "mp3", "mp4", "avi", "mov", "wav", "zip", "rar", "7z", "html", "htm",
+];
+
+// Rule set cache, refreshed when the user rules file changes on disk.
+static CACHE: Mutex<Option<(Option<SystemTime>, Arc<RuleSet>)>> = Mutex::new(None);
+
+// ---- Rule file format ----
+
+#[derive(Deserialize)]
+struct RuleFile {
+    #[serde(default)]
+    rules: Vec<RuleSpec>,
+}
+
+#[derive(Deserialize, Clone)]
+struct RuleSpec {
+    id: String,
+    name: String,
+    #[serde(default = "default_severity")]
+    severity: String,
+    #[serde(default = "default_enabled")]
+    enabled: bool,
+    #[serde(default)]
+    name_globs: Vec<String>,
+    #[serde(default)]
+    path_globs: Vec<String>,
+    #[serde(default)]
+    extensions: Vec<String>,
+    #[serde(default)]
+    double_extension: bool,
+    #[serde(default)]
+    bidi_override: bool,
+    #[serde(default)]
+    min_size: Option<u64>,
+    #[serde(default)]
+    max_size: Option<u64>,
+    // Whole-file Shannon entropy, bits per byte
+    #[serde(default)]
+    min_entropy: Option<f64>,
+    #[serde(default)]
+    max_entropy: Option<f64>,
+}
+
+fn default_severity() -> String {
+    "medium".to_string()
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+// ---- Compiled rules ----
+
+struct Rule {
+    id: String,
+    name: String,
+    severity: String,
+    name_globs: Option<GlobSet>,
+    path_globs: Option<GlobSet>,
+    extensions: Vec<String>,
+    double_extension: bool,
+    bidi_override: bool,
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+    min_entropy: Option<f64>,
+    max_entropy: Option<f64>,
+}
+
+pub(crate) struct RuleSet {
+    rules: Vec<Rule>,
+}
+
+pub(crate) struct RuleMatch {
+    pub(crate) rule_id: String,
+    pub(crate) name: String,
+    pub(crate) severity: String,
+}
+
+fn build_globset(patterns: &[String], rule_id: &str) -> Result<Option<GlobSet>, String> {
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+
+    let mut builder = GlobSetBuilder::new();
+    for p in patterns {
+        let glob: Glob = GlobBuilder::new(p)
+            .case_insensitive(true)
+            .literal_separator(false)
+            .build()
+            .map_err(|e| format!("Rule {rule_id}: invalid glob {p:?}: {e}"))?;
+        builder.add(glob);
+    }
+
+    builder
+        .build()
+        .map(Some)
+        .map_err(|e| format!("Rule {rule_id}: {e}"))
+}
+
+fn compile(spec: &RuleSpec) -> Result<Rule, String> {
+    let has_condition = !spec.name_globs.is_empty()
+        || !spec.path_globs.is_empty()
+        || !spec.extensions.is_empty()
+        || spec.double_extension
+        || spec.bidi_override
+        || spec.min_size.is_some()
+        || spec.max_size.is_some()
+        || spec.min_entropy.is_some()
+        || spec.max_entropy.is_some();
+    if !has_condition {
+        return Err(format!("Rule {} has no conditions", spec.id));
+    }
+
+    Ok(Rule {
+        id: spec.id.clone(),
+        name: spec.name.clone(),
+        severity: spec.severity.to_lowercase(),
+        name_globs: build_globset(&spec.name_globs, &spec.id)?,
+        path_globs: build_globset(&spec.path_globs, &spec.id)?,
+        extensions: spec
+            .extensions
+            .iter()
+            .map(|e| e.trim_start_matches('.').to_lowercase())
+            .collect(),
+        double_extension: spec.double_extension,
+        bidi_override: spec.bidi_override,
+        min_size: spec.min_size,
+        max_size: spec.max_size,
+        min_entropy: spec.min_entropy,
+        max_entropy: spec.max_entropy,
+    })
+}
+
+/// `invoice.pdf.exe`, `photo.jpg   .scr` etc.
+fn has_double_extension(file_name: &str) -> bool {
+    let lower = file_name.to_lowercase();
+    let parts: Vec<&str> = lower.split('.').collect();
+    if parts.len() < 3 {
+        return false;
+    }
+
+    let last = parts[parts.len() - 1].trim();
+    let decoy = parts[parts.len() - 2].trim();
+    EXECUTABLE_EXTENSIONS.contains(&last) && DECOY_EXTENSIONS.contains(&decoy)
+}
+
+/// Right-to-left override and friends, used to make `exe.pdf` render as `fdp.exe`.
+fn has_bidi_override(file_name: &str) -> bool {
+    file_name
+        .chars()
+        .any(|c| matches!(c, '\u{202A}'..='\u{202E}' | '\u{2066}'..='\u{2069}'))
+}
+
+impl Rule {
+    fn matches(
+        &self,
+        path: &Path,
+        file_name: &str,
+        size: Option<u64>,
+        entropy: Option<f64>,
+    ) -> bool {
+        if let Some(set) = &self.name_globs {
+            if !set.is_match(file_name) {
+                return false;
+            }
+        }
+
+        if let Some(set) = &self.path_globs {
+            if !set.is_match(path) {
+                return false;
+            }
+        }
+
+        if !self.extensions.is_empty() {
+            let ext = path
+                .extension()
+                .and_then(|s| s.to_str())
+                .map(|s| s.trim().to_lowercase());
+            match ext {
+                Some(ext) if self.extensions.contains(&ext) => {}
+                _ => return false,
+            }
+        }
+
+        if self.double_extension && !has_double_extension(file_name) {
+            return false;
+        }
+
+        if self.bidi_override && !has_bidi_override(file_name) {
+            return false;
+        }
+
+        if self.min_size.is_some() || self.max_size.is_some() {
+            let Some(size) = size else {
+                return false;
+            };
+            if self.min_size.is_some_and(|min| size < min) {
+                return false;
+            }
+            if self.max_size.is_some_and(|max| size > max) {
+                return false;
+            }
+        }
+
+        if self.min_entropy.is_some() || self.max_entropy.is_some() {
+            let Some(entropy) = entropy else {
+                return false;
+            };
+            if self.min_entropy.is_some_and(|min| entropy < min) {
+                return false;
+            }
+            if self.max_entropy.is_some_and(|max| entropy > max) {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+impl RuleSet {
+    pub(crate) fn evaluate(
+        &self,
+        path: &Path,
+        size: Option<u64>,
+        entropy: Option<f64>,
+    ) -> Vec<RuleMatch> {
+        let file_name = match path.file_name() {
+            Some(n) => n.to_string_lossy().to_string(),
+            None => return vec![],
+        };
+
+        self.rules
+            .iter()
+            .filter(|r| r.matches(path, &file_name, size, entropy))
+            .map(|r| RuleMatch {
+                rule_id: r.id.clone(),
+                name: r.name.clone(),
+                severity: r.severity.clone(),
+            })
+            .collect()
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.rules.len()
+    }
+}
+
+// ---- Loading ----
+
+fn user_rules_path() -> PathBuf {
+    app_data_dir().join("local_rules.json")
+}
+
+fn parse_rule_file(json: &[u8], label: &str) -> Result<Vec<RuleSpec>, String> {
+    serde_json::from_slice::<RuleFile>(json)
+        .map(|f| f.rules)
+        .map_err(|e| format!("Failed to parse {label}: {e}"))
+}
+
+/// Builds the rule set from built-ins plus the user file. Errors only for a
+/// broken user file; built-ins are known good.
+fn load_rule_set() -> Result<RuleSet, String> {
+    let mut specs = parse_rule_file(BUILTIN_RULES_JSON.as_bytes(), "built-in rules")?;
+
+    if let Ok(bytes) = fs::read(user_rules_path()) {
+        for user in parse_rule_file(&bytes, "local_rules.json")? {
+            specs.retain(|s| s.id != user.id);
+            specs.push(user);
+        }
+    }
+
+    let rules = specs
+        .iter()
+        .filter(|s| s.enabled)
+        .map(compile)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(RuleSet { rules })
+}
+
+fn user_rules_mtime() -> Option<SystemTime> {
+    fs::metadata(user_rules_path())
+        .and_then(|m| m.modified())
+        .ok()
+}
+
+/// Current rule set. A broken user file logs an error and keeps the last good
+/// set (or the built-ins) so detection never silently turns off.
+pub(crate) fn current() -> Arc<RuleSet> {
+    let mtime = user_rules_mtime();
+    let mut cache = CACHE.lock().unwrap_or_else(|e| e.into_inner());
+
+    if let Some((cached_mtime, set)) = cache.as_ref() {
+        if *cached_mtime == mtime {
+            return set.clone();
+        }
+    }
+
+    let set = match load_rule_set() {
+        Ok(set) => Arc::new(set),
+        Err(e) => {
+            eprintln!("[RULES] {e}");
+            match cache.as_ref() {
+                Some((_, previous)) => previous.clone(),
+                None => {
+                    let specs = parse_rule_file(BUILTIN_RULES_JSON.as_bytes(), "built-in rules")
+                        .unwrap_or_default();
+                    Arc::new(RuleSet {
+                        rules: specs.iter().filter_map(|s| compile(s).ok()).collect(),
+                    })
+                }
+            }
+        }
+    };
+
+    *cache = Some((mtime, set.clone()));
+    set
+}
+
+// ---- Commands ----
+
+/// Re-reads the user rules file, returning the number of active rules or the
+/// parse error so the UI can show it.
+#[tauri::command]
+pub(crate) fn reload_local_rules() -> Result<usize, String> {
+    let set = load_rule_set()?;
+    let count = set.len();
+
+    let mut cache = CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    *cache = Some((user_rules_mtime(), Arc::new(set)));
+
+    println!("[RULES] reloaded: {count} active rule(s)");
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule_set(json: &str) -> RuleSet {
+        let specs = parse_rule_file(json.as_bytes(), "test rules").unwrap();
+        RuleSet {
+            rules: specs.iter().map(|s| compile(s).unwrap()).collect(),
+        }
+    }
+
+    fn ids(set: &RuleSet, path: &str, size: Option<u64>, entropy: Option<f64>) -> Vec<String> {
+        set.evaluate(Path::new(path), size, entropy)
+            .into_iter()
+            .map(|m| m.rule_id)
+            .collect()
+    }
+
+    #[test]
+    fn builtin_rules_compile_and_fire() {
+        let set = rule_set(BUILTIN_RULES_JSON);
+        assert_eq!(set.len(), 5);
+
+        let cases: &[(&str, Option<u64>, &[&str])] = &[
+            ("/tmp/stellar-test.bin", None, &["STL-TEST-0001"]),
+            ("/tmp/STELLAR_TEST.BIN", None, &["STL-TEST-0001"]),
+            ("/home/u/invoice.pdf.exe", None, &["STL-LR-0001"]),
+            ("/home/u/Downloads/cute.scr", None, &["STL-LR-0003"]),
+            ("/home/u/Desktop/cute.scr", None, &[]),
+            (
+                "/home/u/Downloads/photo.jpg.scr",
+                None,
+                &["STL-LR-0001", "STL-LR-0003"],
+            ),
+            ("/home/u/invoice\u{202E}fdp.exe", None, &["STL-LR-0002"]),
+            ("/home/u/update.hta", Some(4096), &["STL-LR-0004"]),
+            ("/home/u/update.hta", Some(262_145), &[]),
+            ("/home/u/update.hta", None, &[]),
+            ("/home/u/report.pdf", Some(4096), &[]),
+        ];
+        for &(path, size, expected) in cases {
+            assert_eq!(ids(&set, path, size, None), expected, "{path}");
+        }
+    }
+
+    #[test]
+    fn globs_are_case_insensitive_and_cross_separators() {
+        let set = rule_set(
+            r#"{ "rules": [
+                { "id": "N", "name": "n", "name_globs": ["*.invoice.*"] },
+                { "id": "P", "name": "p", "path_globs": ["**/AppData/*/Temp/*.exe"] }
+            ] }"#,
+        );
+        let cases: &[(&str, &[&str])] = &[
+            ("/x/May.Invoice.zip", &["N"]),
+            ("/x/invoice.zip", &[]),
+            ("/home/u/AppData/Local/Temp/a.exe", &["P"]),
+            ("/home/u/appdata/local/temp/A.EXE", &["P"]),
+            // `*` is not limited to one path segment
+            ("/home/u/AppData/Local/x/y/Temp/a.exe", &["P"]),
+            ("/home/u/AppData/Local/Temp/a.dll", &[]),
+        ];
+        for &(path, expected) in cases {
+            assert_eq!(ids(&set, path, None, None), expected, "{path}");
+        }
+    }
+
+    #[test]
+    fn extensions_ignore_dots_and_case() {
+        let set = rule_set(
+            r#"{ "rules": [ { "id": "E", "name": "e", "extensions": [".LNK", "url"] } ] }"#,
+        );
+        for (path, hit) in [
+            ("/x/a.lnk", true),
+            ("/x/a.LnK", true),
+            ("/x/a.url", true),
+            ("/x/a.lnk.txt", false),
+            ("/x/lnk", false),
+        ] {
+            assert_eq!(!ids(&set, path, None, None).is_empty(), hit, "{path}");
+        }
+    }
+
+    #[test]
+    fn double_extension_and_bidi_tables() {
+        let doubles: &[(&str, bool)] = &[
+            ("invoice.pdf.exe", true),
+            ("photo.JPG.Scr", true),
+            ("photo.jpg   .scr", true),
+            ("setup.exe", false),
+            ("archive.tar.gz", false),
+            ("notes.txt.bak", false),
+            ("report.final.exe", false),
+        ];
+        for &(name, expected) in doubles {
+            assert_eq!(has_double_extension(name), expected, "{name}");
+        }
+
+        assert!(has_bidi_override("invoice\u{202E}fdp.exe"));
+        assert!(has_bidi_override("a\u{2066}b"));
+        assert!(!has_bidi_override("résumé.pdf"));
+    }
+
+    #[test]
+    fn size_and_entropy_bounds_are_inclusive_and_need_a_value() {
+        let set = rule_set(
+            r#"{ "rules": [
+                { "id": "S", "name": "s", "min_size": 100, "max_size": 200 },
+                { "id": "H", "name": "h", "min_entropy": 7.2, "max_entropy": 7.9 }
+            ] }"#,
+        );
+        let sizes: &[(Option<u64>, bool)] = &[
+            (Some(99), false),
+            (Some(100), true),
+            (Some(200), true),
+            (Some(201), false),
+            (None, false),
+        ];
+        for &(size, hit) in sizes {
+            assert_eq!(ids(&set, "/x/a", size, None) == ["S"], hit, "{size:?}");
+        }
+        let entropies: &[(Option<f64>, bool)] = &[
+            (Some(7.19), false),
+            (Some(7.2), true),
+            (Some(7.9), true),
+            (Some(7.91), false),
+            (None, false),
+        ];
+        for &(entropy, hit) in entropies {
+            assert_eq!(
+                ids(&set, "/x/a", None, entropy) == ["H"],
+                hit,
+                "{entropy:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn every_condition_of_a_rule_must_hold() {
+        let set = rule_set(
+            r#"{ "rules": [ {
+                "id": "A", "name": "a", "extensions": ["exe"],
+                "path_globs": ["**/Downloads/**"], "max_size": 1000, "min_entropy": 7.0
+            } ] }"#,
+        );
+        let cases: &[(&str, u64, f64, bool)] = &[
+            ("/u/Downloads/a.exe", 500, 7.5, true),
+            ("/u/Desktop/a.exe", 500, 7.5, false),
+            ("/u/Downloads/a.msi", 500, 7.5, false),
+            ("/u/Downloads/a.exe", 5000, 7.5, false),
+            ("/u/Downloads/a.exe", 500, 6.0, false),
+        ];
+        for &(path, size, entropy, hit) in cases {
+            let got = !ids(&set, path, Some(size), Some(entropy)).is_empty();
+            assert_eq!(got, hit, "{path} {size} {entropy}");
+        }
+    }
+
+    #[test]
+    fn unusable_rules_are_rejected() {
+        let specs = parse_rule_file(
+            br#"{ "rules": [
+                { "id": "EMPTY", "name": "matches everything" },
+                { "id": "GLOB", "name": "bad glob", "name_globs": ["a[b"] }
+            ] }"#,
+            "test rules",
+        )
+        .unwrap();
+        assert!(matches!(compile(&specs[0]), Err(e) if e.contains("no conditions")));
+        assert!(matches!(compile(&specs[1]), Err(e) if e.contains("invalid glob")));
+
+        assert!(parse_rule_file(b"{ \"rules\": [ { \"id\": 1 } ] }", "x").is_err());
+        // Severity defaults and is normalised; a disabled spec still parses
+        let specs = parse_rule_file(
+            br#"{ "rules": [ { "id": "D", "name": "d", "severity": "HIGH", "enabled": false, "extensions": ["x"] } ] }"#,
+            "x",
+        )
+        .unwrap();
+        assert!(!specs[0].enabled);
+        assert_eq!(compile(&specs[0]).unwrap().severity, "high");
+    }
+}
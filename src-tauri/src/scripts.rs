@@ -0,0 +1,368 @@
+// Script heuristics for droppers: shell, Python, PowerShell and JavaScript.
+//
+// Hash lookups rarely know a dropper script (every sample is regenerated), so
+// we score what the text does instead: download-and-run chains, encoded or
+// concatenated payloads, reverse shells, AMSI bypasses. Matching is done on
+// lowercased ASCII with plain substring checks, which is enough for the usual
+// tricks and cheap for the realtime watcher.
+
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::{indicator_score, Indicator};
+
+/// Score from which the scan reports a suspicious verdict.
+pub(crate) const DETECTION_SCORE: u32 = 50;
+
+// Long obfuscated payloads sit at the start; the tail of a huge script adds little
+const MAX_SCRIPT_BYTES: usize = 4 * 1024 * 1024;
+const BASE64_BLOB_CHARS: usize = 200;
+const LONG_LINE_CHARS: usize = 5000;
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Language {
+    Shell,
+    Python,
+    PowerShell,
+    JavaScript,
+}
+
+impl Language {
+    pub(crate) fn label(self) -> &'static str {
+        match self {
+            Language::Shell => "Shell",
+            Language::Python => "Python",
+            Language::PowerShell => "PowerShell",
+            Language::JavaScript => "JavaScript",
+        }
+    }
+}
+
+#[derive(Serialize, Clone)]
+pub(crate) struct ScriptReport {
+    pub(crate) language: Language,
+    pub(crate) score: u32,
+    pub(crate) indicators: Vec<Indicator>,
+}
+
+pub(crate) fn language_from_extension(path: &Path) -> Option<Language> {
+    let ext = path.extension()?.to_str()?.to_lowercase();
+    match ext.as_str() {
+        "sh" | "bash" | "zsh" | "ksh" | "command" => Some(Language::Shell),
+        "py" | "pyw" => Some(Language::Python),
+        "ps1" | "psm1" | "psd1" => Some(Language::PowerShell),
+        "js" | "jse" | "mjs" | "cjs" => Some(Language::JavaScript),
+        _ => None,
+    }
+}
+
+/// `#!/usr/bin/env python3` etc. Wins over the extension.
+fn language_from_shebang(data: &[u8]) -> Option<Language> {
+    let first = data.strip_prefix(b"#!")?;
+    let end = memchr::memchr(b'\n', first).unwrap_or(first.len()).min(256);
+    let line = String::from_utf8_lossy(&first[..end]).to_lowercase();
+    let interpreter = line
+        .split_whitespace()
+        .find(|w| !w.ends_with("/env") && *w != "env" && !w.starts_with('-'))?;
+    let name = interpreter.rsplit('/').next()?;
+
+    if name.starts_with("python") {
+        Some(Language::Python)
+    } else if name.starts_with("node") {
+        Some(Language::JavaScript)
+    } else if name.starts_with("pwsh") || name.starts_with("powershell") {
+        Some(Language::PowerShell)
+    } else if matches!(name, "sh" | "bash" | "zsh" | "dash" | "ksh" | "ash") {
+        Some(Language::Shell)
+    } else {
+        None
+    }
+}
+
+pub(crate) fn language(path: &Path, data: &[u8]) -> Option<Language> {
+    language_from_shebang(data).or_else(|| language_from_extension(path))
+}
+
+// ---- Shared checks ----
+
+fn longest_base64_run(text: &[u8]) -> usize {
+    let mut best = 0;
+    let mut run = 0;
+    let (mut upper, mut lower, mut digit) = (false, false, false);
+
+    for b in text {
+        if b.is_ascii_alphanumeric() || matches!(b, b'+' | b'/' | b'=') {
+            run += 1;
+            upper |= b.is_ascii_uppercase();
+            lower |= b.is_ascii_lowercase();
+            digit |= b.is_ascii_digit();
+        } else {
+            // Identifiers and "=====" rulers aren't base64: require mixed classes
+            if upper && lower && digit {
+                best = best.max(run);
+            }
+            run = 0;
+            (upper, lower, digit) = (false, false, false);
+        }
+    }
+    if upper && lower && digit {
+        best = best.max(run);
+    }
+    best
+}
+
+/// A `|` followed by one of `targets` as the next command (`curl x | sudo bash`).
+fn pipes_into(line: &str, targets: &[&str]) -> bool {
+    line.match_indices('|').any(|(i, _)| {
+        let rest = line[i + 1..].trim_start();
+        let rest = rest.strip_prefix("sudo ").unwrap_or(rest).trim_start();
+        targets.iter().any(|t| {
+            rest.strip_prefix(t)
+                .is_some_and(|after| !after.starts_with(|c: char| c.is_ascii_alphanumeric()))
+        })
+    })
+}
+
+/// `word` not embedded in a longer identifier (`iex` but not `ziex`).
+fn has_word(text: &str, word: &str) -> bool {
+    let is_ident = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '-';
+    text.match_indices(word).any(|(i, _)| {
+        !text[..i].ends_with(is_ident) && !text[i + word.len()..].starts_with(is_ident)
+    })
+}
+
+fn count(text: &str, needle: &str) -> usize {
+    text.matches(needle).count()
+}
+
+// ---- Per-language checks ----
+
+fn shell_indicators(text: &str, add: &mut dyn FnMut(&'static str, u32, String)) {
+    let shells = ["sh", "bash", "zsh", "dash", "python", "python3", "perl"];
+
+    if text
+        .lines()
+        .any(|l| (l.contains("curl") || l.contains("wget")) && pipes_into(l, &shells))
+    {
+        add(
+            "download_pipe_shell",
+            35,
+            "Downloads and pipes into a shell".into(),
+        );
+    }
+    if text.lines().any(|l| {
+        (l.contains("base64 -d") || l.contains("base64 --decode"))
+            && (pipes_into(l, &shells) || l.contains("eval"))
+    }) {
+        add("decode_and_run", 30, "Decodes base64 and runs it".into());
+    }
+    if text.contains("eval") && (text.contains("$(") || text.contains('`')) {
+        add("eval_subst", 15, "eval of command substitution".into());
+    }
+    if text.contains("/dev/tcp/") || text.contains("/dev/udp/") {
+        add(
+            "reverse_shell",
+            35,
+            "Bash /dev/tcp network redirection".into(),
+        );
+    }
+    if text.contains("nc -e")
+        || text.contains("ncat -e")
+        || (text.contains("mkfifo") && text.contains("nc "))
+    {
+        add("netcat_shell", 30, "Netcat shell".into());
+    }
+    if text.contains("chmod +x") && (text.contains("/tmp/") || text.contains("/dev/shm/")) {
+        add(
+            "exec_from_tmp",
+            10,
+            "Makes a file in /tmp or /dev/shm executable".into(),
+        );
+    }
+    if text.contains("unset histfile") || text.contains("history -c") || text.contains("histsize=0")
+    {
+        add(
+            "anti_forensics",
+            15,
+            "Clears or disables shell history".into(),
+        );
+    }
+    if text.contains("crontab") || text.contains(".bashrc") || text.contains("authorized_keys") {
+        add(
+            "persistence",
+            10,
+            "Touches crontab, shell rc or SSH keys".into(),
+        );
+    }
+}
+
+fn python_indicators(text: &str, add: &mut dyn FnMut(&'static str, u32, String)) {
+    let runs = text.contains("exec(") || text.contains("eval(");
+    if runs
+        && (text.contains("b64decode")
+            || text.contains("zlib.decompress")
+            || text.contains("marshal.loads")
+            || text.contains("codecs.decode"))
+    {
+        add("decode_and_exec", 30, "exec/eval of decoded data".into());
+    }
+    if runs && (text.contains("urlopen(") || text.contains("requests.get(")) {
+        add(
+            "download_and_exec",
+            25,
+            "Downloads code and runs it with exec/eval".into(),
+        );
+    }
+    if text.contains("socket") && (text.contains("pty.spawn") || text.contains("os.dup2")) {
+        add(
+            "reverse_shell",
+            35,
+            "Socket wired to a shell (dup2 / pty.spawn)".into(),
+        );
+    }
+    if text.contains("__import__(") && text.contains("exec(") {
+        add(
+            "dynamic_import_exec",
+            10,
+            "Dynamic __import__ with exec".into(),
+        );
+    }
+}
+
+fn powershell_indicators(text: &str, add: &mut dyn FnMut(&'static str, u32, String)) {
+    if text.contains("-encodedcommand") || text.contains("-enc ") || text.contains("-ec ") {
+        add("encoded_command", 35, "Encoded PowerShell command".into());
+    }
+    if text.contains("frombase64string") {
+        add("base64_decode", 15, "Decodes base64 at runtime".into());
+    }
+
+    let invokes = has_word(text, "iex") || text.contains("invoke-expression");
+    if invokes {
+        add(
+            "invoke_expression",
+            20,
+            "Invoke-Expression of dynamic code".into(),
+        );
+    }
+    if invokes
+        && (text.contains("downloadstring")
+            || text.contains("downloadfile")
+            || text.contains("invoke-webrequest")
+            || text.contains("net.webclient")
+            || text.contains("iwr "))
+    {
+        add(
+            "download_cradle",
+            25,
+            "Download cradle feeding Invoke-Expression".into(),
+        );
+    }
+    if text.contains("-windowstyle hidden") || text.contains("-w hidden") {
+        add("hidden_window", 10, "Runs with a hidden window".into());
+    }
+    if text.contains("-executionpolicy bypass")
+        || text.contains("-ep bypass")
+        || text.contains("-exec bypass")
+    {
+        add("policy_bypass", 10, "Bypasses the execution policy".into());
+    }
+    if text.contains("amsiutils") || text.contains("amsiinitfailed") {
+        add("amsi_bypass", 35, "Tampers with AMSI".into());
+    }
+    // 'Inv'+'oke-Ex'+'pression' and I`E`X style obfuscation
+    if count(text, "'+'") + count(text, "\"+\"") >= 10 || count(text, "`") >= 20 {
+        add(
+            "string_obfuscation",
+            15,
+            "Heavy string splitting or backtick escaping".into(),
+        );
+    }
+}
+
+fn javascript_indicators(text: &str, add: &mut dyn FnMut(&'static str, u32, String)) {
+    if text.contains("eval(")
+        && (text.contains("unescape(") || text.contains("atob(") || text.contains("fromcharcode"))
+    {
+        add("decode_and_eval", 25, "eval of decoded data".into());
+    }
+    if text.contains("activexobject")
+        && (text.contains("wscript.shell") || text.contains("shell.application"))
+    {
+        add("wsh_shell", 25, "Windows Script Host shell object".into());
+    }
+    if text.contains("msxml2.xmlhttp")
+        || text.contains("adodb.stream")
+        || text.contains("winhttp.winhttprequest")
+    {
+        add(
+            "wsh_download",
+            20,
+            "Downloads and writes files through COM".into(),
+        );
+    }
+    if count(text, "fromcharcode") >= 20 {
+        add(
+            "charcode_obfuscation",
+            15,
+            "Builds strings from character codes".into(),
+        );
+    }
+    if text.contains("child_process") && (text.contains(".exec(") || text.contains("spawn(")) {
+        add("node_exec", 10, "Spawns processes from Node.js".into());
+    }
+}
+
+/// Scores a script. `None` when it isn't a script in a language we handle.
+pub(crate) fn analyze(path: &Path, data: &[u8]) -> Option<ScriptReport> {
+    let language = language(path, data)?;
+    let data = &data[..data.len().min(MAX_SCRIPT_BYTES)];
+    let text = String::from_utf8_lossy(data).to_ascii_lowercase();
+
+    let mut indicators: Vec<Indicator> = Vec::new();
+    let mut add = |id: &'static str, weight: u32, detail: String| {
+        indicators.push(Indicator { id, weight, detail });
+    };
+
+    let blob = longest_base64_run(data);
+    if blob >= BASE64_BLOB_CHARS {
+        add(
+            "base64_blob",
+            20,
+            format!("Base64 blob of {blob} characters"),
+        );
+    }
+    if text.lines().any(|l| l.len() > LONG_LINE_CHARS) {
+        add(
+            "long_line",
+            10,
+            format!("Line longer than {LONG_LINE_CHARS} characters"),
+        );
+    }
+
+    match language {
+        Language::Shell => shell_indicators(&text, &mut add),
+        Language::Python => python_indicators(&text, &mut add),
+        Language::PowerShell => powershell_indicators(&text, &mut add),
+        Language::JavaScript => javascript_indicators(&text, &mut add),
+    }
+
+    Some(ScriptReport {
+        language,
+        score: indicator_score(&indicators),
+        indicators,
+    })
+}
+
+impl ScriptReport {
+    /// Human-readable reason for the verdict.
+    pub(crate) fn reason(&self) -> String {
+        self.indicators
+            .iter()
+            .map(|i| i.detail.as_str())
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+}
@@ -0,0 +1,319 @@
+// Holds realtime writes back until the file is complete.
+//
+// Hashing on the first event reads large downloads half-written, and again on
+// every event after that. Instead a written path waits until its writer closed
+// it (close-write, Linux only) or its size and mtime stopped changing for a
+// while. Browsers download under a temporary name (`.part`, `.crdownload`) and
+// rename when done, so those names are ignored and the final name is ready as
+// soon as the rename lands. Each version of a file is scanned once.
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+// No size or mtime change for this long counts as finished
+const STABLE_FOR: Duration = Duration::from_secs(2);
+const MAX_PENDING: usize = 4096;
+const MAX_SCANNED: usize = 8192;
+
+// In-progress download names; Safari writes inside a `.download` bundle instead
+const DOWNLOAD_EXTENSIONS: &[&str] = &["part", "partial", "crdownload", "download", "opdownload"];
+
+type Version = (u64, SystemTime);
+
+struct Pending {
+    version: Option<Version>,
+    changed_at: Instant,
+    closed: bool,
+}
+
+pub(crate) struct SettleQueue {
+    pending: HashMap<PathBuf, Pending>,
+    // Last version handed out per path, so repeat events don't rescan it
+    scanned: HashMap<PathBuf, Version>,
+}
+
+fn has_download_extension(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| DOWNLOAD_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+}
+
+/// A browser download still being written.
+pub(crate) fn is_in_progress(path: &Path) -> bool {
+    has_download_extension(path) || path.parent().is_some_and(has_download_extension)
+}
+
+fn version(path: &Path) -> Option<Version> {
+    let meta = fs::metadata(path).ok().filter(|m| m.is_file())?;
+    Some((meta.len(), meta.modified().ok()?))
+}
+
+impl SettleQueue {
+    pub(crate) fn new() -> Self {
+        SettleQueue {
+            pending: HashMap::new(),
+            scanned: HashMap::new(),
+        }
+    }
+
+    fn track(&mut self, path: &Path, now: Instant, closed: bool) {
+        if is_in_progress(path) {
+            return;
+        }
+        if self.pending.len() >= MAX_PENDING && !self.pending.contains_key(path) {
+            eprintln!("[Realtime] too many files in flight, skipping {:?}", path);
+            return;
+        }
+
+        let entry = self
+            .pending
+            .entry(path.to_path_buf())
+            .or_insert_with(|| Pending {
+                version: None,
+                changed_at: now,
+                closed: false,
+            });
+        entry.changed_at = now;
+        entry.closed = closed;
+    }
+
+    /// Created or written to.
+    pub(crate) fn written(&mut self, path: &Path, now: Instant) {
+        self.track(path, now, false);
+    }
+
+    /// The writer closed the file.
+    pub(crate) fn closed(&mut self, path: &Path, now: Instant) {
+        self.track(path, now, true);
+    }
+
+    pub(crate) fn renamed(&mut self, from: &Path, to: &Path, now: Instant) {
+        self.pending.remove(from);
+
+        // A finished download is complete the moment it gets its real name
+        if is_in_progress(from) {
+            self.track(to, now, true);
+            return;
+        }
+
+        // Same content under a new name needs no second scan
+        if let Some(v) = self.scanned.remove(from) {
+            if version(to) == Some(v) {
+                self.scanned.insert(to.to_path_buf(), v);
+                return;
+            }
+        }
+        self.written(to, now);
+    }
+
+    /// A path `due` handed out that could not be scanned. It is offered again
+    /// once it has been stable for another `STABLE_FOR`.
+    pub(crate) fn requeue(&mut self, path: &Path, now: Instant) {
+        self.scanned.remove(path);
+        self.track(path, now, false);
+    }
+
+    pub(crate) fn removed(&mut self, path: &Path) {
+        self.pending.remove(path);
+        self.scanned.remove(path);
+    }
+
+    /// Paths that finished writing since the last call, each version once.
+    pub(crate) fn due(&mut self, now: Instant) -> Vec<PathBuf> {
+        let mut ready = Vec::new();
+
+        self.pending.retain(|path, p| {
+            let Some(current) = version(path) else {
+                return false; // gone, or not a regular file
+            };
+            let changed = p.version != Some(current);
+            if changed {
+                p.version = Some(current);
+                p.changed_at = now;
+            }
+
+            let done = p.closed || (!changed && now.duration_since(p.changed_at) >= STABLE_FOR);
+            if done {
+                ready.push((path.clone(), current));
+            }
+            !done
+        });
+
+        if self.scanned.len() + ready.len() > MAX_SCANNED {
+            self.scanned.clear();
+        }
+        ready
+            .into_iter()
+            .filter(|(path, v)| self.scanned.insert(path.clone(), *v) != Some(*v))
+            .map(|(path, _)| path)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir =
+                std::env::temp_dir().join(format!("stellar-settle-{name}-{}", std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            TempDir(dir)
+        }
+
+        fn file(&self, name: &str, content: &[u8]) -> PathBuf {
+            let path = self.0.join(name);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).unwrap();
+            }
+            fs::write(&path, content).unwrap();
+            path
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn secs(start: Instant, s: u64) -> Instant {
+        start + Duration::from_secs(s)
+    }
+
+    #[test]
+    fn written_files_wait_until_stable() {
+        let dir = TempDir::new("stable");
+        let path = dir.file("report.pdf", b"first");
+        let start = Instant::now();
+        let mut q = SettleQueue::new();
+
+        q.written(&path, start);
+        assert!(q.due(start).is_empty());
+        assert!(q.due(secs(start, 1)).is_empty());
+        assert_eq!(q.due(secs(start, 2)), vec![path.clone()]);
+        assert!(q.due(secs(start, 3)).is_empty());
+
+        // Still growing: the clock restarts on every change
+        q.written(&path, secs(start, 10));
+        fs::write(&path, b"first and more").unwrap();
+        assert!(q.due(secs(start, 10)).is_empty());
+        fs::write(&path, b"first and more again").unwrap();
+        assert!(q.due(secs(start, 11)).is_empty());
+        assert!(q.due(secs(start, 12)).is_empty());
+        assert_eq!(q.due(secs(start, 13)), vec![path.clone()]);
+    }
+
+    #[test]
+    fn close_write_is_due_at_once_and_each_version_is_scanned_once() {
+        let dir = TempDir::new("closed");
+        let path = dir.file("notes.txt", b"v1");
+        let start = Instant::now();
+        let mut q = SettleQueue::new();
+
+        q.closed(&path, start);
+        assert_eq!(q.due(start), vec![path.clone()]);
+
+        // Same size and mtime again: already scanned
+        q.closed(&path, secs(start, 1));
+        assert!(q.due(secs(start, 1)).is_empty());
+
+        fs::write(&path, b"version 2").unwrap();
+        q.closed(&path, secs(start, 2));
+        assert_eq!(q.due(secs(start, 2)), vec![path.clone()]);
+    }
+
+    #[test]
+    fn downloads_wait_for_their_final_name() {
+        let dir = TempDir::new("download");
+        let start = Instant::now();
+        let mut q = SettleQueue::new();
+
+        let part = dir.file("setup.exe.part", b"MZ");
+        let chrome = dir.file("setup.exe.crdownload", b"MZ");
+        let safari = dir.file("setup.exe.download/setup.exe", b"MZ");
+        for p in [&part, &chrome, &safari] {
+            assert!(is_in_progress(p), "{p:?}");
+            q.written(p, start);
+        }
+        assert!(q.due(secs(start, 5)).is_empty());
+
+        let done = dir.0.join("setup.exe");
+        fs::rename(&part, &done).unwrap();
+        q.renamed(&part, &done, secs(start, 6));
+        assert_eq!(q.due(secs(start, 6)), vec![done.clone()]);
+    }
+
+    #[test]
+    fn renames_of_scanned_content_are_not_rescanned() {
+        let dir = TempDir::new("rename");
+        let from = dir.file("a.docx", b"content");
+        let to = dir.0.join("b.docx");
+        let start = Instant::now();
+        let mut q = SettleQueue::new();
+
+        q.closed(&from, start);
+        assert_eq!(q.due(start), vec![from.clone()]);
+
+        fs::rename(&from, &to).unwrap();
+        q.renamed(&from, &to, secs(start, 1));
+        assert!(q.due(secs(start, 10)).is_empty());
+
+        // A rename that brings new content is a write
+        let other = dir.file("c.docx", b"other content");
+        fs::rename(&other, &to).unwrap();
+        q.renamed(&other, &to, secs(start, 11));
+        assert!(q.due(secs(start, 11)).is_empty());
+        assert_eq!(q.due(secs(start, 13)), vec![to.clone()]);
+    }
+
+    #[test]
+    fn requeued_paths_come_back_after_another_stable_period() {
+        let dir = TempDir::new("requeue");
+        let path = dir.file("invoice.pdf", b"%PDF-1.7");
+        let start = Instant::now();
+        let mut q = SettleQueue::new();
+
+        q.closed(&path, start);
+        assert_eq!(q.due(start), vec![path.clone()]);
+
+        // The scan queue was full: same version, but not scanned yet
+        q.requeue(&path, secs(start, 1));
+        assert!(q.due(secs(start, 1)).is_empty());
+        assert!(q.due(secs(start, 2)).is_empty());
+        assert_eq!(q.due(secs(start, 3)), vec![path.clone()]);
+        assert!(q.due(secs(start, 10)).is_empty());
+    }
+
+    #[test]
+    fn removed_and_vanished_files_drop_out() {
+        let dir = TempDir::new("removed");
+        let kept = dir.file("kept.txt", b"a");
+        let removed = dir.file("removed.txt", b"b");
+        let vanished = dir.file("vanished.txt", b"c");
+        let start = Instant::now();
+        let mut q = SettleQueue::new();
+
+        for p in [&kept, &removed, &vanished] {
+            q.written(p, start);
+        }
+        q.removed(&removed);
+        fs::remove_file(&vanished).unwrap();
+        assert!(q.due(start).is_empty());
+        assert_eq!(q.due(secs(start, 2)), vec![kept.clone()]);
+
+        // Directories are not files to scan
+        let sub = dir.0.join("folder");
+        fs::create_dir(&sub).unwrap();
+        q.closed(&sub, start);
+        assert!(q.due(secs(start, 5)).is_empty());
+    }
+}
@@ -0,0 +1,1243 @@
+// Content signatures: a YARA-style rule language evaluated against file bytes.
+//
+// Supported subset:
+//   rule Name : tags { meta: ... strings: ... condition: ... }
+//   strings:   "text" [nocase] [wide] [ascii] [fullword]
+//              { 4D 5A ?? 00 4? [2-8] FF }   (wildcards, nibble masks, jumps)
+//   condition: $a, #a > 2, $a at 0, $a in (0..1024), any/all/N of them,
+//              any of ($a*, $b), filesize < 2MB, uint16(0) == 0x5A4D,
+//              uint32be(n), and / or / not / ( ), true / false
+//
+// Built-in rules ship in `rules/signatures.yar`; additional packs are loaded
+// from `<data dir>/signatures/*.yar|*.yara`.
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use memchr::memmem;
+
+use crate::app_data_dir;
+
+const BUILTIN_SIGNATURES: &str = include_str!("../rules/signatures.yar");
+
+// Only the head of big files is inspected; droppers and scripts sit well within it.
+pub(crate) const MAX_CONTENT_SCAN_BYTES: u64 = 32 * 1024 * 1024; // 32 MB
+
+// Caps so a pathological pattern can't stall the scanner.
+const MAX_MATCHES_PER_STRING: usize = 1000;
+const MAX_JUMP: usize = 4096;
+const MAX_JUMPS_PER_PATTERN: usize = 8;
+// Byte comparisons one hex string may spend on one file before it gives up
+const HEX_STEP_BUDGET: usize = 16 * 1024 * 1024;
+// nocase strings search a lowered copy of this much input at a time
+const NOCASE_CHUNK: usize = 64 * 1024;
+
+// Compiled packs, refreshed when the signatures dir changes.
+type PackStamp = Vec<(PathBuf, Option<SystemTime>)>;
+static CACHE: Mutex<Option<(PackStamp, Arc<SignatureSet>)>> = Mutex::new(None);
+
+// ---- Compiled form ----
+
+#[derive(Clone, Copy)]
+enum Piece {
+    Byte { value: u8, mask: u8 },
+    Jump { min: usize, max: usize },
+}
+
+enum Pattern {
+    Text {
+        needle: Vec<u8>,
+        nocase: bool,
+        fullword: bool,
+    },
+    Hex(Vec<Piece>),
+}
+
+struct StringDef {
+    id: String,
+    // `ascii wide` yields two variants; a hit on either counts
+    variants: Vec<Pattern>,
+}
+
+#[derive(Clone, Copy)]
+enum Cmp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Cmp {
+    fn eval(self, a: u64, b: u64) -> bool {
+        match self {
+            Cmp::Eq => a == b,
+            Cmp::Ne => a != b,
+            Cmp::Lt => a < b,
+            Cmp::Le => a <= b,
+            Cmp::Gt => a > b,
+            Cmp::Ge => a >= b,
+        }
+    }
+}
+
+enum Quant {
+    Any,
+    All,
+    N(u64),
+}
+
+enum Expr {
+    Bool(bool),
+    Found(usize),
+    At(usize, u64),
+    In(usize, u64, u64),
+    Count(usize, Cmp, u64),
+    FileSize(Cmp, u64),
+    Int {
+        bytes: usize,
+        big_endian: bool,
+        offset: u64,
+        cmp: Cmp,
+        value: u64,
+    },
+    Of(Quant, Vec<usize>),
+    Not(Box<Expr>),
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+}
+
+struct SigRule {
+    name: String,
+    severity: String,
+    strings: Vec<StringDef>,
+    condition: Expr,
+}
+
+pub(crate) struct SignatureSet {
+    rules: Vec<SigRule>,
+}
+
+pub(crate) struct SignatureMatch {
+    pub(crate) rule: String,
+    pub(crate) severity: String,
+}
+
+// ---- Lexer ----
+
+#[derive(Clone, PartialEq, Debug)]
+enum Tok {
+    Ident(String),
+    Str(Vec<u8>),
+    Hex(String),
+    Num(u64),
+    Var(String),   // $name / $name* / $
+    Count(String), // #name
+    Sym(&'static str),
+}
+
+fn lex(src: &str) -> Result<Vec<Tok>, String> {
+    let chars: Vec<char> = src.chars().collect();
+    let mut toks: Vec<Tok> = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        // Comments
+        if c == '/' && chars.get(i + 1) == Some(&'/') {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+            continue;
+        }
+        if c == '/' && chars.get(i + 1) == Some(&'*') {
+            i += 2;
+            while i + 1 < chars.len() && !(chars[i] == '*' && chars[i + 1] == '/') {
+                i += 1;
+            }
+            i += 2;
+            continue;
+        }
+
+        let after_assign = toks.last() == Some(&Tok::Sym("="));
+
+        if c == '{' && after_assign {
+            let start = i + 1;
+            while i < chars.len() && chars[i] != '}' {
+                i += 1;
+            }
+            if i >= chars.len() {
+                return Err("unterminated hex string".to_string());
+            }
+            toks.push(Tok::Hex(chars[start..i].iter().collect()));
+            i += 1;
+            continue;
+        }
+
+        if c == '/' && after_assign {
+            return Err("regular expression strings are not supported".to_string());
+        }
+
+        if c == '"' {
+            let mut out: Vec<u8> = Vec::new();
+            i += 1;
+            loop {
+                let ch = *chars.get(i).ok_or("unterminated string")?;
+                i += 1;
+                match ch {
+                    '"' => break,
+                    '\\' => {
+                        let esc = *chars.get(i).ok_or("unterminated string")?;
+                        i += 1;
+                        match esc {
+                            'n' => out.push(b'\n'),
+                            'r' => out.push(b'\r'),
+                            't' => out.push(b'\t'),
+                            '0' => out.push(0),
+                            '\\' => out.push(b'\\'),
+                            '"' => out.push(b'"'),
+                            'x' => {
+                                let hex: String =
+                                    chars.get(i..i + 2).unwrap_or(&[]).iter().collect();
+                                let b = u8::from_str_radix(&hex, 16)
+                                    .map_err(|_| format!("bad \\x escape: {hex}"))?;
+                                out.push(b);
+                                i += 2;
+                            }
+                            other => return Err(format!("unknown escape \\{other}")),
+                        }
+                    }
+                    other => {
+                        let mut buf = [0u8; 4];
+                        out.extend_from_slice(other.encode_utf8(&mut buf).as_bytes());
+                    }
+                }
+            }
+            toks.push(Tok::Str(out));
+            continue;
+        }
+
+        if c == '$' || c == '#' {
+            let start = i + 1;
+            i += 1;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            if c == '$' && chars.get(i) == Some(&'*') {
+                i += 1;
+            }
+            let name: String = chars[start..i].iter().collect();
+            toks.push(if c == '$' {
+                Tok::Var(name)
+            } else {
+                Tok::Count(name)
+            });
+            continue;
+        }
+
+        if c.is_ascii_digit() {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric()) {
+                i += 1;
+            }
+            let raw: String = chars[start..i].iter().collect();
+            toks.push(Tok::Num(parse_number(&raw)?));
+            continue;
+        }
+
+        if c.is_ascii_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            toks.push(Tok::Ident(chars[start..i].iter().collect()));
+            continue;
+        }
+
+        let two: String = chars[i..(i + 2).min(chars.len())].iter().collect();
+        let sym = match two.as_str() {
+            "==" => Some("=="),
+            "!=" => Some("!="),
+            "<=" => Some("<="),
+            ">=" => Some(">="),
+            ".." => Some(".."),
+            _ => None,
+        };
+        if let Some(sym) = sym {
+            toks.push(Tok::Sym(sym));
+            i += 2;
+            continue;
+        }
+
+        let sym = match c {
+            '{' => "{",
+            '}' => "}",
+            '(' => "(",
+            ')' => ")",
+            ':' => ":",
+            ',' => ",",
+            '=' => "=",
+            '<' => "<",
+            '>' => ">",
+            other => return Err(format!("unexpected character {other:?}")),
+        };
+        toks.push(Tok::Sym(sym));
+        i += 1;
+    }
+
+    Ok(toks)
+}
+
+fn parse_number(raw: &str) -> Result<u64, String> {
+    let lower = raw.to_lowercase();
+    let (digits, mult) = if let Some(d) = lower.strip_suffix("kb") {
+        (d, 1024)
+    } else if let Some(d) = lower.strip_suffix("mb") {
+        (d, 1024 * 1024)
+    } else {
+        (lower.as_str(), 1)
+    };
+
+    let value = match digits.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => digits.parse::<u64>(),
+    }
+    .map_err(|_| format!("bad number {raw}"))?;
+
+    Ok(value * mult)
+}
+
+// ---- Hex strings ----
+
+fn parse_hex_pattern(raw: &str) -> Result<Vec<Piece>, String> {
+    let chars: Vec<char> = raw.chars().filter(|c| !c.is_whitespace()).collect();
+    let mut pieces: Vec<Piece> = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        if chars[i] == '[' {
+            let end = chars[i..]
+                .iter()
+                .position(|c| *c == ']')
+                .map(|p| p + i)
+                .ok_or("unterminated jump in hex string")?;
+            let inner: String = chars[i + 1..end].iter().collect();
+            let (min, max) = match inner.split_once('-') {
+                Some((a, b)) => (
+                    a.parse::<usize>()
+                        .map_err(|_| format!("bad jump [{inner}]"))?,
+                    if b.is_empty() {
+                        MAX_JUMP
+                    } else {
+                        b.parse::<usize>()
+                            .map_err(|_| format!("bad jump [{inner}]"))?
+                    },
+                ),
+                None => {
+                    let n = inner
+                        .parse::<usize>()
+                        .map_err(|_| format!("bad jump [{inner}]"))?;
+                    (n, n)
+                }
+            };
+            if min > max || max > MAX_JUMP {
+                return Err(format!("jump [{inner}] out of range"));
+            }
+            pieces.push(Piece::Jump { min, max });
+            i = end + 1;
+            continue;
+        }
+
+        if chars[i] == '(' || chars[i] == '|' {
+            return Err("hex alternatives are not supported".to_string());
+        }
+
+        let hi = chars[i];
+        let lo = *chars.get(i + 1).ok_or("odd number of hex digits")?;
+        let nibble = |c: char| -> Result<(u8, u8), String> {
+            if c == '?' {
+                Ok((0, 0))
+            } else {
+                c.to_digit(16)
+                    .map(|d| (d as u8, 0xF))
+                    .ok_or_else(|| format!("bad hex digit {c:?}"))
+            }
+        };
+        let (hv, hm) = nibble(hi)?;
+        let (lv, lm) = nibble(lo)?;
+        pieces.push(Piece::Byte {
+            value: (hv << 4) | lv,
+            mask: (hm << 4) | lm,
+        });
+        i += 2;
+    }
+
+    let jumps = pieces
+        .iter()
+        .filter(|p| matches!(p, Piece::Jump { .. }))
+        .count();
+    if jumps > MAX_JUMPS_PER_PATTERN {
+        return Err(format!(
+            "hex string has {jumps} jumps, at most {MAX_JUMPS_PER_PATTERN} are allowed"
+        ));
+    }
+
+    match (pieces.first(), pieces.last()) {
+        (Some(Piece::Byte { .. }), Some(Piece::Byte { .. })) => Ok(pieces),
+        (None, _) => Err("empty hex string".to_string()),
+        _ => Err("hex strings can't start or end with a jump".to_string()),
+    }
+}
+
+// ---- Parser ----
+
+struct Parser {
+    toks: Vec<Tok>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Tok> {
+        self.toks.get(self.pos)
+    }
+
+    fn next(&mut self) -> Result<Tok, String> {
+        let t = self
+            .toks
+            .get(self.pos)
+            .cloned()
+            .ok_or("unexpected end of input")?;
+        self.pos += 1;
+        Ok(t)
+    }
+
+    fn eat_sym(&mut self, sym: &str) -> bool {
+        if matches!(self.peek(), Some(Tok::Sym(s)) if *s == sym) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect_sym(&mut self, sym: &str) -> Result<(), String> {
+        if self.eat_sym(sym) {
+            Ok(())
+        } else {
+            Err(format!("expected '{sym}', found {:?}", self.peek()))
+        }
+    }
+
+    fn eat_ident(&mut self, word: &str) -> bool {
+        if matches!(self.peek(), Some(Tok::Ident(s)) if s == word) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn ident(&mut self) -> Result<String, String> {
+        match self.next()? {
+            Tok::Ident(s) => Ok(s),
+            other => Err(format!("expected identifier, found {other:?}")),
+        }
+    }
+
+    fn number(&mut self) -> Result<u64, String> {
+        match self.next()? {
+            Tok::Num(n) => Ok(n),
+            other => Err(format!("expected number, found {other:?}")),
+        }
+    }
+
+    fn rules(&mut self) -> Result<Vec<SigRule>, String> {
+        let mut out = Vec::new();
+        while self.peek().is_some() {
+            out.push(self.rule()?);
+        }
+        Ok(out)
+    }
+
+    fn rule(&mut self) -> Result<SigRule, String> {
+        // Modifiers are accepted for compatibility; every rule is evaluated.
+        while self.eat_ident("private") || self.eat_ident("global") {}
+
+        if !self.eat_ident("rule") {
+            return Err(format!("expected 'rule', found {:?}", self.peek()));
+        }
+        let ident = self.ident()?;
+
+        if self.eat_sym(":") {
+            while matches!(self.peek(), Some(Tok::Ident(_))) {
+                self.pos += 1;
+            }
+        }
+
+        self.expect_sym("{")
+            .map_err(|e| format!("rule {ident}: {e}"))?;
+
+        let mut meta: HashMap<String, String> = HashMap::new();
+        let mut strings: Vec<StringDef> = Vec::new();
+        let mut condition: Option<Expr> = None;
+
+        while !self.eat_sym("}") {
+            let section = self.ident().map_err(|e| format!("rule {ident}: {e}"))?;
+            self.expect_sym(":")
+                .map_err(|e| format!("rule {ident}: {e}"))?;
+
+            match section.as_str() {
+                "meta" => self.meta(&mut meta),
+                "strings" => self.strings(&mut strings),
+                "condition" => self.expr(&strings).map(|e| condition = Some(e)),
+                other => Err(format!("unknown section '{other}'")),
+            }
+            .map_err(|e| format!("rule {ident}: {e}"))?;
+        }
+
+        let condition = condition.ok_or_else(|| format!("rule {ident}: missing condition"))?;
+
+        Ok(SigRule {
+            name: meta.remove("name").unwrap_or(ident),
+            severity: meta
+                .remove("severity")
+                .map(|s| s.to_lowercase())
+                .unwrap_or_else(|| "high".to_string()),
+            strings,
+            condition,
+        })
+    }
+
+    fn at_section_end(&self) -> bool {
+        matches!(
+            (self.peek(), self.toks.get(self.pos + 1)),
+            (Some(Tok::Sym("}")), _) | (None, _) | (Some(Tok::Ident(_)), Some(Tok::Sym(":")))
+        )
+    }
+
+    fn meta(&mut self, meta: &mut HashMap<String, String>) -> Result<(), String> {
+        while !self.at_section_end() {
+            let key = self.ident()?;
+            self.expect_sym("=")?;
+            let value = match self.next()? {
+                Tok::Str(s) => String::from_utf8_lossy(&s).to_string(),
+                Tok::Num(n) => n.to_string(),
+                Tok::Ident(s) => s,
+                other => return Err(format!("bad meta value {other:?}")),
+            };
+            meta.insert(key, value);
+        }
+        Ok(())
+    }
+
+    fn strings(&mut self, strings: &mut Vec<StringDef>) -> Result<(), String> {
+        while !self.at_section_end() {
+            let id = match self.next()? {
+                Tok::Var(name) if !name.is_empty() && !name.ends_with('*') => name,
+                other => return Err(format!("expected string identifier, found {other:?}")),
+            };
+            if strings.iter().any(|s| s.id == id) {
+                return Err(format!("duplicate string ${id}"));
+            }
+            self.expect_sym("=")?;
+
+            let variants = match self.next()? {
+                Tok::Hex(raw) => vec![Pattern::Hex(
+                    parse_hex_pattern(&raw).map_err(|e| format!("${id}: {e}"))?,
+                )],
+                Tok::Str(text) => {
+                    if text.is_empty() {
+                        return Err(format!("${id}: empty string"));
+                    }
+                    let (mut nocase, mut wide, mut ascii, mut fullword) =
+                        (false, false, false, false);
+                    while let Some(Tok::Ident(m)) = self.peek().cloned() {
+                        match m.as_str() {
+                            "nocase" => nocase = true,
+                            "wide" => wide = true,
+                            "ascii" => ascii = true,
+                            "fullword" => fullword = true,
+                            _ => break,
+                        }
+                        self.pos += 1;
+                    }
+
+                    let mut variants = Vec::new();
+                    let prep = |b: Vec<u8>| {
+                        if nocase {
+                            b.to_ascii_lowercase()
+                        } else {
+                            b
+                        }
+                    };
+                    if ascii || !wide {
+                        variants.push(Pattern::Text {
+                            needle: prep(text.clone()),
+                            nocase,
+                            fullword,
+                        });
+                    }
+                    if wide {
+                        let utf16: Vec<u8> = text.iter().flat_map(|b| [*b, 0]).collect();
+                        variants.push(Pattern::Text {
+                            needle: prep(utf16),
+                            nocase,
+                            fullword,
+                        });
+                    }
+                    variants
+                }
+                other => return Err(format!("${id}: expected string value, found {other:?}")),
+            };
+
+            strings.push(StringDef { id, variants });
+        }
+        Ok(())
+    }
+
+    fn string_index(&self, strings: &[StringDef], name: &str) -> Result<usize, String> {
+        strings
+            .iter()
+            .position(|s| s.id == name)
+            .ok_or_else(|| format!("undefined string ${name}"))
+    }
+
+    fn cmp(&mut self) -> Result<Cmp, String> {
+        match self.next()? {
+            Tok::Sym("==") => Ok(Cmp::Eq),
+            Tok::Sym("!=") => Ok(Cmp::Ne),
+            Tok::Sym("<") => Ok(Cmp::Lt),
+            Tok::Sym("<=") => Ok(Cmp::Le),
+            Tok::Sym(">") => Ok(Cmp::Gt),
+            Tok::Sym(">=") => Ok(Cmp::Ge),
+            other => Err(format!("expected comparison, found {other:?}")),
+        }
+    }
+
+    fn expr(&mut self, strings: &[StringDef]) -> Result<Expr, String> {
+        let mut terms = vec![self.and_expr(strings)?];
+        while self.eat_ident("or") {
+            terms.push(self.and_expr(strings)?);
+        }
+        Ok(if terms.len() == 1 {
+            terms.remove(0)
+        } else {
+            Expr::Or(terms)
+        })
+    }
+
+    fn and_expr(&mut self, strings: &[StringDef]) -> Result<Expr, String> {
+        let mut terms = vec![self.not_expr(strings)?];
+        while self.eat_ident("and") {
+            terms.push(self.not_expr(strings)?);
+        }
+        Ok(if terms.len() == 1 {
+            terms.remove(0)
+        } else {
+            Expr::And(terms)
+        })
+    }
+
+    fn not_expr(&mut self, strings: &[StringDef]) -> Result<Expr, String> {
+        if self.eat_ident("not") {
+            return Ok(Expr::Not(Box::new(self.not_expr(strings)?)));
+        }
+        self.primary(strings)
+    }
+
+    fn string_set(&mut self, strings: &[StringDef]) -> Result<Vec<usize>, String> {
+        if self.eat_ident("them") {
+            return Ok((0..strings.len()).collect());
+        }
+
+        self.expect_sym("(")?;
+        let mut set: Vec<usize> = Vec::new();
+        loop {
+            match self.next()? {
+                Tok::Var(name) => match name.strip_suffix('*') {
+                    Some(prefix) => set.extend(
+                        strings
+                            .iter()
+                            .enumerate()
+                            .filter(|(_, s)| s.id.starts_with(prefix))
+                            .map(|(i, _)| i),
+                    ),
+                    None => set.push(self.string_index(strings, &name)?),
+                },
+                other => return Err(format!("expected string in set, found {other:?}")),
+            }
+            if !self.eat_sym(",") {
+                break;
+            }
+        }
+        self.expect_sym(")")?;
+
+        set.sort_unstable();
+        set.dedup();
+        if set.is_empty() {
+            return Err("string set matches nothing".to_string());
+        }
+        Ok(set)
+    }
+
+    fn primary(&mut self, strings: &[StringDef]) -> Result<Expr, String> {
+        match self.next()? {
+            Tok::Sym("(") => {
+                let e = self.expr(strings)?;
+                self.expect_sym(")")?;
+                Ok(e)
+            }
+            Tok::Var(name) => {
+                let idx = self.string_index(strings, &name)?;
+                if self.eat_ident("at") {
+                    Ok(Expr::At(idx, self.number()?))
+                } else if self.eat_ident("in") {
+                    self.expect_sym("(")?;
+                    let from = self.number()?;
+                    self.expect_sym("..")?;
+                    let to = self.number()?;
+                    self.expect_sym(")")?;
+                    Ok(Expr::In(idx, from, to))
+                } else {
+                    Ok(Expr::Found(idx))
+                }
+            }
+            Tok::Count(name) => {
+                let idx = self.string_index(strings, &name)?;
+                let cmp = self.cmp()?;
+                Ok(Expr::Count(idx, cmp, self.number()?))
+            }
+            Tok::Num(n) => {
+                self.expect_of()?;
+                Ok(Expr::Of(Quant::N(n), self.string_set(strings)?))
+            }
+            Tok::Ident(word) => match word.as_str() {
+                "true" => Ok(Expr::Bool(true)),
+                "false" => Ok(Expr::Bool(false)),
+                "any" | "all" => {
+                    self.expect_of()?;
+                    let quant = if word == "any" {
+                        Quant::Any
+                    } else {
+                        Quant::All
+                    };
+                    Ok(Expr::Of(quant, self.string_set(strings)?))
+                }
+                "filesize" => {
+                    let cmp = self.cmp()?;
+                    Ok(Expr::FileSize(cmp, self.number()?))
+                }
+                "uint8" | "uint16" | "uint32" | "uint16be" | "uint32be" => {
+                    let bytes = match word.trim_end_matches("be") {
+                        "uint8" => 1,
+                        "uint16" => 2,
+                        _ => 4,
+                    };
+                    self.expect_sym("(")?;
+                    let offset = self.number()?;
+                    self.expect_sym(")")?;
+                    let cmp = self.cmp()?;
+                    Ok(Expr::Int {
+                        bytes,
+                        big_endian: word.ends_with("be"),
+                        offset,
+                        cmp,
+                        value: self.number()?,
+                    })
+                }
+                other => Err(format!("unsupported condition keyword '{other}'")),
+            },
+            other => Err(format!("unexpected token in condition: {other:?}")),
+        }
+    }
+
+    fn expect_of(&mut self) -> Result<(), String> {
+        if self.eat_ident("of") {
+            Ok(())
+        } else {
+            Err("expected 'of'".to_string())
+        }
+    }
+}
+
+pub(crate) fn compile_source(src: &str) -> Result<SignatureSet, String> {
+    let toks = lex(src)?;
+    let rules = Parser { toks, pos: 0 }.rules()?;
+    Ok(SignatureSet { rules })
+}
+
+// ---- Matching ----
+
+fn is_word_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'_'
+}
+
+/// Whether `pieces` match at `pos`. Instead of backtracking through jumps this
+/// tracks every position the pattern could have reached, so a jump costs at most
+/// its width. Each byte comparison is charged to `budget`; None once it is spent.
+fn hex_match_at(data: &[u8], pos: usize, pieces: &[Piece], budget: &mut usize) -> Option<bool> {
+    let mut reached = vec![pos];
+    let mut next = Vec::new();
+
+    for piece in pieces {
+        next.clear();
+        match *piece {
+            Piece::Byte { value, mask } => {
+                *budget = budget.checked_sub(reached.len())?;
+                next.extend(
+                    reached
+                        .iter()
+                        .filter(|&&p| data.get(p).is_some_and(|b| b & mask == value & mask))
+                        .map(|p| p + 1),
+                );
+            }
+            Piece::Jump { min, max } => {
+                // `reached` is ascending, so overlapping ranges merge in one pass
+                for &p in &reached {
+                    let from = (p + min).max(next.last().map_or(0, |l| l + 1));
+                    let to = (p + max).min(data.len());
+                    next.extend(from..=to);
+                }
+            }
+        }
+        std::mem::swap(&mut reached, &mut next);
+        if reached.is_empty() {
+            return Some(false);
+        }
+    }
+    Some(true)
+}
+
+fn find_hex(data: &[u8], pieces: &[Piece]) -> Vec<usize> {
+    let mut out = Vec::new();
+    let mut budget = HEX_STEP_BUDGET;
+
+    // Anchor on the first exact byte ahead of any jump; a pattern starting with
+    // wildcards would otherwise be tried at every offset
+    let anchor = pieces
+        .iter()
+        .take_while(|p| matches!(p, Piece::Byte { .. }))
+        .position(|p| matches!(p, Piece::Byte { mask: 0xFF, .. }));
+    let candidates: Box<dyn Iterator<Item = usize>> = match anchor {
+        Some(i) => {
+            let Piece::Byte { value, .. } = pieces[i] else {
+                return out;
+            };
+            Box::new(memchr::memchr_iter(value, data).filter_map(move |p| p.checked_sub(i)))
+        }
+        None => Box::new(0..data.len()),
+    };
+
+    for pos in candidates {
+        match hex_match_at(data, pos, pieces, &mut budget) {
+            Some(true) => out.push(pos),
+            Some(false) => continue,
+            None => break, // out of budget: keep what was found
+        }
+        if out.len() >= MAX_MATCHES_PER_STRING {
+            break;
+        }
+    }
+
+    out
+}
+
+/// Offsets of `needle` in `haystack`. A `nocase` needle is already lowercase;
+/// the haystack is lowered a chunk at a time instead of copied whole.
+fn find_text(haystack: &[u8], needle: &[u8], nocase: bool, fullword: bool) -> Vec<usize> {
+    let word_ok = |pos: usize| {
+        !fullword
+            || ((pos == 0 || !is_word_byte(haystack[pos - 1]))
+                && haystack
+                    .get(pos + needle.len())
+                    .map_or(true, |b| !is_word_byte(*b)))
+    };
+
+    let mut out = Vec::new();
+    if !nocase {
+        for pos in memmem::find_iter(haystack, needle).filter(|p| word_ok(*p)) {
+            out.push(pos);
+            if out.len() >= MAX_MATCHES_PER_STRING {
+                break;
+            }
+        }
+        return out;
+    }
+
+    // Chunks overlap by the needle length so a match across a border is
+    // seen once, in the chunk where it starts
+    let finder = memmem::Finder::new(needle);
+    let mut lowered = Vec::with_capacity(NOCASE_CHUNK + needle.len());
+    for start in (0..haystack.len()).step_by(NOCASE_CHUNK) {
+        let end = haystack
+            .len()
+            .min(start + NOCASE_CHUNK + needle.len().saturating_sub(1));
+        lowered.clear();
+        lowered.extend(haystack[start..end].iter().map(u8::to_ascii_lowercase));
+
+        for pos in finder.find_iter(&lowered).take_while(|p| *p < NOCASE_CHUNK) {
+            if word_ok(start + pos) {
+                out.push(start + pos);
+                if out.len() >= MAX_MATCHES_PER_STRING {
+                    return out;
+                }
+            }
+        }
+    }
+    out
+}
+
+struct ScanInput<'a> {
+    data: &'a [u8],
+    file_size: u64,
+}
+
+impl StringDef {
+    fn offsets(&self, input: &ScanInput) -> Vec<usize> {
+        let mut all: Vec<usize> = Vec::new();
+        for v in &self.variants {
+            match v {
+                Pattern::Hex(pieces) => all.extend(find_hex(input.data, pieces)),
+                Pattern::Text {
+                    needle,
+                    nocase,
+                    fullword,
+                } => all.extend(find_text(input.data, needle, *nocase, *fullword)),
+            }
+        }
+        all.sort_unstable();
+        all.dedup();
+        all
+    }
+}
+
+fn read_int(data: &[u8], offset: u64, bytes: usize, big_endian: bool) -> Option<u64> {
+    let start = usize::try_from(offset).ok()?;
+    let slice = data.get(start..start.checked_add(bytes)?)?;
+    let mut v: u64 = 0;
+    if big_endian {
+        for b in slice {
+            v = (v << 8) | *b as u64;
+        }
+    } else {
+        for b in slice.iter().rev() {
+            v = (v << 8) | *b as u64;
+        }
+    }
+    Some(v)
+}
+
+impl Expr {
+    fn eval(&self, input: &ScanInput, hits: &[Vec<usize>]) -> bool {
+        match self {
+            Expr::Bool(b) => *b,
+            Expr::Found(i) => !hits[*i].is_empty(),
+            Expr::At(i, off) => hits[*i].iter().any(|p| *p as u64 == *off),
+            Expr::In(i, from, to) => hits[*i]
+                .iter()
+                .any(|p| (*from..=*to).contains(&(*p as u64))),
+            Expr::Count(i, cmp, n) => cmp.eval(hits[*i].len() as u64, *n),
+            Expr::FileSize(cmp, n) => cmp.eval(input.file_size, *n),
+            Expr::Int {
+                bytes,
+                big_endian,
+                offset,
+                cmp,
+                value,
+            } => read_int(input.data, *offset, *bytes, *big_endian)
+                .is_some_and(|v| cmp.eval(v, *value)),
+            Expr::Of(quant, set) => {
+                let found = set.iter().filter(|i| !hits[**i].is_empty()).count() as u64;
+                match quant {
+                    Quant::Any => found >= 1,
+                    Quant::All => found == set.len() as u64,
+                    Quant::N(n) => found >= *n,
+                }
+            }
+            Expr::Not(e) => !e.eval(input, hits),
+            Expr::And(es) => es.iter().all(|e| e.eval(input, hits)),
+            Expr::Or(es) => es.iter().any(|e| e.eval(input, hits)),
+        }
+    }
+}
+
+impl SignatureSet {
+    /// Evaluates every rule against `data` (the head of a file of `file_size`
+    /// bytes); only the first `MAX_CONTENT_SCAN_BYTES` are searched.
+    pub(crate) fn scan_bytes(&self, data: &[u8], file_size: u64) -> Vec<SignatureMatch> {
+        if self.rules.is_empty() {
+            return vec![];
+        }
+        let data = &data[..data.len().min(MAX_CONTENT_SCAN_BYTES as usize)];
+
+        let input = ScanInput { data, file_size };
+
+        let mut out = Vec::new();
+        for rule in &self.rules {
+            let hits: Vec<Vec<usize>> = rule.strings.iter().map(|s| s.offsets(&input)).collect();
+            if rule.condition.eval(&input, &hits) {
+                out.push(SignatureMatch {
+                    rule: rule.name.clone(),
+                    severity: rule.severity.clone(),
+                });
+            }
+        }
+        out
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.rules.len()
+    }
+}
+
+// ---- Loading ----
+
+fn signatures_dir() -> PathBuf {
+    app_data_dir().join("signatures")
+}
+
+fn pack_files() -> PackStamp {
+    let mut files: PackStamp = fs::read_dir(signatures_dir())
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| {
+            p.extension()
+                .and_then(|s| s.to_str())
+                .is_some_and(|e| e.eq_ignore_ascii_case("yar") || e.eq_ignore_ascii_case("yara"))
+        })
+        .map(|p| {
+            let mtime = fs::metadata(&p).and_then(|m| m.modified()).ok();
+            (p, mtime)
+        })
+        .collect();
+    files.sort_by(|a, b| a.0.cmp(&b.0));
+    files
+}
+
+/// Built-ins plus every pack in the data dir. A broken pack is skipped with a
+/// log line rather than disabling the others.
+fn load_signature_set(packs: &PackStamp) -> (SignatureSet, Vec<String>) {
+    let mut errors = Vec::new();
+    let mut set = compile_source(BUILTIN_SIGNATURES).unwrap_or_else(|e| {
+        errors.push(format!("built-in signatures: {e}"));
+        SignatureSet { rules: vec![] }
+    });
+
+    for (path, _) in packs {
+        let compiled = fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|src| compile_source(&src));
+        match compiled {
+            Ok(pack) => set.rules.extend(pack.rules),
+            Err(e) => errors.push(format!("{}: {e}", path.display())),
+        }
+    }
+
+    (set, errors)
+}
+
+pub(crate) fn current() -> Arc<SignatureSet> {
+    let packs = pack_files();
+    let mut cache = CACHE.lock().unwrap_or_else(|e| e.into_inner());
+
+    if let Some((stamp, set)) = cache.as_ref() {
+        if *stamp == packs {
+            return set.clone();
+        }
+    }
+
+    let (set, errors) = load_signature_set(&packs);
+    for e in &errors {
+        eprintln!("[SIG] {e}");
+    }
+    println!("[SIG] loaded {} signature rule(s)", set.len());
+
+    let set = Arc::new(set);
+    *cache = Some((packs, set.clone()));
+    set
+}
+
+// ---- Commands ----
+
+/// Recompiles all packs; returns the number of active rules, or every compile
+/// error so broken packs can be fixed.
+#[tauri::command]
+pub(crate) fn reload_signatures() -> Result<usize, String> {
+    let packs = pack_files();
+    let (set, errors) = load_signature_set(&packs);
+    let count = set.len();
+
+    let mut cache = CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    *cache = Some((packs, Arc::new(set)));
+
+    if errors.is_empty() {
+        Ok(count)
+    } else {
+        Err(errors.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(strings: &str, condition: &str) -> String {
+        format!("rule T {{ strings: {strings} condition: {condition} }}")
+    }
+
+    fn hits(src: &str, data: &[u8]) -> bool {
+        let set = compile_source(src).expect("rule compiles");
+        !set.scan_bytes(data, data.len() as u64).is_empty()
+    }
+
+    fn hex_hits(pattern: &str, data: &[u8]) -> bool {
+        hits(&rule(&format!("$a = {{ {pattern} }}"), "$a"), data)
+    }
+
+    #[test]
+    fn hex_jumps_respect_bounds() {
+        for gap in 0..7 {
+            let mut data = b"MZ".to_vec();
+            data.extend(std::iter::repeat(b'x').take(gap));
+            data.extend(b"PE");
+            assert_eq!(
+                hex_hits("4D 5A [2-4] 50 45", &data),
+                (2..=4).contains(&gap),
+                "gap {gap}"
+            );
+        }
+        assert!(hex_hits("4D 5A [3] 50", b"MZabcP"));
+        assert!(!hex_hits("4D 5A [3] 50", b"MZabP"));
+        assert!(hex_hits("4D 5A [2-] 50", b"MZ0123456789P"));
+    }
+
+    #[test]
+    fn hex_masks_and_wildcards() {
+        assert!(hex_hits("4? 5A", b"AZ"));
+        assert!(!hex_hits("4? 5A", b"QZ"));
+        assert!(hex_hits("?1 5A", b"QZ"));
+        assert!(hex_hits("?? 5A 90", b"\x00\x01Z\x90"));
+        assert!(!hex_hits("?? 5A 90", b"Z\x90"));
+        assert!(hex_hits("?? ?? [1-2] 41", b"xyzA"));
+    }
+
+    #[test]
+    fn hex_offsets_are_reported() {
+        let pieces = parse_hex_pattern("?? 5A [1-2] 90").unwrap();
+        let data = b"\x00Z\x01\x90..\x00Z\x01\x02\x90";
+        assert_eq!(find_hex(data, &pieces), vec![0, 6]);
+    }
+
+    #[test]
+    fn pathological_hex_pattern_stays_bounded() {
+        let data = vec![0u8; 1 << 20];
+        let pattern = "00 [0-4096] 00 [0-4096] 00 [0-4096] 00 [0-4096] 00 [0-4096] \
+                       00 [0-4096] 00 [0-4096] 00 [0-4096] 01";
+        let started = std::time::Instant::now();
+        assert!(!hex_hits(pattern, &data));
+        assert!(started.elapsed() < std::time::Duration::from_secs(10));
+    }
+
+    #[test]
+    fn hex_parse_errors() {
+        for bad in [
+            "[2] 41",
+            "41 [2]",
+            "41 [5-2] 42",
+            "41 [0-5000] 42",
+            "41 (42 | 43)",
+            "41 4",
+            "41 GG",
+            "",
+            "41 [1] 41 [1] 41 [1] 41 [1] 41 [1] 41 [1] 41 [1] 41 [1] 41 [1] 41",
+        ] {
+            assert!(parse_hex_pattern(bad).is_err(), "{bad:?} should not parse");
+        }
+        assert!(
+            parse_hex_pattern("41 [1] 41 [1] 41 [1] 41 [1] 41 [1] 41 [1] 41 [1] 41 [1] 41").is_ok()
+        );
+    }
+
+    #[test]
+    fn text_modifiers() {
+        let fullword = rule(r#"$a = "IEX" nocase fullword"#, "$a");
+        assert!(hits(&fullword, b"x = iex(y)"));
+        assert!(hits(&fullword, b"IEX"));
+        assert!(!hits(&fullword, b"IEXPLORE"));
+        assert!(!hits(&fullword, b"_IEX"));
+
+        let wide = rule(r#"$a = "cmd" wide"#, "$a");
+        assert!(hits(&wide, b"c\x00m\x00d\x00"));
+        assert!(!hits(&wide, b"cmd"));
+
+        let both = rule(r#"$a = "cmd" ascii wide"#, "$a");
+        assert!(hits(&both, b"cmd"));
+        assert!(hits(&both, b"c\x00m\x00d\x00"));
+
+        assert!(!hits(&rule(r#"$a = "Cmd""#, "$a"), b"cmd"));
+    }
+
+    #[test]
+    fn nocase_matches_across_chunks() {
+        // One match straddling the chunk border, one ending exactly on it
+        let mut data = vec![b'.'; 3 * NOCASE_CHUNK];
+        data[NOCASE_CHUNK - 2..NOCASE_CHUNK + 4].copy_from_slice(b"PoWeRs");
+        data[2 * NOCASE_CHUNK - 6..2 * NOCASE_CHUNK].copy_from_slice(b"POWERS");
+
+        let found = find_text(&data, b"powers", true, true);
+        assert_eq!(found, [NOCASE_CHUNK - 2, 2 * NOCASE_CHUNK - 6]);
+        assert!(find_text(&data, b"powers", false, false).is_empty());
+
+        let count = rule(r#"$a = "powers" nocase"#, "#a == 2");
+        assert!(hits(&count, &data));
+    }
+
+    #[test]
+    fn conditions() {
+        let strings = r#"$a = "ab" $b = "zz""#;
+        let data = b"ab..ab..ab";
+
+        assert!(hits(&rule(strings, "#a == 3"), data));
+        assert!(!hits(&rule(strings, "#a > 3"), data));
+        assert!(hits(&rule(strings, "$a at 4"), data));
+        assert!(!hits(&rule(strings, "$a at 1"), data));
+        assert!(hits(&rule(strings, "$a in (5..8)"), data));
+        assert!(!hits(&rule(strings, "$a in (1..3)"), data));
+        assert!(hits(&rule(strings, "any of them"), data));
+        assert!(!hits(&rule(strings, "all of them"), data));
+        assert!(hits(&rule(strings, "1 of ($a, $b)"), data));
+        assert!(hits(&rule(strings, "$a and not $b"), data));
+        assert!(hits(&rule(strings, "($b or $a) and filesize < 1KB"), data));
+        assert!(!hits(&rule(strings, "filesize > 10"), data));
+
+        let header = rule(
+            r#"$a = "x""#,
+            "uint16(0) == 0x5A4D and uint32be(0) == 0x4D5A9000",
+        );
+        assert!(hits(&header, b"MZ\x90\x00x"));
+        assert!(!hits(&header, b"MZ"));
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(compile_source("rule T { condition: $a }").is_err());
+        assert!(compile_source(r#"rule T { strings: $a = "x" }"#).is_err());
+        assert!(compile_source(r#"rule T { strings: $a = "x" condition: $a and }"#).is_err());
+        assert!(compile_source("rule { condition: true }").is_err());
+        assert!(compile_source(BUILTIN_SIGNATURES).is_ok());
+    }
+}
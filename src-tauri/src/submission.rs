@@ -0,0 +1,383 @@
+// Opt-in upload of files the threat API has never seen ("unknown" verdict).
+//
+// Uploads only happen after the user consented in Settings. Every accepted
+// upload is remembered in `submissions.json` and re-checked in the background
+// until the backend hands out a final verdict.
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use reqwest::blocking::multipart::{Form, Part};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Emitter};
+use tauri_plugin_notification::NotificationExt;
+
+use crate::{
+    app_data_dir, build_client_payload, call_threat_api_batch, check_session, cloud_detection,
+    http_client, load_runtime_config, notify_api_error_state, onaccess, pinning,
+    save_runtime_config, session_token, DetectionRecord, RealtimeThreatPayload,
+    ScanFinishedPayload, ThreatApiFile, API_BASE_URL,
+};
+
+const API_SAMPLE_SUBMIT_PATH: &str = "/api/av/v1/sample/submit";
+
+const DEFAULT_MAX_SUBMISSION_BYTES: u64 = 10 * 1024 * 1024; // 10 MB
+const RECHECK_INTERVAL: Duration = Duration::from_secs(30 * 60);
+const SUBMISSION_MAX_AGE_SECS: u64 = 14 * 24 * 60 * 60; // give up after 14 days
+
+// Extensions worth uploading: native executables, installers and scripts.
+const SUBMITTABLE_EXTENSIONS: &[&str] = &[
+    "exe", "dll", "scr", "sys", "msi", "com", "cpl", "bat", "cmd", "ps1", "psm1", "vbs", "vbe",
+    "js", "jse", "wsf", "hta", "jar", "sh", "bash", "zsh", "command", "py", "pl", "rb", "php",
+    "so", "dylib", "elf", "bin", "run", "appimage", "deb", "rpm", "pkg", "dmg", "apk",
+];
+
+// Serializes read-modify-write of the pending list (watcher, scans, rechecker).
+// Holds the hashes being uploaded right now, so two workers that meet the same
+// unknown file don't both send it.
+static PENDING_LOCK: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+// ---- Config / state ----
+
+#[derive(Serialize, Deserialize, Clone)]
+pub(crate) struct SampleSubmissionConfig {
+    enabled: bool,
+    /// Unix seconds of when the user agreed to upload samples.
+    consent_at: Option<u64>,
+    max_file_bytes: u64,
+}
+
+impl Default for SampleSubmissionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            consent_at: None,
+            max_file_bytes: DEFAULT_MAX_SUBMISSION_BYTES,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub(crate) struct PendingSubmission {
+    submission_id: String,
+    sha256: String,
+    path: String,
+    submitted_at: u64,
+}
+
+#[derive(Deserialize)]
+struct SubmitResponse {
+    submission_id: String,
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn submissions_path() -> PathBuf {
+    app_data_dir().join("submissions.json")
+}
+
+fn load_pending() -> Vec<PendingSubmission> {
+    fs::read(submissions_path())
+        .ok()
+        .and_then(|b| serde_json::from_slice(&b).ok())
+        .unwrap_or_default()
+}
+
+fn save_pending(items: &[PendingSubmission]) {
+    let p = submissions_path();
+    if let Some(parent) = p.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    if let Ok(json) = serde_json::to_vec_pretty(items) {
+        let _ = fs::write(p, json);
+    }
+}
+
+// ---- Upload ----
+
+fn is_submittable_type(path: &Path) -> bool {
+    let ext = path
+        .extension()
+        .and_then(|s| s.to_str())
+        .map(|s| s.to_lowercase());
+
+    match ext {
+        Some(ext) => SUBMITTABLE_EXTENSIONS.contains(&ext.as_str()),
+        None => is_executable_mode(path),
+    }
+}
+
+#[cfg(unix)]
+fn is_executable_mode(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(path)
+        .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable_mode(_path: &Path) -> bool {
+    false
+}
+
+fn upload_sample(
+    path: &Path,
+    bytes: Vec<u8>,
+    sha256: &str,
+    consent_at: u64,
+) -> Result<String, String> {
+    let url = format!("{}{}", API_BASE_URL, API_SAMPLE_SUBMIT_PATH);
+    let client = http_client()?;
+
+    let file_name = path
+        .file_name()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "sample.bin".to_string());
+
+    let client_json = serde_json::to_string(&build_client_payload())
+        .map_err(|e| format!("Failed to serialize client info: {e}"))?;
+
+    let form = Form::new()
+        .text("sha256", sha256.to_string())
+        .text("consent_at", consent_at.to_string())
+        .text("client", client_json)
+        .part(
+            "file",
+            Part::bytes(bytes)
+                .file_name(file_name)
+                .mime_str("application/octet-stream")
+                .map_err(|e| format!("Failed to build upload: {e}"))?,
+        );
+
+    let token = session_token();
+    let mut builder = client.post(&url).multipart(form);
+    if let Some(t) = &token {
+        builder = builder.bearer_auth(t);
+    }
+
+    let resp = builder.send().map_err(|e| {
+        pinning::pin_failure(&e).unwrap_or_else(|| format!("Sample upload error: {e}"))
+    })?;
+
+    let status = resp.status();
+    check_session(status, token.is_some())?;
+    if !status.is_success() {
+        return Err(format!("Sample upload returned HTTP {status}"));
+    }
+
+    let parsed: SubmitResponse = resp
+        .json()
+        .map_err(|e| format!("Failed to parse sample upload response: {e}"))?;
+
+    Ok(parsed.submission_id)
+}
+
+/// Reads the file for upload, but only if it is still the content that got the
+/// "unknown" verdict; a file rewritten since would be submitted under a wrong hash.
+fn read_sample(path: &Path, sha256: &str, max_bytes: u64) -> Option<Vec<u8>> {
+    let bytes = fs::read(path).ok()?;
+    if bytes.is_empty() || bytes.len() as u64 > max_bytes {
+        return None;
+    }
+    (hex::encode(Sha256::digest(&bytes)) == sha256).then_some(bytes)
+}
+
+/// Uploads `path` if the user opted in and the file qualifies. Never fails loudly:
+/// submission is best effort and must not break scans or the watcher.
+pub(crate) fn maybe_submit_unknown(app: &AppHandle, path: &Path, sha256: &str) {
+    let cfg = load_runtime_config().sample_submission;
+    let consent_at = match (cfg.enabled, cfg.consent_at) {
+        (true, Some(t)) => t,
+        _ => return,
+    };
+
+    if !is_submittable_type(path) {
+        return;
+    }
+
+    match fs::metadata(path) {
+        Ok(m) if m.is_file() && m.len() > 0 && m.len() <= cfg.max_file_bytes => {}
+        _ => return,
+    }
+
+    // Reserve the hash before uploading; it is released once the outcome is saved
+    let sha256 = sha256.to_lowercase();
+    {
+        let Ok(mut uploading) = PENDING_LOCK.lock() else {
+            return;
+        };
+        if uploading.contains(&sha256) || load_pending().iter().any(|p| p.sha256 == sha256) {
+            return;
+        }
+        uploading.push(sha256.clone());
+    }
+
+    let result = match read_sample(path, &sha256, cfg.max_file_bytes) {
+        Some(bytes) => upload_sample(path, bytes, &sha256, consent_at),
+        None => Err("file changed since it was scanned".to_string()),
+    };
+
+    let Ok(mut uploading) = PENDING_LOCK.lock() else {
+        return;
+    };
+    uploading.retain(|h| h != &sha256);
+
+    match result {
+        Ok(id) => {
+            println!("[SUBMIT] uploaded {:?} sha256={} id={}", path, sha256, id);
+
+            let mut pending = load_pending();
+            pending.push(PendingSubmission {
+                submission_id: id,
+                sha256,
+                path: path.to_string_lossy().to_string(),
+                submitted_at: now_secs(),
+            });
+            save_pending(&pending);
+        }
+        Err(e) => {
+            eprintln!("[SUBMIT] upload failed for {:?}: {e}", path);
+            drop(uploading);
+            notify_api_error_state(app, &e);
+        }
+    }
+}
+
+// ---- Re-check ----
+
+fn recheck_pending(app: &AppHandle) {
+    let pending = load_pending();
+    if pending.is_empty() {
+        return;
+    }
+
+    let files: Vec<ThreatApiFile> = pending
+        .iter()
+        .map(|p| ThreatApiFile {
+            sha256: p.sha256.clone(),
+            size: None,
+            extension: None,
+            file_type: None,
+            pe: None,
+            md5: None,
+            sha1: None,
+            ssdeep: None,
+            tlsh: None,
+        })
+        .collect();
+
+    let results = match call_threat_api_batch(files) {
+        Ok(r) => r,
+        Err(e) => {
+            eprintln!("[SUBMIT] re-check failed: {e}");
+            notify_api_error_state(app, &e);
+            return;
+        }
+    };
+
+    let verdicts: HashMap<String, _> = results
+        .into_iter()
+        .map(|r| (r.sha256.to_lowercase(), r))
+        .collect();
+
+    let mut detections: Vec<DetectionRecord> = Vec::new();
+    let mut resolved: Vec<String> = Vec::new();
+    let now = now_secs();
+
+    for p in &pending {
+        let Some(r) = verdicts.get(&p.sha256) else {
+            continue;
+        };
+
+        let verdict = r.verdict.to_lowercase();
+        if verdict == "unknown" {
+            if now.saturating_sub(p.submitted_at) > SUBMISSION_MAX_AGE_SECS {
+                resolved.push(p.submission_id.clone());
+            }
+            continue;
+        }
+
+        println!(
+            "[SUBMIT] submission {} resolved: verdict={}",
+            p.submission_id, verdict
+        );
+        resolved.push(p.submission_id.clone());
+
+        // Only alert if the file is still where we found it
+        if verdict != "clean" && Path::new(&p.path).exists() {
+            detections.push(cloud_detection(r, &p.path, None));
+        }
+    }
+
+    if !resolved.is_empty() {
+        let _guard = PENDING_LOCK.lock();
+        let mut current = load_pending();
+        current.retain(|p| !resolved.contains(&p.submission_id));
+        save_pending(&current);
+    }
+
+    if !detections.is_empty() {
+        let count = detections.len();
+        let blocked = onaccess::remember(&detections);
+        let _ = app.emit(
+            "realtime_threat_detected",
+            RealtimeThreatPayload {
+                scan: ScanFinishedPayload::from_detections(detections),
+                blocked,
+            },
+        );
+
+        let _ = app
+            .notification()
+            .builder()
+            .title("Stellar Antivirus")
+            .body(format!(
+                "{count} previously unknown file(s) are now classified as threats."
+            ))
+            .show();
+    }
+}
+
+pub(crate) fn start_submission_rechecker(app: AppHandle) {
+    thread::spawn(move || loop {
+        thread::sleep(RECHECK_INTERVAL);
+        recheck_pending(&app);
+    });
+}
+
+// ---- Commands ----
+
+#[tauri::command]
+pub(crate) fn get_sample_submission_config() -> SampleSubmissionConfig {
+    load_runtime_config().sample_submission
+}
+
+#[tauri::command]
+pub(crate) fn set_sample_submission_consent(enabled: bool, max_file_bytes: Option<u64>) {
+    let mut cfg = load_runtime_config();
+    cfg.sample_submission.enabled = enabled;
+    cfg.sample_submission.consent_at = if enabled { Some(now_secs()) } else { None };
+    if let Some(limit) = max_file_bytes {
+        cfg.sample_submission.max_file_bytes = limit;
+    }
+    save_runtime_config(&cfg);
+
+    println!("Sample submission set to: {enabled}");
+}
+
+#[tauri::command]
+pub(crate) fn get_pending_submissions() -> Vec<PendingSubmission> {
+    load_pending()
+}
@@ -0,0 +1,396 @@
+// TLSH (Trend Micro locality sensitive hash), the standard 128-bucket variant
+// with a one-byte checksum, written as "T1" plus 70 hex characters.
+//
+// ssdeep compares the order of content and drops to zero once a variant is
+// reshuffled; TLSH summarises how byte triplets are distributed, so it keeps
+// recognising variants with moved or padded sections. Distances run from 0
+// (same distribution) into the hundreds; unlike ssdeep similarity, lower is
+// closer.
+
+const WINDOW: usize = 5;
+const BUCKETS: usize = 256;
+const EFF_BUCKETS: usize = 128;
+const CODE_SIZE: usize = EFF_BUCKETS / 4;
+// Shorter input has too few triplets to fill the buckets
+pub(crate) const MIN_DATA_LENGTH: u64 = 50;
+const DIGEST_BYTES: usize = 3 + CODE_SIZE; // checksum, length, quartile ratios, body
+const VERSION_PREFIX: &str = "T1";
+
+// Pearson permutation (RFC 3074), as used by the reference implementation
+const V_TABLE: [u8; 256] = [
+    1, 87, 49, 12, 176, 178, 102, 166, 121, 193, 6, 84, 249, 230, 44, 163, 14, 197, 213, 181, 161,
+    85, 218, 80, 64, 239, 24, 226, 236, 142, 38, 200, 110, 177, 104, 103, 141, 253, 255, 50, 77,
+    101, 81, 18, 45, 96, 31, 222, 25, 107, 190, 70, 86, 237, 240, 34, 72, 242, 20, 214, 244, 227,
+    149, 235, 97, 234, 57, 22, 60, 250, 82, 175, 208, 5, 127, 199, 111, 62, 135, 248, 174, 169,
+    211, 58, 66, 154, 106, 195, 245, 171, 17, 187, 182, 179, 0, 243, 132, 56, 148, 75, 128, 133,
+    158, 100, 130, 126, 91, 13, 153, 246, 216, 219, 119, 68, 223, 78, 83, 88, 201, 99, 122, 11, 92,
+    32, 136, 114, 52, 10, 138, 30, 48, 183, 156, 35, 61, 26, 143, 74, 251, 94, 129, 162, 63, 152,
+    170, 7, 115, 167, 241, 206, 3, 150, 55, 59, 151, 220, 90, 53, 23, 131, 125, 173, 15, 238, 79,
+    95, 89, 16, 105, 137, 225, 224, 217, 160, 37, 123, 118, 73, 2, 157, 46, 116, 9, 145, 134, 228,
+    207, 212, 202, 215, 69, 229, 27, 188, 67, 124, 168, 252, 42, 4, 29, 108, 21, 247, 19, 205, 39,
+    203, 233, 40, 186, 147, 198, 192, 155, 33, 164, 191, 98, 204, 165, 180, 117, 76, 140, 36, 210,
+    172, 41, 54, 159, 8, 185, 232, 113, 196, 231, 47, 146, 120, 51, 65, 28, 144, 254, 221, 93, 189,
+    194, 139, 112, 43, 71, 109, 184, 209,
+];
+
+// ---- Hashing ----
+
+fn pearson(salt: u8, a: u8, b: u8, c: u8) -> u8 {
+    let mut h = V_TABLE[salt as usize];
+    h = V_TABLE[(h ^ a) as usize];
+    h = V_TABLE[(h ^ b) as usize];
+    V_TABLE[(h ^ c) as usize]
+}
+
+/// Log-scale bucket of the input length, one byte in the digest.
+fn length_code(len: u64) -> u8 {
+    const LOG_1_5: f64 = 0.405_465_1;
+    const LOG_1_3: f64 = 0.262_364_26;
+    const LOG_1_1: f64 = 0.095_310_18;
+
+    // The reference computes on a float length
+    let log = (len as f32 as f64).ln();
+    let code = if len <= 656 {
+        (log / LOG_1_5).floor()
+    } else if len <= 3199 {
+        (log / LOG_1_3 - 8.727_77).floor()
+    } else {
+        (log / LOG_1_1 - 62.547_2).floor()
+    };
+    (code as i64 & 0xFF) as u8
+}
+
+fn swap_nibbles(b: u8) -> u8 {
+    b.rotate_left(4)
+}
+
+/// Streaming TLSH; feed it the same chunks as the other digests.
+pub(crate) struct TlshHasher {
+    window: [u8; WINDOW],
+    buckets: Box<[u32; BUCKETS]>,
+    checksum: u8,
+    len: u64,
+}
+
+impl TlshHasher {
+    pub(crate) fn new() -> Self {
+        TlshHasher {
+            window: [0; WINDOW],
+            buckets: Box::new([0; BUCKETS]),
+            checksum: 0,
+            len: 0,
+        }
+    }
+
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        for &c in data {
+            let j = (self.len % WINDOW as u64) as usize;
+            self.window[j] = c;
+
+            if self.len >= WINDOW as u64 - 1 {
+                let back = |n: usize| self.window[(j + WINDOW - n) % WINDOW];
+                let (w1, w2, w3, w4) = (back(1), back(2), back(3), back(4));
+
+                self.checksum = pearson(0, c, w1, self.checksum);
+                for (salt, x, y) in [
+                    (2, w1, w2),
+                    (3, w1, w3),
+                    (5, w2, w3),
+                    (7, w2, w4),
+                    (11, w1, w4),
+                    (13, w3, w4),
+                ] {
+                    self.buckets[pearson(salt, c, x, y) as usize] += 1;
+                }
+            }
+            self.len += 1;
+        }
+    }
+
+    /// The "T1..." digest, or None when the input is too short or too uniform
+    /// (half the buckets or more empty) to say anything about.
+    pub(crate) fn finish(self) -> Option<String> {
+        if self.len < MIN_DATA_LENGTH || self.len > u64::from(u32::MAX) {
+            return None;
+        }
+
+        let counts = &self.buckets[..EFF_BUCKETS];
+        if counts.iter().filter(|&&n| n > 0).count() <= EFF_BUCKETS / 2 {
+            return None;
+        }
+        let mut sorted = counts.to_vec();
+        sorted.sort_unstable();
+        let (q1, q2, q3) = (
+            sorted[EFF_BUCKETS / 4 - 1],
+            sorted[EFF_BUCKETS / 2 - 1],
+            sorted[EFF_BUCKETS - EFF_BUCKETS / 4 - 1],
+        );
+
+        // Two bits per bucket: which quartile its count falls in
+        let mut code = [0u8; CODE_SIZE];
+        for (byte, quad) in code.iter_mut().zip(counts.chunks(4)) {
+            for (j, &n) in quad.iter().enumerate() {
+                let level = if n > q3 {
+                    3
+                } else if n > q2 {
+                    2
+                } else if n > q1 {
+                    1
+                } else {
+                    0
+                };
+                *byte |= level << (j * 2);
+            }
+        }
+
+        // q3 > 0 here: more than half the buckets are non-zero
+        let ratio = |q: u32| (q.wrapping_mul(100) as f32 / q3 as f32) as u32 % 16;
+        let (q1_ratio, q2_ratio) = (ratio(q1) as u8, ratio(q2) as u8);
+
+        let mut digest = Vec::with_capacity(DIGEST_BYTES);
+        digest.push(swap_nibbles(self.checksum));
+        digest.push(swap_nibbles(length_code(self.len)));
+        digest.push((q1_ratio << 4) | q2_ratio);
+        digest.extend(code.iter().rev());
+
+        Some(format!("{VERSION_PREFIX}{}", hex::encode_upper(digest)))
+    }
+}
+
+// ---- Comparison ----
+
+struct Parsed {
+    checksum: u8,
+    length: u8,
+    q1_ratio: u8,
+    q2_ratio: u8,
+    code: [u8; CODE_SIZE],
+}
+
+/// Accepts digests with or without the "T1" prefix, in either case.
+fn parse(digest: &str) -> Option<Parsed> {
+    let digest = digest.trim();
+    let hex_part = digest
+        .strip_prefix(VERSION_PREFIX)
+        .or_else(|| digest.strip_prefix("t1"))
+        .unwrap_or(digest);
+    let bytes = hex::decode(hex_part).ok()?;
+    if bytes.len() != DIGEST_BYTES {
+        return None;
+    }
+
+    Some(Parsed {
+        checksum: bytes[0],
+        length: swap_nibbles(bytes[1]),
+        q1_ratio: bytes[2] >> 4,
+        q2_ratio: bytes[2] & 0x0F,
+        code: bytes[3..].try_into().ok()?,
+    })
+}
+
+pub(crate) fn is_valid(digest: &str) -> bool {
+    parse(digest).is_some()
+}
+
+/// Distance on a ring of `range` values.
+fn mod_diff(a: u8, b: u8, range: u32) -> u32 {
+    let d = u32::from(a.abs_diff(b));
+    d.min(range - d)
+}
+
+/// Quartile levels differ by 0-3 per bucket; opposite extremes weigh double.
+fn code_distance(a: &[u8; CODE_SIZE], b: &[u8; CODE_SIZE]) -> u32 {
+    a.iter()
+        .zip(b)
+        .map(|(x, y)| {
+            (0..4)
+                .map(|j| {
+                    let d = ((x >> (j * 2)) & 3).abs_diff((y >> (j * 2)) & 3);
+                    if d == 3 {
+                        6
+                    } else {
+                        u32::from(d)
+                    }
+                })
+                .sum::<u32>()
+        })
+        .sum()
+}
+
+/// TLSH distance including the length term; None if either digest is malformed.
+pub(crate) fn distance(a: &str, b: &str) -> Option<u32> {
+    let (a, b) = (parse(a)?, parse(b)?);
+
+    let mut diff = match mod_diff(a.length, b.length, 256) {
+        d @ (0 | 1) => d,
+        d => d * 12,
+    };
+    for (x, y) in [(a.q1_ratio, b.q1_ratio), (a.q2_ratio, b.q2_ratio)] {
+        diff += match mod_diff(x, y, 16) {
+            d @ (0 | 1) => d,
+            d => (d - 1) * 12,
+        };
+    }
+    if a.checksum != b.checksum {
+        diff += 1;
+    }
+    Some(diff + code_distance(&a.code, &b.code))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn digest(data: &[u8]) -> Option<String> {
+        let mut h = TlshHasher::new();
+        h.update(data);
+        h.finish()
+    }
+
+    // Deterministic xorshift bytes
+    fn noise(len: usize, seed: u32) -> Vec<u8> {
+        let mut x = seed;
+        (0..len)
+            .map(|_| {
+                x ^= x << 13;
+                x ^= x >> 17;
+                x ^= x << 5;
+                x as u8
+            })
+            .collect()
+    }
+
+    // Text-like input with a skewed byte distribution, as real files have
+    fn prose(len: usize, seed: u32) -> Vec<u8> {
+        const WORDS: &[&str] = &[
+            "the ", "file ", "scanner ", "reads ", "every ", "byte ", "once ", "and ", "hashes ",
+            "it ", "for ", "known ", "samples ", "\n",
+        ];
+        noise(len, seed)
+            .iter()
+            .flat_map(|b| WORDS[*b as usize % WORDS.len()].bytes())
+            .take(len)
+            .collect()
+    }
+
+    #[test]
+    fn digest_has_the_standard_shape() {
+        let d = digest(&prose(10_000, 7)).unwrap();
+        assert_eq!(d.len(), VERSION_PREFIX.len() + DIGEST_BYTES * 2, "{d}");
+        assert!(d.starts_with("T1"));
+        assert!(d[2..]
+            .chars()
+            .all(|c| c.is_ascii_digit() || ('A'..='F').contains(&c)));
+        assert!(is_valid(&d));
+        assert!(is_valid(&d[2..]));
+        assert!(is_valid(&d.to_lowercase()));
+    }
+
+    #[test]
+    fn short_or_uniform_input_has_no_digest() {
+        assert_eq!(digest(b""), None);
+        assert_eq!(digest(&prose(49, 1)), None);
+        assert_eq!(digest(&[0u8; 100_000]), None);
+        assert_eq!(digest(&b"ab".repeat(50_000)), None);
+        assert!(digest(&noise(MIN_DATA_LENGTH as usize * 8, 1)).is_some());
+    }
+
+    #[test]
+    fn streaming_matches_one_shot() {
+        let data = noise(200_000, 3);
+        let mut h = TlshHasher::new();
+        for chunk in data.chunks(4093) {
+            h.update(chunk);
+        }
+        assert_eq!(h.finish(), digest(&data));
+    }
+
+    #[test]
+    fn length_code_steps_through_the_three_scales() {
+        let cases: &[(u64, u8)] = &[
+            (50, 9),
+            (656, 15),
+            (657, 16),
+            (3199, 22),
+            (3200, 22),
+            (1 << 20, 82),
+            (u64::from(u32::MAX), 170),
+        ];
+        for &(len, code) in cases {
+            assert_eq!(length_code(len), code, "{len}");
+        }
+    }
+
+    #[test]
+    fn distance_tracks_how_much_changed() {
+        let original = prose(64 * 1024, 11);
+        let a = digest(&original).unwrap();
+        assert_eq!(distance(&a, &a), Some(0));
+
+        // A patched copy stays close; symmetry holds
+        let mut patched = original.clone();
+        patched[1000..1400].copy_from_slice(&noise(400, 5));
+        let b = digest(&patched).unwrap();
+        let near = distance(&a, &b).unwrap();
+        assert_eq!(distance(&b, &a), Some(near));
+        assert!(near <= 30, "patched copy at {near}");
+
+        // Unrelated content is far away
+        let c = digest(&noise(64 * 1024, 99)).unwrap();
+        let far = distance(&a, &c).unwrap();
+        assert!(far > 100, "unrelated content at {far}");
+    }
+
+    #[test]
+    fn distance_terms() {
+        let base = digest(&prose(20_000, 2)).unwrap();
+        let bytes = hex::decode(&base[2..]).unwrap();
+        let with = |i: usize, b: u8| {
+            let mut v = bytes.clone();
+            v[i] = b;
+            format!("T1{}", hex::encode_upper(v))
+        };
+
+        // Checksum mismatch costs 1
+        assert_eq!(distance(&base, &with(0, bytes[0] ^ 0xFF)), Some(1));
+        // Length: one step costs 1, further steps 12 each, wrapping at 256
+        let length = swap_nibbles(bytes[1]);
+        let length_at = |l: u8| with(1, swap_nibbles(l));
+        assert_eq!(distance(&base, &length_at(length.wrapping_add(1))), Some(1));
+        assert_eq!(
+            distance(&base, &length_at(length.wrapping_add(3))),
+            Some(36)
+        );
+        assert_eq!(
+            distance(&base, &length_at(length.wrapping_sub(3))),
+            Some(36)
+        );
+        // Quartile ratios wrap at 16; steps past the first cost 12
+        let q1 = bytes[2] >> 4;
+        let q1_at = |q: u8| with(2, (q << 4) | (bytes[2] & 0x0F));
+        assert_eq!(distance(&base, &q1_at((q1 + 1) % 16)), Some(1));
+        assert_eq!(distance(&base, &q1_at((q1 + 3) % 16)), Some(24));
+        // Body: a bucket jumping between extremes counts 6, 1 <-> 2 counts 1
+        let flipped = with(3, bytes[3] ^ 0b11);
+        let expected = match bytes[3] & 0b11 {
+            0 | 3 => 6,
+            _ => 1,
+        };
+        assert_eq!(distance(&base, &flipped), Some(expected));
+    }
+
+    #[test]
+    fn malformed_digests_do_not_compare() {
+        let d = digest(&prose(10_000, 4)).unwrap();
+        for bad in [
+            "",
+            "T1",
+            "not a digest",
+            &d[..d.len() - 2],
+            &format!("{d}00"),
+        ] {
+            assert_eq!(distance(&d, bad), None, "{bad}");
+            assert!(!is_valid(bad));
+        }
+    }
+}
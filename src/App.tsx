@@ -131,6 +131,46 @@ const baseNameFromPath = (p: string): string => {
   return last && last.length > 0 ? last : normalized;
 };
 
+// Backend findings -> UI rows. Suspicious files (heuristics only) are listed
+// for review, never with a delete/quarantine recommendation.
+const mapFindings = (
+    payload: any,
+    action: "delete" | "quarantine",
+    source: "full_scan" | "realtime",
+    nowIso: string
+): Threat[] => {
+  const threats = (payload.threats as [string, string][]) || [];
+  const suspicious = (payload.suspicious as [string, string][]) || [];
+  const rows = [
+    ...threats.map(([detection, filePath]) => ({ detection, filePath, recommendedAction: action })),
+    ...suspicious.map(([detection, filePath]) => ({
+      detection: `${detection} (suspicious)`,
+      filePath,
+      recommendedAction: "review",
+    })),
+  ];
+
+  return rows.map((r, idx): Threat => ({
+    id: Date.now() + idx,
+    fileName: baseNameFromPath(r.filePath),
+    filePath: r.filePath,
+    detection: r.detection,
+    recommendedAction: r.recommendedAction,
+    detectedAt: nowIso,
+    source,
+    status: "active",
+  }));
+};
+
+// "2 threats", or "1 suspicious file" when nothing is a confirmed threat
+const describeFindings = (payload: any): string => {
+  const threats = ((payload.threats as unknown[]) || []).length;
+  const suspicious = ((payload.suspicious as unknown[]) || []).length;
+  return threats > 0
+      ? `${threats} threat${threats === 1 ? "" : "s"}`
+      : `${suspicious} suspicious file${suspicious === 1 ? "" : "s"}`;
+};
+
 // Optional: avoid duplicate log spam within same minute for same scan_type/result/details
 const pushLogDedup = (prev: ScanLogEntry[], entry: ScanLogEntry) => {
   const last = prev[0];
@@ -185,6 +225,12 @@ const App: React.FC = () => {
 
   const scanIntervalRef = useRef<ReturnType<typeof setInterval> | null>(null);
 
+  // Set when the threat API answer failed its pin or signature check; the
+  // verdicts were discarded, so a scan must not read as "no threats found".
+  // Cleared when the next scan starts.
+  const [resultsUnverified, setResultsUnverified] = useState(false);
+  const resultsUnverifiedRef = useRef(false);
+
   const [showRealtimeConfirm, setShowRealtimeConfirm] = useState(false);
 
   const [showThreatsModal, setShowThreatsModal] = useState(false);
@@ -304,31 +350,20 @@ const App: React.FC = () => {
       const scanLabel =
           activeScanRef.current === "quick" ? "Quick scan" : "Full scan";
 
-      if (threatsArray.length > 0) {
-        const nowIso = now.toISOString();
-        const mapped: Threat[] = threatsArray.map((t, idx) => {
-          const detection = t[0];
-          const filePath = t[1];
-
-          return {
-            id: Date.now() + idx,
-            fileName: baseNameFromPath(filePath),
-            filePath,
-            detection,
-            recommendedAction: "delete",
-            detectedAt: nowIso,
-            source: "full_scan",
-            status: "active",
-          };
-        });
+      const mapped = mapFindings(payload, "delete", "full_scan", now.toISOString());
+
+      if (mapped.length > 0) {
+        const found = describeFindings(payload);
 
         setStatus("at_risk");
         setThreats((prev) => mergeThreatsByPath(prev, mapped));
         setShowThreatsModal(true);
 
         showNotification(
-            "Stellar Antivirus – threats found",
-            `${mapped.length} threat${mapped.length === 1 ? "" : "s"} detected during ${scanLabel.toLowerCase()}.`
+            threatsArray.length > 0
+                ? "Stellar Antivirus – threats found"
+                : "Stellar Antivirus – suspicious files found",
+            `${found} detected during ${scanLabel.toLowerCase()}.`
         );
 
         setLogs((prev) =>
@@ -337,11 +372,16 @@ const App: React.FC = () => {
               timestamp: ts,
               scan_type: "full_scan",
               result: "threats_found",
-              details: `${scanLabel} found ${mapped.length} threat${
-                  mapped.length === 1 ? "" : "s"
-              }.`,
+              details: `${scanLabel} found ${found}.`,
             })
         );
+      } else if (resultsUnverifiedRef.current) {
+        setStatus("at_risk");
+
+        showNotification(
+            "Stellar Antivirus – results could not be verified",
+            `${scanLabel} finished, but the threat check failed verification. Run the scan again later.`
+        );
       } else {
         setStatus(realtimeEnabled ? "protected" : "not_protected");
 
@@ -381,38 +421,34 @@ const App: React.FC = () => {
     if (!isTauri) return;
 
     let unlistenRealtime: UnlistenFn | null = null;
+    let unlistenRansomware: UnlistenFn | null = null;
 
     listen("realtime_threat_detected", (event) => {
       const payload = event.payload as any;
-      const threatsArray = (payload.threats as [string, string][]) || [];
-      if (!threatsArray.length) return;
-
       const now = new Date();
       const nowIso = now.toISOString();
       const ts = nowIso.slice(0, 16).replace("T", " ");
 
-      const mapped: Threat[] = threatsArray.map((t, idx) => {
-        const detection = t[0];
-        const filePath = t[1];
-
-        return {
-          id: Date.now() + idx,
-          fileName: baseNameFromPath(filePath),
-          filePath,
-          detection,
-          recommendedAction: "quarantine",
-          detectedAt: nowIso,
-          source: "realtime",
-          status: "active",
-        };
-      });
+      const mapped = mapFindings(payload, "quarantine", "realtime", nowIso);
+      if (!mapped.length) return;
 
       setThreats((prev) => mergeThreatsByPath(prev, mapped));
       setShowThreatsModal(true);
 
+      // Only on-access mode stops files; otherwise they were just observed
+      const blocked = payload.blocked === true;
+      const count = describeFindings(payload);
+      const hasThreats = ((payload.threats as unknown[]) || []).length > 0;
+
       showNotification(
-          "Stellar Antivirus – threat blocked",
-          `${mapped.length} threat${mapped.length === 1 ? "" : "s"} blocked in real-time.`
+          blocked
+              ? "Stellar Antivirus – threat blocked"
+              : hasThreats
+                  ? "Stellar Antivirus – threat detected"
+                  : "Stellar Antivirus – suspicious file",
+          blocked
+              ? `${count} blocked in real-time.`
+              : `${count} detected in real-time.`
       );
 
       setLogs((prev) =>
@@ -421,9 +457,9 @@ const App: React.FC = () => {
             timestamp: ts,
             scan_type: "realtime",
             result: "threats_found",
-            details: `Real-time protection blocked ${mapped.length} threat${
-                mapped.length === 1 ? "" : "s"
-            }.`,
+            details: blocked
+                ? `Real-time protection blocked ${count}.`
+                : `Real-time protection detected ${count}.`,
           })
       );
 
@@ -432,8 +468,36 @@ const App: React.FC = () => {
       unlistenRealtime = fn;
     });
 
+    // Behaviour alerts name the files being damaged; those are the user's own
+    // files, so they are logged rather than offered for quarantine. A tripped
+    // decoy (canary) is restored by the backend and only named in the log.
+    listen("ransomware_activity", (event) => {
+      const payload = event.payload as any;
+      const paths = (payload.paths as string[]) || [];
+      const ts = new Date().toISOString().slice(0, 16).replace("T", " ");
+      const cause = payload.process?.exe ? ` Likely cause: ${payload.process.exe}.` : "";
+      const affected = payload.canary
+          ? ""
+          : ` (${paths.length} file${paths.length === 1 ? "" : "s"} affected)`;
+
+      setLogs((prev) =>
+          pushLogDedup(prev, {
+            id: prev.length + 1,
+            timestamp: ts,
+            scan_type: "realtime",
+            result: "threats_found",
+            details: `Possible ransomware in ${payload.directory}: ${payload.reason}${affected}.${cause}`,
+          })
+      );
+
+      setStatus("at_risk");
+    }).then((fn) => {
+      unlistenRansomware = fn;
+    });
+
     return () => {
       if (unlistenRealtime) unlistenRealtime();
+      if (unlistenRansomware) unlistenRansomware();
     };
   }, []);
 
@@ -445,6 +509,8 @@ const App: React.FC = () => {
     }
 
     activeScanRef.current = "full";
+    resultsUnverifiedRef.current = false;
+    setResultsUnverified(false);
 
     if (!isTauri) {
       // Browser demo fallback
@@ -502,6 +568,8 @@ const App: React.FC = () => {
     }
 
     activeScanRef.current = "quick";
+    resultsUnverifiedRef.current = false;
+    setResultsUnverified(false);
 
     if (!isTauri) {
       // Browser demo fallback (faster)
@@ -655,7 +723,9 @@ const App: React.FC = () => {
     });
   };
 
-  const handleRestoreQuarantine = async (id: number) => {
+  // With `falsePositive`, the restored file is also allowlisted and reported,
+  // so later scans and on-access checks leave it alone.
+  const handleRestoreQuarantine = async (id: number, falsePositive = false) => {
     const entry = quarantine.find((q) => q.id === id);
     if (!entry) return;
 
@@ -692,8 +762,44 @@ const App: React.FC = () => {
           details: `Restored file from quarantine: ${entry.fileName}`,
         })
     );
+
+    if (!falsePositive || !isTauri) return;
+
+    // The quarantine list doesn't keep hashes; the backend hashes the restored file
+    try {
+      const reported = (await invoke("report_false_positive", {
+        hash: "",
+        path: entry.originalPath,
+        note: entry.detection || null,
+      })) as { reported?: boolean };
+
+      setLogs((prev) =>
+          pushLogDedup(prev, {
+            id: prev.length + 1,
+            timestamp: ts,
+            scan_type: "realtime",
+            result: "clean",
+            details: reported?.reported
+                ? `Reported as false positive: ${entry.fileName}`
+                : `Allowlisted locally: ${entry.fileName} (report could not be sent)`,
+          })
+      );
+    } catch (err) {
+      console.error("False-positive report error:", err);
+      setLogs((prev) =>
+          pushLogDedup(prev, {
+            id: prev.length + 1,
+            timestamp: ts,
+            scan_type: "realtime",
+            result: "clean",
+            details: `Failed to mark as false positive: ${entry.fileName}`,
+          })
+      );
+    }
   };
 
+  const handleReportFalsePositive = (id: number) => handleRestoreQuarantine(id, true);
+
   const handleDeleteQuarantine = async (id: number) => {
     const entry = quarantine.find((q) => q.id === id);
     if (!entry) return;
@@ -771,6 +877,53 @@ const App: React.FC = () => {
     };
   }, [token]);
 
+  // Hand the session token to the Rust threat client (memory only there)
+  useEffect(() => {
+    if (!isTauri) return;
+    invoke("set_session_token", { token }).catch(() => {});
+  }, [token]);
+
+  // Backend rejected our token on a threat API call => sign out.
+  // A failed pin or signature check => results could not be verified.
+  useEffect(() => {
+    if (!isTauri) return;
+
+    let unlistenExpired: UnlistenFn | null = null;
+    let unlistenIntegrity: UnlistenFn | null = null;
+
+    listen("session_expired", () => {
+      handleLogout();
+    }).then((fn) => {
+      unlistenExpired = fn;
+    });
+
+    listen("threat_api_integrity_error", (event) => {
+      const payload = event.payload as any;
+      const ts = new Date().toISOString().slice(0, 16).replace("T", " ");
+
+      resultsUnverifiedRef.current = true;
+      setResultsUnverified(true);
+      setStatus("at_risk");
+
+      setLogs((prev) =>
+          pushLogDedup(prev, {
+            id: prev.length + 1,
+            timestamp: ts,
+            scan_type: activeScanRef.current ? "full_scan" : "realtime",
+            result: "threats_found",
+            details: `Results could not be verified: ${payload?.message ?? "integrity check failed"}.`,
+          })
+      );
+    }).then((fn) => {
+      unlistenIntegrity = fn;
+    });
+
+    return () => {
+      if (unlistenExpired) unlistenExpired();
+      if (unlistenIntegrity) unlistenIntegrity();
+    };
+  }, []);
+
   const handleLoginSuccess = (newToken?: string) => {
     if (typeof window !== "undefined" && newToken) {
       window.localStorage.setItem(STORAGE_KEYS.token, newToken);
@@ -905,7 +1058,12 @@ const App: React.FC = () => {
             )}
 
             <div className="flex-1 flex flex-col min-h-0">
-              {isAntivirusView && <HeaderBar realtimeEnabled={realtimeEnabled} />}
+              {isAntivirusView && (
+                  <HeaderBar
+                      realtimeEnabled={realtimeEnabled}
+                      resultsUnverified={resultsUnverified}
+                  />
+              )}
 
               <main className="flex-1 overflow-y-auto bg-[#F6F6FD] px-[20px] py-[20px]">
                 {view === "antivirus_dashboard" && (
@@ -929,6 +1087,7 @@ const App: React.FC = () => {
                         quarantine={quarantine}
                         onViewThreats={handleOpenThreatsModal}
                         onRestoreQuarantine={handleRestoreQuarantine}
+                        onReportFalsePositive={handleReportFalsePositive}
                         onDeleteQuarantine={handleDeleteQuarantine}
                         onClearLogs={handleClearLogs}
                     />